@@ -0,0 +1,86 @@
+//! Benchmarks of the board operations every piece move and lock goes through.
+
+use std::hint::black_box;
+
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use rust_tetris::board::{BOARD_HEIGHT, BOARD_WIDTH, Board};
+use rust_tetris::ruleset::Ruleset;
+use rust_tetris::tetromino::{RotationSystem, Shape, Tetromino};
+
+const SHAPES: [Shape; 7] = [
+    Shape::I,
+    Shape::O,
+    Shape::T,
+    Shape::S,
+    Shape::Z,
+    Shape::J,
+    Shape::L,
+];
+
+/// Returns a board with a ragged stack of garbage 12 rows high.
+fn stack() -> Board {
+    let mut board = Board::new();
+    let mut rng = fastrand::Rng::with_seed(1);
+    for y in BOARD_HEIGHT - 12..BOARD_HEIGHT {
+        board.set_row(y, board.full_row() & !(1 << rng.usize(..BOARD_WIDTH)));
+    }
+    board
+}
+
+/// Returns every shape in every rotation at every position of the board,
+/// most of them fitting on an empty board.
+fn pieces() -> Vec<Tetromino> {
+    let scale = Ruleset::versus().piece_scale();
+    let mut pieces = Vec::new();
+    for shape in SHAPES {
+        for rotation in 0..4 {
+            for y in -1..BOARD_HEIGHT as i32 {
+                for x in -2..BOARD_WIDTH as i32 {
+                    let mut piece =
+                        Tetromino::with_rotation_system((x, y), shape, RotationSystem::Srs);
+                    piece.rotation = rotation;
+                    piece.scale = scale;
+                    pieces.push(piece);
+                }
+            }
+        }
+    }
+    pieces
+}
+
+fn can_place(c: &mut Criterion) {
+    let pieces = pieces();
+    let mut group = c.benchmark_group("can_place");
+    for (name, board) in [("empty", Board::new()), ("stack", stack())] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                pieces
+                    .iter()
+                    .filter(|piece| black_box(&board).can_place(piece))
+                    .count()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn clear_lines(c: &mut Criterion) {
+    let mut group = c.benchmark_group("clear_lines");
+    for lines in [0, 1, 4] {
+        let mut board = stack();
+        for y in BOARD_HEIGHT - lines..BOARD_HEIGHT {
+            board.set_row(y, board.full_row());
+        }
+        group.bench_function(format!("{lines} lines"), |b| {
+            b.iter_batched_ref(
+                || board.clone(),
+                |board| board.clear_lines(),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, can_place, clear_lines);
+criterion_main!(benches);
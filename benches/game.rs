@@ -0,0 +1,84 @@
+//! Benchmarks of the game engine, from a single hard drop to whole games
+//! played headlessly on fixed seeds.
+
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use rust_tetris::board::{BOARD_HEIGHT, Board};
+use rust_tetris::bot::{Bot, Weights};
+use rust_tetris::game::{Game, GameState};
+use rust_tetris::input::Input;
+use rust_tetris::randomizer::Randomizer;
+use rust_tetris::ruleset::Ruleset;
+use rust_tetris::tetromino::Shape;
+
+/// How many frames the headless games run for, two minutes of play.
+const FRAMES: u64 = 60 * 120;
+
+/// The seeds of the headless games.
+const SEEDS: [u64; 3] = [1, 2, 3];
+
+fn hard_lock(c: &mut Criterion) {
+    let mut board = Board::new();
+    board.push_garbage(10, 4);
+    let mut group = c.benchmark_group("hard_lock");
+    for (name, board) in [("empty", Board::new()), ("stack", board)] {
+        let mut game = Game::from_position(
+            Ruleset::versus(),
+            board,
+            Randomizer::sequence(vec![Shape::I; BOARD_HEIGHT]),
+        );
+        // Hard lock only moves the piece, so it can be put back every time
+        let piece = game.current_piece.clone();
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                game.current_piece = piece.clone();
+                game.hard_lock();
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Plays a game on the engine alone: every piece is moved a few columns
+/// and hard dropped, so no time goes to deciding moves.
+fn drop_game(seed: u64) -> u32 {
+    let mut game = Game::new(Ruleset::versus(), seed);
+    while matches!(game.state, GameState::Playing) && game.frame < FRAMES {
+        let (direction, taps) = match game.pieces % 2 {
+            0 => (Input::LEFT, game.pieces % 5),
+            _ => (Input::RIGHT, game.pieces % 4),
+        };
+        for _ in 0..taps {
+            game.update(direction);
+            game.update(Input::NONE);
+        }
+        game.update(Input::HARD_DROP);
+        game.update(Input::NONE);
+    }
+    game.pieces
+}
+
+/// Plays a game with a bot placing pieces as fast as it can, searching
+/// the current piece only.
+fn bot_game(seed: u64) -> u32 {
+    let mut game = Game::new(Ruleset::versus(), seed);
+    let mut bot = Bot::new(Weights::default(), f64::INFINITY);
+    bot.lookahead = 0;
+    bot.play(&mut game, FRAMES);
+    game.pieces
+}
+
+fn headless(c: &mut Criterion) {
+    let mut group = c.benchmark_group("headless");
+    group.sample_size(10);
+    for (name, play) in [("drops", drop_game as fn(u64) -> u32), ("bot", bot_game)] {
+        let pieces: u32 = SEEDS.iter().map(|&seed| play(seed)).sum();
+        group.throughput(Throughput::Elements(u64::from(pieces)));
+        group.bench_function(name, |b| {
+            b.iter(|| SEEDS.iter().map(|&seed| play(seed)).sum::<u32>())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, hard_lock, headless);
+criterion_main!(benches);
@@ -0,0 +1,64 @@
+//! Benchmarks of placement enumeration, the core of the bot's search.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use rust_tetris::board::{BOARD_HEIGHT, BOARD_WIDTH, Board};
+use rust_tetris::movegen;
+use rust_tetris::ruleset::Ruleset;
+use rust_tetris::tetromino::Shape;
+
+const SHAPES: [Shape; 7] = [
+    Shape::I,
+    Shape::O,
+    Shape::T,
+    Shape::S,
+    Shape::Z,
+    Shape::J,
+    Shape::L,
+];
+
+/// Returns a board with a ragged stack 8 rows high, full of overhangs to
+/// tuck and spin under.
+fn stack() -> Board {
+    let mut board = Board::new();
+    let mut rng = fastrand::Rng::with_seed(1);
+    for y in BOARD_HEIGHT - 8..BOARD_HEIGHT {
+        for x in 0..BOARD_WIDTH as i32 {
+            if rng.u8(..3) != 0 {
+                board.fill_pos(x, y as i32);
+            }
+        }
+    }
+    board
+}
+
+fn placements(c: &mut Criterion) {
+    let ruleset = Ruleset::versus();
+    let mut group = c.benchmark_group("placements");
+    for (name, board) in [("empty", Board::new()), ("stack", stack())] {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                SHAPES
+                    .iter()
+                    .map(|&shape| movegen::placements(&board, shape, &ruleset).len())
+                    .sum::<usize>()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn hard_drops(c: &mut Criterion) {
+    let ruleset = Ruleset::versus();
+    let board = stack();
+    c.bench_function("hard_drops", |b| {
+        b.iter(|| {
+            SHAPES
+                .iter()
+                .map(|&shape| movegen::hard_drops(&board, shape, &ruleset).len())
+                .sum::<usize>()
+        })
+    });
+}
+
+criterion_group!(benches, placements, hard_drops);
+criterion_main!(benches);
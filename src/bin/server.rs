@@ -0,0 +1,53 @@
+//! Headless match server.
+//!
+//! Usage: `server [ADDR] [--garbage clean|random|messy:PERCENT] [--players N]
+//! [--results FILE]`
+//!
+//! Clients join with `rust_tetris --join ADDR` and are grouped in the order
+//! they connect. Groups of two play versus matches, larger groups, set with
+//! `--players`, play royale matches.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use rust_tetris::garbage::HoleStrategy;
+use rust_tetris::server::{self, ServerConfig};
+
+/// The address listened on when none is given.
+const DEFAULT_ADDR: &str = "0.0.0.0:7878";
+
+fn parse_args() -> Result<ServerConfig, String> {
+    let mut config = ServerConfig {
+        addr: DEFAULT_ADDR.to_string(),
+        garbage: HoleStrategy::Clean,
+        players: 2,
+        results: None,
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("Missing value for {}", arg));
+        match arg.as_str() {
+            "--garbage" => config.garbage = HoleStrategy::parse(&value()?)?,
+            "--players" => {
+                config.players = match value()?.parse() {
+                    Ok(players @ 2..=99) => players,
+                    _ => return Err("--players takes a number from 2 to 99".to_string()),
+                }
+            }
+            "--results" => config.results = Some(PathBuf::from(value()?)),
+            _ if !arg.starts_with("--") => config.addr = arg,
+            _ => return Err(format!("Unknown option {}", arg)),
+        }
+    }
+    Ok(config)
+}
+
+fn main() -> ExitCode {
+    match parse_args().and_then(server::run) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
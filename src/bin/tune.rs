@@ -0,0 +1,70 @@
+//! Headless tuner for the bot's evaluation weights.
+//!
+//! Usage: `tune [--algorithm ga|cmaes] [--generations N] [--population N]
+//! [--games N] [--pieces N] [--pressure LINES] [--lookahead N] [--threads N]
+//! [--seed N] [--output FILE]`
+//!
+//! Every generation, each candidate plays `--games` games of at most
+//! `--pieces` pieces, receiving `--pressure` garbage lines per piece. The
+//! best weights found are written to `--output`, which the game loads with
+//! `rust_tetris --bot-weights FILE`.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::thread;
+
+use rust_tetris::tune::{self, Algorithm, TuneConfig};
+
+/// The file the weights are written to when none is given.
+const DEFAULT_OUTPUT: &str = "weights.txt";
+
+/// Parses the value of a numeric option.
+fn number<T: FromStr>(arg: &str, value: String) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} takes a number, found '{}'", arg, value))
+}
+
+fn parse_args() -> Result<TuneConfig, String> {
+    let mut config = TuneConfig {
+        algorithm: Algorithm::Genetic,
+        generations: 20,
+        population: 24,
+        games: 8,
+        pieces: 300,
+        pressure: 0.2,
+        lookahead: 0,
+        threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+        seed: 1,
+        output: PathBuf::from(DEFAULT_OUTPUT),
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next().ok_or(format!("Missing value for {}", arg))?;
+        match arg.as_str() {
+            "--algorithm" => config.algorithm = Algorithm::parse(&value)?,
+            "--generations" => config.generations = number(&arg, value)?,
+            "--population" => config.population = number(&arg, value)?,
+            "--games" => config.games = number(&arg, value)?,
+            "--pieces" => config.pieces = number(&arg, value)?,
+            "--pressure" => config.pressure = number(&arg, value)?,
+            "--lookahead" => config.lookahead = number(&arg, value)?,
+            "--threads" => config.threads = number(&arg, value)?,
+            "--seed" => config.seed = number(&arg, value)?,
+            "--output" => config.output = PathBuf::from(value),
+            _ => return Err(format!("Unknown option {}", arg)),
+        }
+    }
+    Ok(config)
+}
+
+fn main() -> ExitCode {
+    match parse_args().and_then(tune::run) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
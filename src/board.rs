@@ -3,37 +3,58 @@
 //! This module provides the [`Board`] struct and associated functionality
 //! for representing and manipulating the Tetris playing field, including
 //! piece placement, collision detection, and row completion checks.
+//!
+//! Every row is stored as a bitmask, so collisions are checked a row of a
+//! piece at a time and full rows are found with a single comparison.
 
 use crate::tetromino::Tetromino;
-use std::mem;
 
 /// The width of the Tetris board in cells.
 pub const BOARD_WIDTH: usize = 10;
 
+/// The width of the widest board, see [`Board::with_width`].
+pub const MAX_BOARD_WIDTH: usize = 20;
+
 /// The height of the Tetris board in cells.
 pub const BOARD_HEIGHT: usize = 20;
 
 /// Represents the Tetris game board.
 ///
-/// The board is a 2D grid where `true` indicates an occupied cell
-/// and `false` indicates an empty cell. The coordinate system uses
-/// (0, 0) as the top-left corner.
+/// The board is a grid of rows, each a bitmask where bit `x` is set when
+/// column `x` is occupied. The coordinate system uses (0, 0) as the
+/// top-left corner.
+///
+/// Boards are [`BOARD_WIDTH`] cells wide unless created with
+/// [`Board::with_width`]. Columns past the width of the board are never
+/// filled.
 ///
 /// # Examples
 ///
 /// ```
+/// # use rust_tetris::board::Board;
 /// let board = Board::new();
-/// assert!(!board.grid[0][0]); // Initially all cells are empty
+/// assert!(!board.is_filled(0, 0)); // Initially all cells are empty
 /// ```
+#[derive(Clone)]
 pub struct Board {
-    /// The internal grid representation. Each cell is `true` if occupied, `false` if empty.
-    pub grid: [[bool; BOARD_WIDTH]; BOARD_HEIGHT],
+    /// The rows from the top, bit `x` of a row set when column `x` is occupied.
+    rows: [u32; BOARD_HEIGHT],
+    /// The game frame at which each cell was filled by a locking piece.
+    /// Cells filled any other way, and empty cells, hold `0`.
+    pub lock_frames: [[u64; MAX_BOARD_WIDTH]; BOARD_HEIGHT],
+    width: usize,
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Board {
     /// Creates a new empty board.
     ///
-    /// All cells in the grid are initialized to `false` (empty).
+    /// Every row starts empty, with no bits set.
     ///
     /// # Returns
     ///
@@ -42,15 +63,106 @@ impl Board {
     /// # Examples
     ///
     /// ```
+    /// # use rust_tetris::board::Board;
     /// let board = Board::new();
     /// assert!(board.pos_free(0, 0));
     /// ```
     pub fn new() -> Self {
+        Self::with_width(BOARD_WIDTH)
+    }
+
+    /// Creates a new empty board with a custom width.
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The number of columns, clamped to
+    ///   `BOARD_WIDTH..=MAX_BOARD_WIDTH`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_tetris::board::Board;
+    /// let board = Board::with_width(16);
+    /// assert_eq!(board.width(), 16);
+    /// assert!(board.pos_free(15, 0));
+    /// assert!(!board.pos_free(16, 0));
+    /// ```
+    pub fn with_width(width: usize) -> Self {
         Board {
-            grid: [[false; 10]; 20],
+            rows: [0; BOARD_HEIGHT],
+            lock_frames: [[0; MAX_BOARD_WIDTH]; BOARD_HEIGHT],
+            width: width.clamp(BOARD_WIDTH, MAX_BOARD_WIDTH),
         }
     }
 
+    /// Returns the number of columns of the board.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Returns the bitmask of a full row, with the bit of every column set.
+    pub fn full_row(&self) -> u32 {
+        (1 << self.width) - 1
+    }
+
+    /// Returns a row as a bitmask, bit `x` set when column `x` is occupied.
+    ///
+    /// # Arguments
+    ///
+    /// * `y` - The row index, below [`BOARD_HEIGHT`]
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_tetris::board::Board;
+    /// let mut board = Board::new();
+    /// board.fill_pos(0, 19);
+    /// board.fill_pos(2, 19);
+    /// assert_eq!(board.row(19), 0b101);
+    /// ```
+    pub fn row(&self, y: usize) -> u32 {
+        self.rows[y]
+    }
+
+    /// Replaces a row. Bits past the width of the board are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `y` - The row index, below [`BOARD_HEIGHT`]
+    /// * `row` - The bitmask of the occupied columns
+    pub fn set_row(&mut self, y: usize, row: u32) {
+        self.rows[y] = row & self.full_row();
+    }
+
+    /// Returns every cell of the board, `true` where it is occupied.
+    ///
+    /// Columns past the width of the board are always `false`. Reading rows
+    /// with [`row`](Board::row) is cheaper when the bitmask will do.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_tetris::board::Board;
+    /// let mut board = Board::new();
+    /// board.fill_pos(3, 19);
+    /// let grid = board.grid();
+    /// assert!(grid[19][3]);
+    /// assert!(!grid[19][4]);
+    /// ```
+    pub fn grid(&self) -> [[bool; MAX_BOARD_WIDTH]; BOARD_HEIGHT] {
+        std::array::from_fn(|y| std::array::from_fn(|x| self.is_filled(x, y)))
+    }
+
+    /// Checks if a cell is occupied.
+    ///
+    /// # Arguments
+    ///
+    /// * `x` - The column index, below the width of the board
+    /// * `y` - The row index, below [`BOARD_HEIGHT`]
+    pub fn is_filled(&self, x: usize, y: usize) -> bool {
+        self.rows[y] >> x & 1 != 0
+    }
+
     /// Checks if a position is within the board boundaries.
     ///
     /// # Arguments
@@ -66,18 +178,20 @@ impl Board {
     /// # Examples
     ///
     /// ```
-    /// assert!(Board::pos_in_bounds(0, 0));
-    /// assert!(Board::pos_in_bounds(9, 19));
-    /// assert!(!Board::pos_in_bounds(-1, 0));
-    /// assert!(!Board::pos_in_bounds(10, 20));
+    /// # use rust_tetris::board::Board;
+    /// let board = Board::new();
+    /// assert!(board.pos_in_bounds(0, 0));
+    /// assert!(board.pos_in_bounds(9, 19));
+    /// assert!(!board.pos_in_bounds(-1, 0));
+    /// assert!(!board.pos_in_bounds(10, 20));
     /// ```
-    pub fn pos_in_bounds(x: i32, y: i32) -> bool {
+    pub fn pos_in_bounds(&self, x: i32, y: i32) -> bool {
         if x < 0 || y < 0 {
             return false;
         }
         let x_usize = x as usize;
         let y_usize = y as usize;
-        x_usize < BOARD_WIDTH && y_usize < BOARD_HEIGHT
+        x_usize < self.width && y_usize < BOARD_HEIGHT
     }
 
     /// Checks if a position is free (empty and within bounds).
@@ -95,21 +209,22 @@ impl Board {
     /// # Examples
     ///
     /// ```
+    /// # use rust_tetris::board::Board;
     /// let mut board = Board::new();
     /// assert!(board.pos_free(5, 5));
     /// board.fill_pos(5, 5);
     /// assert!(!board.pos_free(5, 5));
     /// ```
     pub fn pos_free(&self, x: i32, y: i32) -> bool {
-        if !(Self::pos_in_bounds(x, y)) {
+        if !(self.pos_in_bounds(x, y)) {
             return false;
         }
-        !self.grid[y as usize][x as usize]
+        !self.is_filled(x as usize, y as usize)
     }
 
     /// Checks if a row is completely filled.
     ///
-    /// A row is considered full when all cells in that row are occupied (`true`).
+    /// A row is considered full when the bit of every column is set.
     ///
     /// # Arguments
     ///
@@ -123,6 +238,7 @@ impl Board {
     /// # Examples
     ///
     /// ```
+    /// # use rust_tetris::board::{BOARD_WIDTH, Board};
     /// let mut board = Board::new();
     /// // Fill the bottom row
     /// for x in 0..BOARD_WIDTH {
@@ -132,7 +248,10 @@ impl Board {
     /// assert!(!board.is_row_full(18));
     /// ```
     pub fn is_row_full(&self, y: i32) -> bool {
-        self.grid[y as usize].iter().all(|&pos| pos)
+        usize::try_from(y)
+            .ok()
+            .and_then(|y| self.rows.get(y))
+            .is_some_and(|&row| row == self.full_row())
     }
 
     /// Clears a row (sets all positions to free) and propogates every above row down by 1
@@ -144,31 +263,24 @@ impl Board {
     /// # Examples
     ///
     /// ```
+    /// # use rust_tetris::board::{BOARD_WIDTH, Board};
     /// let mut board = Board::new();
     /// // Fill the bottom row
     /// for x in 0..BOARD_WIDTH {
     ///     board.fill_pos(x as i32, 19);
     /// }
-    /// board.clear_row_and_prop(10);
+    /// board.clear_row(19);
+    /// assert!(board.is_empty());
     /// ```
     pub fn clear_row(&mut self, y: i32) {
-        let mut temp_row = [false; 10];
-        if !(Self::pos_in_bounds(0, y)) {
+        if !(self.pos_in_bounds(0, y)) {
             return;
         }
-        self.grid
-            .iter_mut()
-            .take(y as usize + 1)
-            .enumerate()
-            .for_each(|(i, row)| match i {
-                0 => {
-                    row.iter_mut().for_each(|pos| {
-                        *pos = false;
-                    });
-                    temp_row = *row;
-                }
-                _ => mem::swap(row, &mut temp_row),
-            });
+        let y = y as usize;
+        self.rows.copy_within(0..y, 1);
+        self.rows[0] = 0;
+        self.lock_frames.copy_within(0..y, 1);
+        self.lock_frames[0] = [0; MAX_BOARD_WIDTH];
     }
 
     pub fn clear_until_not_full(&mut self, y: i32) -> u32 {
@@ -180,20 +292,105 @@ impl Board {
         cleared_count
     }
 
+    /// Clears every full row, moving the rows above them down.
+    ///
+    /// # Returns
+    ///
+    /// The number of rows cleared.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_tetris::board::{BOARD_WIDTH, Board};
+    /// let mut board = Board::new();
+    /// for x in 0..BOARD_WIDTH as i32 {
+    ///     board.fill_pos(x, 19);
+    ///     board.fill_pos(x, 17);
+    /// }
+    /// board.fill_pos(0, 18);
+    /// assert_eq!(board.clear_lines(), 2);
+    /// assert_eq!(board.row(19), 1);
+    /// ```
     pub fn clear_lines(&mut self) -> u32 {
-        let mut cleared_count: u32 = 0;
-        for i in (0..BOARD_HEIGHT as i32).rev() {
-            cleared_count += self.clear_until_not_full(i);
-            if cleared_count as usize >= BOARD_HEIGHT {
-                return cleared_count;
-            }
+        let full = self.full_row();
+        // Every row is copied to the bottom-most free slot, which only moves
+        // up past the rows that are kept
+        let mut bottom = BOARD_HEIGHT;
+        for y in (0..BOARD_HEIGHT).rev() {
+            self.rows[bottom - 1] = self.rows[y];
+            self.lock_frames[bottom - 1] = self.lock_frames[y];
+            bottom -= usize::from(self.rows[y] != full);
         }
-        cleared_count
+        self.rows[..bottom].fill(0);
+        self.lock_frames[..bottom].fill([0; MAX_BOARD_WIDTH]);
+        bottom as u32
+    }
+
+    /// Pushes garbage rows in from the bottom of the board.
+    ///
+    /// Every row moves up by `lines`, and the bottom `lines` rows are filled
+    /// except for `hole_column`.
+    ///
+    /// # Arguments
+    ///
+    /// * `lines` - The number of garbage rows to add
+    /// * `hole_column` - The column left empty in every garbage row, below
+    ///   the width of the board
+    ///
+    /// # Returns
+    ///
+    /// `true` if occupied cells were pushed off the top of the board.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_tetris::board::Board;
+    /// let mut board = Board::new();
+    /// assert!(!board.push_garbage(2, 0));
+    /// assert!(board.pos_free(0, 19));
+    /// assert!(!board.pos_free(1, 18));
+    /// ```
+    pub fn push_garbage(&mut self, lines: u32, hole_column: usize) -> bool {
+        debug_assert!(
+            hole_column < self.width,
+            "Hole column {} is outside a board {} wide",
+            hole_column,
+            self.width
+        );
+        // Garbage rows pushed straight past the top of the board overflow too
+        let overflow = lines as usize > BOARD_HEIGHT;
+        let lines = (lines as usize).min(BOARD_HEIGHT);
+        let overflow = overflow || self.rows[..lines].iter().any(|&row| row != 0);
+        self.rows.copy_within(lines.., 0);
+        self.lock_frames.copy_within(lines.., 0);
+        let garbage = self.full_row() & !(1 << hole_column);
+        self.rows[BOARD_HEIGHT - lines..].fill(garbage);
+        self.lock_frames[BOARD_HEIGHT - lines..].fill([0; MAX_BOARD_WIDTH]);
+        overflow
+    }
+
+    /// Checks if every cell of the board is empty.
+    ///
+    /// # Returns
+    ///
+    /// `true` if no cell is occupied, `false` otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_tetris::board::Board;
+    /// let mut board = Board::new();
+    /// assert!(board.is_empty());
+    /// board.fill_pos(0, 19);
+    /// assert!(!board.is_empty());
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.rows.iter().all(|&row| row == 0)
     }
 
     /// Fills (occupies) a position on the board.
     ///
-    /// Sets the specified cell to `true`, marking it as occupied.
+    /// Sets the bit of the specified cell, marking it as occupied.
     ///
     /// # Arguments
     ///
@@ -208,16 +405,17 @@ impl Board {
     /// # Examples
     ///
     /// ```
+    /// # use rust_tetris::board::Board;
     /// let mut board = Board::new();
     /// assert!(board.fill_pos(0, 0));
     /// assert!(!board.pos_free(0, 0));
     /// assert!(!board.fill_pos(-1, 0)); // Out of bounds
     /// ```
     pub fn fill_pos(&mut self, x: i32, y: i32) -> bool {
-        if !Self::pos_in_bounds(x, y) {
+        if !self.pos_in_bounds(x, y) {
             return false;
         }
-        self.grid[y as usize][x as usize] = true;
+        self.rows[y as usize] |= 1 << x;
         true
     }
 
@@ -237,14 +435,28 @@ impl Board {
     /// # Examples
     ///
     /// ```
+    /// # use rust_tetris::board::Board;
+    /// # use rust_tetris::tetromino::{Shape, Tetromino};
     /// let board = Board::new();
-    /// let piece = Tetromino::new(/* ... */);
+    /// let piece = Tetromino::new((3, 0), Shape::T);
     /// if board.can_place(&piece) {
     ///     // Safe to place the piece
     /// }
     /// ```
     pub fn can_place(&self, piece: &Tetromino) -> bool {
-        piece.get_cords().iter().all(|&(x, y)| self.pos_free(x, y))
+        let (left, top, masks) = piece.row_masks();
+        if left < 0 || top < 0 || left as usize >= self.width {
+            return false;
+        }
+        // Columns past the width act as walls
+        let walls = !0u64 << self.width;
+        masks
+            .iter()
+            .take_while(|&&mask| mask != 0)
+            .zip(top as usize..)
+            .all(|(&mask, y)| {
+                y < BOARD_HEIGHT && (u64::from(self.rows[y]) | walls) & u64::from(mask) << left == 0
+            })
     }
 
     /// Locks a tetromino piece onto the board.
@@ -255,21 +467,27 @@ impl Board {
     /// # Arguments
     ///
     /// * `piece` - A reference to the tetromino to lock onto the board
+    /// * `frame` - The game frame of the lock, recorded in [`lock_frames`]
     ///
     /// # Examples
     ///
     /// ```
+    /// # use rust_tetris::board::Board;
+    /// # use rust_tetris::tetromino::{Shape, Tetromino};
     /// let mut board = Board::new();
-    /// let piece = Tetromino::new(/* ... */);
+    /// let piece = Tetromino::new((3, 0), Shape::T);
     /// if board.can_place(&piece) {
-    ///     board.lock_piece(&piece);
+    ///     board.lock_piece(&piece, 0);
     /// }
     /// ```
     ///
     /// [`can_place`]: Board::can_place
-    pub fn lock_piece(&mut self, piece: &Tetromino) {
-        piece.get_cords().iter().for_each(|&(x, y)| {
-            self.fill_pos(x, y);
+    /// [`lock_frames`]: Board::lock_frames
+    pub fn lock_piece(&mut self, piece: &Tetromino, frame: u64) {
+        piece.cells().for_each(|(x, y)| {
+            if self.fill_pos(x, y) {
+                self.lock_frames[y as usize][x as usize] = frame;
+            }
         })
     }
 }
@@ -0,0 +1,601 @@
+//! Bot module for a computer player.
+//!
+//! A [`Bot`] picks where each piece goes by enumerating every reachable
+//! placement with [`movegen`](crate::movegen), then searching a few pieces
+//! deep through the preview and the hold piece, keeping only the most
+//! promising boards at each step. Boards are scored by [`evaluate`] with a
+//! set of [`Weights`].
+//!
+//! The bot plays like a person would: it reads the [`Game`] every frame and
+//! returns the buttons to hold, paced to a number of pieces per second, so it
+//! can take a seat in any match that takes [`Input`]s.
+
+use std::collections::VecDeque;
+
+use crate::board::{BOARD_HEIGHT, Board};
+use crate::game::{Game, GameState, LineClear, Phase, TSpin};
+use crate::input::Input;
+use crate::movegen::{self, Move, Placement};
+use crate::ruleset::{FRAME_RATE, HardDrop, Ruleset};
+use crate::tetromino::{Direction, Shape, Tetromino};
+use crate::versus::attack;
+
+/// The score of a placement that leaves no room for the next piece.
+const TOP_OUT: f64 = -1.0e9;
+
+/// How much each feature of a board counts towards its score. Features that
+/// hurt, like holes, should have negative weights.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Weights {
+    /// Per cell of the summed column heights.
+    pub aggregate_height: f64,
+    /// Per empty cell with a filled cell above it.
+    pub holes: f64,
+    /// Per cell of height difference between neighbouring columns.
+    pub bumpiness: f64,
+    /// Per cell of well depth, not counting the deepest well.
+    pub wells: f64,
+    /// Per slot a T piece could be spun into.
+    pub tspin_setups: f64,
+    /// Per line cleared.
+    pub lines: f64,
+    /// Per garbage line a clear would send, see [`attack`].
+    pub attack: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            aggregate_height: -0.51,
+            holes: -3.6,
+            bumpiness: -0.18,
+            wells: -0.3,
+            tspin_setups: 0.8,
+            lines: 0.2,
+            attack: 1.5,
+        }
+    }
+}
+
+impl Weights {
+    /// The names of the weights, in the order of [`Weights::to_array`].
+    pub const NAMES: [&str; 7] = [
+        "aggregate_height",
+        "holes",
+        "bumpiness",
+        "wells",
+        "tspin_setups",
+        "lines",
+        "attack",
+    ];
+
+    /// Returns the weights as an array, in the order of [`Weights::NAMES`].
+    pub fn to_array(&self) -> [f64; 7] {
+        [
+            self.aggregate_height,
+            self.holes,
+            self.bumpiness,
+            self.wells,
+            self.tspin_setups,
+            self.lines,
+            self.attack,
+        ]
+    }
+
+    /// Creates weights from an array, in the order of [`Weights::NAMES`].
+    pub fn from_array(values: [f64; 7]) -> Self {
+        let [
+            aggregate_height,
+            holes,
+            bumpiness,
+            wells,
+            tspin_setups,
+            lines,
+            attack,
+        ] = values;
+        Self {
+            aggregate_height,
+            holes,
+            bumpiness,
+            wells,
+            tspin_setups,
+            lines,
+            attack,
+        }
+    }
+
+    /// Parses weights from the text of a weights file: one `name: value`
+    /// line per weight, with `#` starting a comment line. Missing weights
+    /// keep their default value.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The contents of the weights file
+    ///
+    /// # Returns
+    ///
+    /// The parsed weights, or a message describing the first problem found.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_tetris::bot::Weights;
+    /// let weights = Weights::parse("# tuned\nholes: -5.5").unwrap();
+    /// assert_eq!(weights.holes, -5.5);
+    /// assert_eq!(Weights::parse(&weights.to_text()), Ok(weights));
+    /// ```
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut values = Self::default().to_array();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, value) = line
+                .split_once(':')
+                .ok_or(format!("Expected 'name: value', found '{}'", line))?;
+            let index = Self::NAMES
+                .iter()
+                .position(|&known| known == name.trim())
+                .ok_or(format!("Unknown weight '{}'", name.trim()))?;
+            values[index] = value
+                .trim()
+                .parse()
+                .map_err(|_| format!("Weight '{}' is not a number", value.trim()))?;
+        }
+        Ok(Self::from_array(values))
+    }
+
+    /// Returns the weights in the format read by [`Weights::parse`].
+    pub fn to_text(&self) -> String {
+        Self::NAMES
+            .iter()
+            .zip(self.to_array())
+            .map(|(name, value)| format!("{}: {}\n", name, value))
+            .collect()
+    }
+}
+
+/// Returns the score of a board: higher is better.
+///
+/// # Arguments
+///
+/// * `board` - The board to score
+/// * `weights` - How much each feature counts
+///
+/// # Examples
+///
+/// ```
+/// # use rust_tetris::board::Board;
+/// # use rust_tetris::bot::{Weights, evaluate};
+/// let weights = Weights::default();
+/// let mut holey = Board::new();
+/// holey.fill_pos(0, 18);
+/// let mut flat = Board::new();
+/// flat.fill_pos(0, 19);
+/// assert!(evaluate(&flat, &weights) > evaluate(&holey, &weights));
+/// ```
+pub fn evaluate(board: &Board, weights: &Weights) -> f64 {
+    let width = board.width();
+    let mut heights = vec![0; width];
+    // The columns with a filled cell in a row above, whose empty cells are holes
+    let mut covered = 0;
+    let mut holes = 0;
+    for y in 0..BOARD_HEIGHT {
+        let row = board.row(y);
+        let mut tops = row & !covered;
+        while tops != 0 {
+            heights[tops.trailing_zeros() as usize] = BOARD_HEIGHT - y;
+            tops &= tops - 1;
+        }
+        holes += (covered & !row).count_ones() as usize;
+        covered |= row;
+    }
+    let aggregate_height: usize = heights.iter().sum();
+    let bumpiness: usize = heights
+        .windows(2)
+        .map(|pair| pair[0].abs_diff(pair[1]))
+        .sum();
+
+    // A well is a column lower than both of its neighbours, the walls
+    // counting as full height. One deep well is kept open for clears.
+    let mut wells: Vec<usize> = (0..width)
+        .map(|x| {
+            let left = if x == 0 { BOARD_HEIGHT } else { heights[x - 1] };
+            let right = heights.get(x + 1).copied().unwrap_or(BOARD_HEIGHT);
+            left.min(right).saturating_sub(heights[x])
+        })
+        .collect();
+    wells.sort_unstable();
+    wells.pop();
+    let wells: usize = wells.iter().sum();
+
+    weights.aggregate_height * aggregate_height as f64
+        + weights.holes * holes as f64
+        + weights.bumpiness * bumpiness as f64
+        + weights.wells * wells as f64
+        + weights.tspin_setups * tspin_setups(board) as f64
+}
+
+/// Counts the slots a T piece pointing down could be spun into: the four
+/// cells of the T are empty, both corners beside its stem are filled, and
+/// so is at least one corner above its arms.
+fn tspin_setups(board: &Board) -> usize {
+    // The columns a stem could be in, with a neighbour on both sides
+    let inner = board.full_row() & !1 & !(1 << (board.width() - 1));
+    (2..BOARD_HEIGHT)
+        .map(|y| {
+            let (above, arms, stem) = (board.row(y - 2), board.row(y - 1), board.row(y));
+            let slots = !arms
+                & !(arms << 1)
+                & !(arms >> 1)
+                & !stem
+                & stem << 1
+                & stem >> 1
+                & !above
+                & (above << 1 | above >> 1);
+            (slots & inner).count_ones() as usize
+        })
+        .sum()
+}
+
+/// A board reached while searching, and how it was reached.
+#[derive(Clone)]
+struct Node {
+    board: Board,
+    hold: Option<Shape>,
+    /// The index of the piece in hand among the searched pieces.
+    next: usize,
+    streak: u32,
+    back_to_back: bool,
+    /// The score of the clears made on the way.
+    reward: f64,
+    /// The reward plus the score of the board.
+    score: f64,
+    /// The first placement made, and whether it needed a hold.
+    first: Option<(bool, Placement)>,
+}
+
+/// The placement the bot is working towards.
+struct Plan {
+    /// Whether the hold button must be pressed first.
+    hold: bool,
+    /// The cells of the target placement, sorted.
+    target: Vec<(i32, i32)>,
+    tspin: TSpin,
+    /// The moves left, valid while the piece is at `from`.
+    path: VecDeque<Move>,
+    from: Option<((i32, i32), u8)>,
+}
+
+/// A computer player.
+pub struct Bot {
+    /// How boards are scored.
+    pub weights: Weights,
+    /// How many pieces the bot places per second at most.
+    pub pps: f64,
+    /// How many preview pieces are searched, on top of the current one.
+    pub lookahead: usize,
+    /// How many boards are kept at each step of the search.
+    pub beam: usize,
+    plan: Option<Plan>,
+    previous: Input,
+    /// Frames since the last piece locked.
+    frames: u32,
+    pieces: u32,
+}
+
+impl Bot {
+    /// Creates a bot searching two preview pieces deep.
+    ///
+    /// # Arguments
+    ///
+    /// * `weights` - How boards are scored
+    /// * `pps` - How many pieces the bot places per second at most,
+    ///   `f64::INFINITY` for as fast as the inputs allow
+    pub fn new(weights: Weights, pps: f64) -> Self {
+        Self {
+            weights,
+            pps,
+            lookahead: 2,
+            beam: 6,
+            plan: None,
+            previous: Input::NONE,
+            frames: 0,
+            pieces: 0,
+        }
+    }
+
+    /// Returns the buttons to hold during the next frame of a game.
+    ///
+    /// Call it once per frame, right before the game is updated.
+    ///
+    /// # Arguments
+    ///
+    /// * `game` - The game the bot is playing
+    pub fn update(&mut self, game: &Game) -> Input {
+        self.frames = self.frames.saturating_add(1);
+        if game.pieces != self.pieces {
+            self.pieces = game.pieces;
+            self.frames = 0;
+            self.plan = None;
+        }
+        let input = self.next_input(game);
+        self.previous = input;
+        input
+    }
+
+    /// Plays a game on its own until it ends.
+    ///
+    /// # Arguments
+    ///
+    /// * `game` - The game to play
+    /// * `max_frames` - How many frames to play at most
+    pub fn play(&mut self, game: &mut Game, max_frames: u64) {
+        while matches!(game.state, GameState::Playing) && game.frame < max_frames {
+            let input = self.update(game);
+            game.update(input);
+        }
+    }
+
+    fn next_input(&mut self, game: &Game) -> Input {
+        if !matches!(game.state, GameState::Playing) || game.phase != Phase::Falling {
+            return Input::NONE;
+        }
+        let Some(piece) = &game.current_piece else {
+            return Input::NONE;
+        };
+        // Buttons are released between presses so each one registers
+        if self.previous != Input::NONE && self.previous != Input::SOFT_DROP {
+            return Input::NONE;
+        }
+
+        if self.plan.is_none() {
+            self.plan = self.choose(game, game.can_hold());
+        }
+        let Some(plan) = &mut self.plan else {
+            return self.lock(game, piece);
+        };
+        if plan.hold {
+            plan.hold = false;
+            plan.from = None;
+            return Input::HOLD;
+        }
+
+        let state = (piece.pos, piece.rotation);
+        if plan.from != Some(state) && !self.find_path(game, piece) {
+            // The target cannot be reached any more
+            self.plan = self.choose(game, false);
+            if !self.find_path(game, piece) {
+                return self.lock(game, piece);
+            }
+        }
+        let Some(plan) = &mut self.plan else {
+            return self.lock(game, piece);
+        };
+        let Some(&step) = plan.path.front() else {
+            return self.lock(game, piece);
+        };
+        let Some(next) = movegen::apply(&game.board, piece, step) else {
+            plan.from = None;
+            return Input::NONE;
+        };
+        match step {
+            Move::Left => self.step(next, Input::LEFT),
+            Move::Right => self.step(next, Input::RIGHT),
+            Move::RotateCw => self.step(next, Input::ROTATE_CW),
+            Move::RotateCcw => self.step(next, Input::ROTATE_CCW),
+            Move::Drop if game.ruleset.hard_drop == HardDrop::Sonic => {
+                self.step(next, Input::HARD_DROP)
+            }
+            Move::Down | Move::Drop => {
+                // Soft dropping onto the stack can lock the piece at once,
+                // without lock delay or with `soft_drop_locks`, so it is
+                // left to fall the last row by itself
+                let mut below = next.clone();
+                below.move_piece(Direction::Down);
+                let landing = !game.board.can_place(&below);
+                if landing && piece.pos.1 + 1 == next.pos.1 {
+                    self.step(next, Input::NONE)
+                } else {
+                    self.step(next, Input::SOFT_DROP)
+                }
+            }
+        }
+    }
+
+    /// Records where a step will take the piece and returns its input.
+    fn step(&mut self, next: Tetromino, input: Input) -> Input {
+        if let Some(plan) = &mut self.plan {
+            // Once the piece gets there the step is done
+            plan.path.pop_front();
+            plan.from = Some((next.pos, next.rotation));
+        }
+        input
+    }
+
+    /// Searches for a path from the current piece to the planned target.
+    ///
+    /// # Returns
+    ///
+    /// Whether a path was found.
+    fn find_path(&mut self, game: &Game, piece: &Tetromino) -> bool {
+        let Some(plan) = &mut self.plan else {
+            return false;
+        };
+        let found = movegen::placements_from(&game.board, piece)
+            .into_iter()
+            .find(|placement| {
+                placement.tspin == plan.tspin && sorted_cells(&placement.piece) == plan.target
+            });
+        let Some(placement) = found else {
+            return false;
+        };
+        plan.path = trim_drops(placement.path);
+        plan.from = Some((piece.pos, piece.rotation));
+        true
+    }
+
+    /// Returns the input locking the piece where it is, once the bot's pace
+    /// allows it.
+    fn lock(&self, game: &Game, piece: &Tetromino) -> Input {
+        if f64::from(self.frames) < FRAME_RATE / self.pps {
+            return Input::NONE;
+        }
+        let mut below = piece.clone();
+        below.move_piece(Direction::Down);
+        match game.ruleset.hard_drop {
+            HardDrop::Lock => Input::HARD_DROP,
+            HardDrop::Sonic if game.board.can_place(&below) => Input::HARD_DROP,
+            HardDrop::Sonic | HardDrop::None => Input::SOFT_DROP,
+        }
+    }
+
+    /// Searches for the best placement of the current piece.
+    ///
+    /// # Arguments
+    ///
+    /// * `game` - The game the bot is playing
+    /// * `hold` - Whether the piece may be swapped with the hold piece
+    fn choose(&self, game: &Game, hold: bool) -> Option<Plan> {
+        let piece = game.current_piece.as_ref()?;
+        let pieces: Vec<Shape> = std::iter::once(piece.shape)
+            .chain(game.next.iter().copied())
+            .take(1 + self.lookahead.min(game.ruleset.preview))
+            .collect();
+        let mut beam = vec![Node {
+            board: game.board.clone(),
+            hold: game.hold,
+            next: 0,
+            streak: game.streak(),
+            back_to_back: game.back_to_back(),
+            reward: 0.0,
+            score: 0.0,
+            first: None,
+        }];
+
+        loop {
+            let mut children = Vec::new();
+            for node in &beam {
+                let first = node.first.is_none();
+                for (held, shape, next, hold_after) in
+                    choices(node, &pieces, hold || !first, &game.ruleset)
+                {
+                    // Later pieces are only tried dropped straight down,
+                    // which keeps the search fast
+                    let found = match (first, held) {
+                        (true, false) => movegen::placements_from(&node.board, piece),
+                        (true, true) => movegen::placements(&node.board, shape, &game.ruleset),
+                        _ => movegen::hard_drops(&node.board, shape, &game.ruleset),
+                    };
+                    for placement in found {
+                        let mut child = self.place(node, &placement, pieces.get(next), game);
+                        child.next = next;
+                        child.hold = hold_after;
+                        if first {
+                            child.first = Some((held, placement));
+                        }
+                        children.push(child);
+                    }
+                }
+            }
+            if children.is_empty() {
+                break;
+            }
+            children.sort_by(|a, b| b.score.total_cmp(&a.score));
+            children.truncate(self.beam.max(1));
+            beam = children;
+        }
+
+        let (hold, placement) = beam.into_iter().next()?.first?;
+        Some(Plan {
+            hold,
+            target: sorted_cells(&placement.piece),
+            tspin: placement.tspin,
+            path: trim_drops(placement.path),
+            from: (!hold).then_some((piece.pos, piece.rotation)),
+        })
+    }
+
+    /// Returns the node reached by locking a placement.
+    fn place(&self, node: &Node, placement: &Placement, next: Option<&Shape>, game: &Game) -> Node {
+        let mut child = node.clone();
+        child.board.lock_piece(&placement.piece, 0);
+        let lines = child.board.clear_lines() / placement.piece.scale as u32;
+        if lines == 0 {
+            child.streak = 0;
+        } else {
+            let mut clear = LineClear {
+                lines,
+                level: game.level,
+                combo: 1,
+                soft_drop_frames: 0,
+                perfect_clear: child.board.is_empty(),
+                tspin: placement.tspin,
+                streak: child.streak,
+                back_to_back: false,
+            };
+            clear.back_to_back = child.back_to_back && clear.is_difficult();
+            child.back_to_back = clear.is_difficult();
+            child.streak += 1;
+            child.reward +=
+                self.weights.lines * lines as f64 + self.weights.attack * attack(&clear) as f64;
+        }
+        let topped_out = next.is_some_and(|&shape| {
+            !child
+                .board
+                .can_place(&movegen::spawn(&child.board, shape, &game.ruleset))
+        });
+        child.score = if topped_out {
+            TOP_OUT
+        } else {
+            child.reward + evaluate(&child.board, &self.weights)
+        };
+        child
+    }
+}
+
+/// Returns the pieces that can be played from a node: whether the hold is
+/// used, the shape played, the index of the next piece in hand and the hold
+/// piece afterwards.
+fn choices(
+    node: &Node,
+    pieces: &[Shape],
+    hold: bool,
+    ruleset: &Ruleset,
+) -> Vec<(bool, Shape, usize, Option<Shape>)> {
+    let Some(&current) = pieces.get(node.next) else {
+        return Vec::new();
+    };
+    let mut found = vec![(false, current, node.next + 1, node.hold)];
+    if hold && ruleset.hold {
+        match node.hold {
+            Some(held) if held != current => {
+                found.push((true, held, node.next + 1, Some(current)));
+            }
+            Some(_) => {}
+            None => {
+                if let Some(&after) = pieces.get(node.next + 1) {
+                    found.push((true, after, node.next + 2, Some(current)));
+                }
+            }
+        }
+    }
+    found
+}
+
+fn sorted_cells(piece: &Tetromino) -> Vec<(i32, i32)> {
+    let mut cells: Vec<(i32, i32)> = piece.cells().collect();
+    cells.sort_unstable();
+    cells
+}
+
+/// Drops the moves at the end of a path that only bring the piece down,
+/// which locking does anyway.
+fn trim_drops(path: Vec<Move>) -> VecDeque<Move> {
+    let mut path = VecDeque::from(path);
+    while matches!(path.back(), Some(Move::Down | Move::Drop)) {
+        path.pop_back();
+    }
+    path
+}
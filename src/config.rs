@@ -0,0 +1,256 @@
+//! Config module loading the player's settings from a TOML file.
+//!
+//! The file lives in the user's config directory, see [`Config::path`], and
+//! is written with the defaults the first time the game starts:
+//!
+//! ```toml
+//! volume = 0.8
+//!
+//! [keys]
+//! left = ["Left"]
+//! rotate_cw = ["Up", "X"]
+//! # ...
+//!
+//! [handling]
+//! das = 10
+//! arr = 2
+//! sdf = 2
+//!
+//! [visuals]
+//! preview = 5
+//! ghost = true
+//! grid = false
+//! theme = "classic"
+//!
+//! [window]
+//! width = 600
+//! height = 800
+//! ```
+//!
+//! Keys are named as in [`controls`](crate::controls), and any number of
+//! keys can be bound to an action. Anything left out takes its default.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use macroquad::prelude::KeyCode;
+use serde::{Deserialize, Serialize};
+
+use crate::controls::{ACTIONS, KeyMap, key_from_name, key_name};
+use crate::input::Input;
+use crate::renderer::Visuals;
+use crate::ruleset::{Handling, Mode, Ruleset};
+
+/// The keys bound to every action of one player, by name.
+///
+/// Actions left out of the file have no keys.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Bindings {
+    pub left: Vec<String>,
+    pub right: Vec<String>,
+    pub soft_drop: Vec<String>,
+    pub hard_drop: Vec<String>,
+    pub rotate_cw: Vec<String>,
+    pub rotate_ccw: Vec<String>,
+    pub hold: Vec<String>,
+    pub target: Vec<String>,
+}
+
+impl Bindings {
+    /// Returns the bindings of a key map.
+    pub fn from_key_map(keys: &KeyMap) -> Self {
+        let mut bindings = Self::default();
+        for (input, _) in ACTIONS {
+            *bindings.keys_mut(input) = keys.keys(input).into_iter().map(key_name).collect();
+        }
+        bindings
+    }
+
+    /// Returns the names of the keys bound to an input.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - One of the inputs of [`ACTIONS`]
+    pub fn keys(&self, input: Input) -> &Vec<String> {
+        match input {
+            Input::LEFT => &self.left,
+            Input::RIGHT => &self.right,
+            Input::SOFT_DROP => &self.soft_drop,
+            Input::HARD_DROP => &self.hard_drop,
+            Input::ROTATE_CW => &self.rotate_cw,
+            Input::ROTATE_CCW => &self.rotate_ccw,
+            Input::HOLD => &self.hold,
+            _ => &self.target,
+        }
+    }
+
+    /// Returns the names of the keys bound to an input, to change them.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - One of the inputs of [`ACTIONS`]
+    pub fn keys_mut(&mut self, input: Input) -> &mut Vec<String> {
+        match input {
+            Input::LEFT => &mut self.left,
+            Input::RIGHT => &mut self.right,
+            Input::SOFT_DROP => &mut self.soft_drop,
+            Input::HARD_DROP => &mut self.hard_drop,
+            Input::ROTATE_CW => &mut self.rotate_cw,
+            Input::ROTATE_CCW => &mut self.rotate_ccw,
+            Input::HOLD => &mut self.hold,
+            _ => &mut self.target,
+        }
+    }
+
+    /// Returns the key map of the bindings, skipping unknown key names.
+    pub fn key_map(&self) -> KeyMap {
+        let mut bound: Vec<(KeyCode, Input)> = Vec::new();
+        for (input, _) in ACTIONS {
+            for name in self.keys(input) {
+                if let Some(key) = key_from_name(name) {
+                    bound.push((key, input));
+                }
+            }
+        }
+        KeyMap::new(bound)
+    }
+
+    /// Checks that every bound key has a known name.
+    fn validate(&self) -> Result<(), String> {
+        for (input, action) in ACTIONS {
+            if let Some(name) = self
+                .keys(input)
+                .iter()
+                .find(|name| key_from_name(name).is_none())
+            {
+                return Err(format!("Unknown key '{}' for {}", name, action));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The size the window opens at, in pixels.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Window {
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Default for Window {
+    fn default() -> Self {
+        Self {
+            width: 600,
+            height: 800,
+        }
+    }
+}
+
+/// Every setting of the player.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// The volume of sound effects, from `0.0` to `1.0`. The game has no
+    /// sound effects yet, so this is only kept for when it does.
+    pub volume: f32,
+    /// The single player keys.
+    pub keys: Bindings,
+    /// The keys of the left player in local versus and co-op.
+    pub versus_left: Bindings,
+    /// The keys of the right player in local versus and co-op.
+    pub versus_right: Bindings,
+    /// The handling used in every mode whose own is not part of its rules,
+    /// see [`Config::apply`].
+    pub handling: Handling,
+    pub visuals: Visuals,
+    /// Only read when the game starts.
+    pub window: Window,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            volume: 0.8,
+            keys: Bindings::from_key_map(&KeyMap::solo()),
+            versus_left: Bindings::from_key_map(&KeyMap::versus_left()),
+            versus_right: Bindings::from_key_map(&KeyMap::versus_right()),
+            handling: Handling::default(),
+            visuals: Visuals::default(),
+            window: Window::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Returns where the config file is kept, inside the user's config
+    /// directory.
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("rust_tetris").join("config.toml"))
+    }
+
+    /// Parses a config file, see the module documentation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_tetris::config::Config;
+    /// let config = Config::parse("[handling]\ndas = 7\n\n[keys]\nhold = [\"A\", \"S\"]\n").unwrap();
+    /// assert_eq!(config.handling.das, 7);
+    /// assert_eq!(config.handling.arr, 2);
+    /// assert_eq!(config.keys.hold, ["A", "S"]);
+    /// assert_eq!(Config::parse(&config.to_text()), Ok(config));
+    /// assert!(Config::parse("[keys]\nleft = [\"Nope\"]").is_err());
+    /// ```
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let config: Config = toml::from_str(text).map_err(|err| err.to_string())?;
+        for bindings in [&config.keys, &config.versus_left, &config.versus_right] {
+            bindings.validate()?;
+        }
+        Ok(config)
+    }
+
+    /// Returns the config in its file format.
+    pub fn to_text(&self) -> String {
+        toml::to_string(self).unwrap_or_default()
+    }
+
+    /// Loads a config file, writing the defaults to it if it does not exist.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        if !path.exists() {
+            let config = Self::default();
+            config.save(path)?;
+            return Ok(config);
+        }
+        let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        Self::parse(&text)
+    }
+
+    /// Saves the config to a file, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+        }
+        fs::write(path, self.to_text()).map_err(|err| err.to_string())
+    }
+
+    /// Returns when a config file was last changed, to reload it when it
+    /// is edited.
+    pub fn modified(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|meta| meta.modified()).ok()
+    }
+
+    /// Returns a ruleset played with the player's handling, unless the mode's
+    /// handling is part of its rules as in NES and Master.
+    pub fn apply(&self, ruleset: Ruleset) -> Ruleset {
+        if matches!(ruleset.mode, Mode::Nes | Mode::Master) {
+            return ruleset;
+        }
+        Ruleset {
+            handling: Some(self.handling),
+            ..ruleset
+        }
+    }
+}
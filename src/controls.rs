@@ -0,0 +1,245 @@
+//! Controls module mapping keyboard keys to game inputs.
+//!
+//! Keys are named after their [`KeyCode`] variant, e.g. `"Left"`, `"X"` or
+//! `"LeftShift"`, which is how the config file refers to them.
+
+use macroquad::prelude::{KeyCode, is_key_down};
+
+use crate::input::Input;
+
+/// Every key that can be bound.
+pub const KEYS: [KeyCode; 104] = [
+    KeyCode::Space,
+    KeyCode::Apostrophe,
+    KeyCode::Comma,
+    KeyCode::Minus,
+    KeyCode::Period,
+    KeyCode::Slash,
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Semicolon,
+    KeyCode::Equal,
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+    KeyCode::LeftBracket,
+    KeyCode::Backslash,
+    KeyCode::RightBracket,
+    KeyCode::GraveAccent,
+    KeyCode::Escape,
+    KeyCode::Enter,
+    KeyCode::Tab,
+    KeyCode::Backspace,
+    KeyCode::Insert,
+    KeyCode::Delete,
+    KeyCode::Right,
+    KeyCode::Left,
+    KeyCode::Down,
+    KeyCode::Up,
+    KeyCode::PageUp,
+    KeyCode::PageDown,
+    KeyCode::Home,
+    KeyCode::End,
+    KeyCode::CapsLock,
+    KeyCode::ScrollLock,
+    KeyCode::NumLock,
+    KeyCode::PrintScreen,
+    KeyCode::Pause,
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+    KeyCode::Kp0,
+    KeyCode::Kp1,
+    KeyCode::Kp2,
+    KeyCode::Kp3,
+    KeyCode::Kp4,
+    KeyCode::Kp5,
+    KeyCode::Kp6,
+    KeyCode::Kp7,
+    KeyCode::Kp8,
+    KeyCode::Kp9,
+    KeyCode::KpDecimal,
+    KeyCode::KpDivide,
+    KeyCode::KpMultiply,
+    KeyCode::KpSubtract,
+    KeyCode::KpAdd,
+    KeyCode::KpEnter,
+    KeyCode::KpEqual,
+    KeyCode::LeftShift,
+    KeyCode::LeftControl,
+    KeyCode::LeftAlt,
+    KeyCode::LeftSuper,
+    KeyCode::RightShift,
+    KeyCode::RightControl,
+    KeyCode::RightAlt,
+    KeyCode::RightSuper,
+];
+
+/// Every input a key can be bound to, with its name in the config file.
+pub const ACTIONS: [(Input, &str); 8] = [
+    (Input::LEFT, "left"),
+    (Input::RIGHT, "right"),
+    (Input::SOFT_DROP, "soft_drop"),
+    (Input::HARD_DROP, "hard_drop"),
+    (Input::ROTATE_CW, "rotate_cw"),
+    (Input::ROTATE_CCW, "rotate_ccw"),
+    (Input::HOLD, "hold"),
+    (Input::TARGET, "target"),
+];
+
+/// Returns the name of a key.
+pub fn key_name(key: KeyCode) -> String {
+    format!("{:?}", key)
+}
+
+/// Returns the key with a name, see [`key_name`].
+pub fn key_from_name(name: &str) -> Option<KeyCode> {
+    KEYS.into_iter().find(|&key| key_name(key) == name)
+}
+
+/// A set of key bindings for one player.
+///
+/// Several keys may be bound to the same input.
+#[derive(Debug, Clone)]
+pub struct KeyMap {
+    bindings: Vec<(KeyCode, Input)>,
+}
+
+impl KeyMap {
+    /// Creates a key map from its bindings.
+    pub fn new(bindings: Vec<(KeyCode, Input)>) -> Self {
+        Self { bindings }
+    }
+
+    /// The single player bindings: arrows to move, Up or X to rotate
+    /// clockwise, Z to rotate counter-clockwise, Space to hard drop and
+    /// C or Shift to hold. Tab switches targeting strategy in royale.
+    pub fn solo() -> Self {
+        Self {
+            bindings: vec![
+                (KeyCode::Left, Input::LEFT),
+                (KeyCode::Right, Input::RIGHT),
+                (KeyCode::Down, Input::SOFT_DROP),
+                (KeyCode::Space, Input::HARD_DROP),
+                (KeyCode::Up, Input::ROTATE_CW),
+                (KeyCode::X, Input::ROTATE_CW),
+                (KeyCode::Z, Input::ROTATE_CCW),
+                (KeyCode::C, Input::HOLD),
+                (KeyCode::LeftShift, Input::HOLD),
+                (KeyCode::Tab, Input::TARGET),
+            ],
+        }
+    }
+
+    /// The bindings of the left player in versus: A/D to move, S to soft
+    /// drop, W to hard drop, G/F to rotate and Q to hold.
+    pub fn versus_left() -> Self {
+        Self {
+            bindings: vec![
+                (KeyCode::A, Input::LEFT),
+                (KeyCode::D, Input::RIGHT),
+                (KeyCode::S, Input::SOFT_DROP),
+                (KeyCode::W, Input::HARD_DROP),
+                (KeyCode::G, Input::ROTATE_CW),
+                (KeyCode::F, Input::ROTATE_CCW),
+                (KeyCode::Q, Input::HOLD),
+            ],
+        }
+    }
+
+    /// The bindings of the right player in versus: arrows to move, Up to
+    /// hard drop, Period/Comma to rotate and Slash to hold.
+    pub fn versus_right() -> Self {
+        Self {
+            bindings: vec![
+                (KeyCode::Left, Input::LEFT),
+                (KeyCode::Right, Input::RIGHT),
+                (KeyCode::Down, Input::SOFT_DROP),
+                (KeyCode::Up, Input::HARD_DROP),
+                (KeyCode::Period, Input::ROTATE_CW),
+                (KeyCode::Comma, Input::ROTATE_CCW),
+                (KeyCode::Slash, Input::HOLD),
+            ],
+        }
+    }
+
+    /// Returns the keys bound to an input, in the order they were bound.
+    pub fn keys(&self, input: Input) -> Vec<KeyCode> {
+        self.bindings
+            .iter()
+            .filter(|&&(_, bound)| bound == input)
+            .map(|&(key, _)| key)
+            .collect()
+    }
+
+    /// Returns a short reminder of the bindings, e.g. `"A/D S W G/F Q"`:
+    /// the first key of each movement, drop, rotation and hold input.
+    pub fn summary(&self) -> String {
+        let first = |input| {
+            self.keys(input)
+                .first()
+                .map_or("-".to_string(), |&key| key_name(key))
+        };
+        format!(
+            "{}/{} {} {} {}/{} {}",
+            first(Input::LEFT),
+            first(Input::RIGHT),
+            first(Input::SOFT_DROP),
+            first(Input::HARD_DROP),
+            first(Input::ROTATE_CW),
+            first(Input::ROTATE_CCW),
+            first(Input::HOLD)
+        )
+    }
+
+    /// Returns the inputs whose keys are currently held down.
+    pub fn read(&self) -> Input {
+        let mut input = Input::NONE;
+        for &(key, bound) in &self.bindings {
+            if is_key_down(key) {
+                input |= bound;
+            }
+        }
+        input
+    }
+}
@@ -0,0 +1,91 @@
+//! Co-op module letting two players share one wide board.
+//!
+//! Each player drives their own [`Game`], with its own pieces, hold and
+//! handling, but the games take turns on a single shared [`Board`]: before a
+//! player's frame is simulated their game is handed the shared board, and
+//! the other player's falling piece as an obstacle it cannot move through.
+//! Score, lines and level are shared as well. The match ends as soon as
+//! either player tops out.
+
+use crate::board::Board;
+use crate::game::{Game, GameState};
+use crate::input::Input;
+use crate::randomizer::Randomizer;
+use crate::ruleset::Ruleset;
+use crate::tetromino::Direction;
+
+/// The board widths offered for co-op, in columns.
+pub const COOP_WIDTHS: [usize; 3] = [16, 18, 20];
+
+/// A local game played by two players on one board.
+pub struct Coop {
+    /// The games of both players. Their boards are only up to date while
+    /// they are being simulated, see [`Coop::board`].
+    pub players: [Game; 2],
+    /// The shared board.
+    pub board: Board,
+}
+
+impl Coop {
+    /// Creates a new co-op game.
+    ///
+    /// Each player spawns pieces in the middle of their half of the board,
+    /// and is dealt their own sequence of pieces.
+    ///
+    /// # Arguments
+    ///
+    /// * `ruleset` - The rules both players play by
+    /// * `seed` - The seed for the pieces
+    /// * `width` - The width of the board, see [`Board::with_width`]
+    pub fn new(ruleset: Ruleset, seed: u64, width: usize) -> Self {
+        let board = Board::with_width(width);
+        let half = (board.width() / 2) as i32;
+        let players = [0, 1].map(|player| {
+            let randomizer = Randomizer::new(ruleset.randomizer, seed.wrapping_add(player));
+            let mut game = Game::from_position(ruleset, board.clone(), randomizer);
+            game.set_spawn_column(player as i32 * half + (half - 3) / 2);
+            game
+        });
+        Self { players, board }
+    }
+
+    /// Returns whether the game has ended.
+    pub fn is_over(&self) -> bool {
+        self.players
+            .iter()
+            .any(|game| !matches!(game.state, GameState::Playing))
+    }
+
+    /// Advances both players by one frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `inputs` - The buttons held by each player during this frame
+    pub fn update(&mut self, inputs: [Input; 2]) {
+        if self.is_over() {
+            return;
+        }
+        for (player, input) in inputs.into_iter().enumerate() {
+            let other = 1 - player;
+            let obstacle = self.players[other].current_piece.clone();
+            let game = &mut self.players[player];
+            game.board = self.board.clone();
+            game.obstacles = obstacle.into_iter().collect();
+            game.update(input);
+            self.board = game.board.clone();
+
+            let (score, lines, level) = (game.score, game.lines, game.level);
+            let partner = &mut self.players[other];
+            partner.score = score;
+            partner.lines = lines;
+            partner.level = level;
+            // Rows dropping after a clear may land on the partner's piece,
+            // which is pushed up out of the way
+            if let Some(piece) = &mut partner.current_piece {
+                while !self.board.can_place(piece) && piece.pos.1 > 0 {
+                    piece.move_piece(Direction::Up);
+                }
+            }
+        }
+    }
+}
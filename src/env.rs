@@ -0,0 +1,357 @@
+//! Env module exposing the game as a reinforcement learning environment.
+//!
+//! [`TetrisEnv`] follows the gym conventions: [`reset`](TetrisEnv::reset)
+//! starts a seeded game and returns the first observation, and
+//! [`step`](TetrisEnv::step) applies one action and returns the next
+//! observation, the reward, whether the game ended and some [`Info`].
+//!
+//! Agents act in one of two [`ActionSpace`]s:
+//!
+//! - [`ActionSpace::Inputs`]: an action is the bitmask of an [`Input`], held
+//!   for one frame, exactly like a player's controller.
+//! - [`ActionSpace::Placements`]: an action indexes the placements listed by
+//!   [`TetrisEnv::placements`], every distinct lock position of the current
+//!   piece and, when holding is allowed, of the piece swapped in by a hold.
+//!   The piece is moved there instantly, then the game is run until the
+//!   next piece spawns.
+//!
+//! Either way the game is a plain [`Game`], so agents play by the exact
+//! rules players do.
+
+use crate::board::BOARD_HEIGHT;
+use crate::game::{Game, GameState, LineClear};
+use crate::input::Input;
+use crate::movegen::{self, Move, Placement};
+use crate::ruleset::Ruleset;
+use crate::tetromino::{Direction, Shape, Tetromino};
+use crate::versus::attack;
+
+/// The shapes in the order of their one-hot encodings.
+pub const SHAPES: [Shape; 7] = [
+    Shape::I,
+    Shape::O,
+    Shape::T,
+    Shape::S,
+    Shape::Z,
+    Shape::J,
+    Shape::L,
+];
+
+/// How many actions the raw input action space has: every combination of
+/// the buttons below [`Input::TARGET`].
+pub const INPUT_ACTIONS: usize = 1 << 7;
+
+/// What an action means.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ActionSpace {
+    /// The bitmask of the buttons held for one frame.
+    Inputs,
+    /// An index into [`TetrisEnv::placements`].
+    Placements,
+}
+
+/// What observations contain.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Encoding {
+    /// Whether to include the board as bitplanes.
+    pub planes: bool,
+    /// How many preview pieces to include, at most the ruleset's preview.
+    pub queue: usize,
+    /// Whether to include the hold piece.
+    pub hold: bool,
+}
+
+impl Default for Encoding {
+    fn default() -> Self {
+        Self {
+            planes: true,
+            queue: 5,
+            hold: true,
+        }
+    }
+}
+
+/// What rewards are given for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Reward {
+    /// One per line cleared.
+    Lines,
+    /// The points scored, as the ruleset counts them.
+    Score,
+    /// The garbage lines a clear would send, see [`attack`].
+    Attack,
+}
+
+/// An observation of the game, with the parts the [`Encoding`] leaves out
+/// empty. Shapes are one-hot vectors ordered like [`SHAPES`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Observation {
+    /// Two planes of `BOARD_HEIGHT` rows of the board width, top row
+    /// first: the locked cells, then the cells of the falling piece.
+    pub planes: Vec<f32>,
+    /// One one-hot shape per preview piece, the next piece first.
+    pub queue: Vec<f32>,
+    /// The one-hot hold shape, all zeros when nothing is held, followed by
+    /// `1.0` when the hold can be used.
+    pub hold: Vec<f32>,
+}
+
+impl Observation {
+    /// Returns the observation as one flat vector: planes, queue, hold.
+    pub fn to_vec(&self) -> Vec<f32> {
+        [&self.planes[..], &self.queue, &self.hold].concat()
+    }
+}
+
+/// Details of the game after a step.
+#[derive(Debug, Copy, Clone)]
+pub struct Info {
+    pub score: u32,
+    pub lines: u32,
+    pub pieces: u32,
+    pub frame: u64,
+    /// The clear made by the piece locked during the step, if any.
+    pub clear: Option<LineClear>,
+    /// How many actions are valid for the next step.
+    pub actions: usize,
+}
+
+/// A placement action.
+#[derive(Debug, Clone)]
+pub struct PlacementAction {
+    /// Whether the piece is swapped with the hold piece first.
+    pub hold: bool,
+    pub placement: Placement,
+}
+
+/// A game played through the gym step/reset interface.
+pub struct TetrisEnv {
+    pub ruleset: Ruleset,
+    pub actions: ActionSpace,
+    pub encoding: Encoding,
+    pub reward: Reward,
+    game: Game,
+    placements: Vec<PlacementAction>,
+}
+
+impl TetrisEnv {
+    /// Creates an environment. Call [`reset`](TetrisEnv::reset) to start
+    /// playing.
+    ///
+    /// # Arguments
+    ///
+    /// * `ruleset` - The rules played by
+    /// * `actions` - What actions mean
+    /// * `encoding` - What observations contain
+    /// * `reward` - What rewards are given for
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_tetris::env::{ActionSpace, Encoding, Reward, TetrisEnv};
+    /// # use rust_tetris::ruleset::Ruleset;
+    /// let mut env = TetrisEnv::new(
+    ///     Ruleset::versus(),
+    ///     ActionSpace::Placements,
+    ///     Encoding::default(),
+    ///     Reward::Lines,
+    /// );
+    /// env.reset(42);
+    /// let (_, reward, done, info) = env.step(0).unwrap();
+    /// assert_eq!((reward, done, info.pieces), (0.0, false, 1));
+    /// ```
+    pub fn new(ruleset: Ruleset, actions: ActionSpace, encoding: Encoding, reward: Reward) -> Self {
+        let mut env = Self {
+            ruleset,
+            actions,
+            encoding,
+            reward,
+            game: Game::new(ruleset, 0),
+            placements: Vec::new(),
+        };
+        env.reset(0);
+        env
+    }
+
+    /// Starts a new game.
+    ///
+    /// # Arguments
+    ///
+    /// * `seed` - The seed of the pieces
+    ///
+    /// # Returns
+    ///
+    /// The first observation.
+    pub fn reset(&mut self, seed: u64) -> Observation {
+        self.game = Game::new(self.ruleset, seed);
+        self.list_placements();
+        self.observe()
+    }
+
+    /// Applies an action.
+    ///
+    /// # Arguments
+    ///
+    /// * `action` - The action, below [`action_count`](TetrisEnv::action_count)
+    ///
+    /// # Returns
+    ///
+    /// The observation, reward, whether the game has ended and details of
+    /// the game, or an error for an action out of range.
+    pub fn step(&mut self, action: usize) -> Result<(Observation, f64, bool, Info), String> {
+        if action >= self.action_count() {
+            return Err(format!("Action {} is out of range", action));
+        }
+        let (score, lines, pieces) = (self.game.score, self.game.lines, self.game.pieces);
+        match self.actions {
+            ActionSpace::Inputs => self.game.update(Input(action as u8)),
+            ActionSpace::Placements => self.place(action)?,
+        }
+        self.list_placements();
+
+        let clear = self.game.last_clear.filter(|_| self.game.pieces != pieces);
+        let reward = match self.reward {
+            Reward::Lines => (self.game.lines - lines) as f64,
+            Reward::Score => (self.game.score - score) as f64,
+            Reward::Attack => clear.map_or(0, |clear| attack(&clear)) as f64,
+        };
+        let done = !matches!(self.game.state, GameState::Playing);
+        let info = Info {
+            score: self.game.score,
+            lines: self.game.lines,
+            pieces: self.game.pieces,
+            frame: self.game.frame,
+            clear,
+            actions: self.action_count(),
+        };
+        Ok((self.observe(), reward, done, info))
+    }
+
+    /// Returns how many actions are valid for the next step.
+    pub fn action_count(&self) -> usize {
+        match self.actions {
+            ActionSpace::Inputs => INPUT_ACTIONS,
+            ActionSpace::Placements => self.placements.len(),
+        }
+    }
+
+    /// Returns the placement actions for the next step, empty in the
+    /// [`ActionSpace::Inputs`] action space.
+    pub fn placements(&self) -> &[PlacementAction] {
+        &self.placements
+    }
+
+    /// Returns the game being played.
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// Returns the current observation.
+    pub fn observe(&self) -> Observation {
+        let width = self.game.board.width();
+        let mut planes = Vec::new();
+        if self.encoding.planes {
+            planes = vec![0.0; 2 * BOARD_HEIGHT * width];
+            for (y, row) in planes.chunks_mut(width).take(BOARD_HEIGHT).enumerate() {
+                for (x, cell) in row.iter_mut().enumerate() {
+                    *cell = f32::from(u8::from(self.game.board.is_filled(x, y)));
+                }
+            }
+            if let Some(piece) = &self.game.current_piece {
+                for (x, y) in piece.cells() {
+                    if self.game.board.pos_in_bounds(x, y) {
+                        planes[(BOARD_HEIGHT + y as usize) * width + x as usize] = 1.0;
+                    }
+                }
+            }
+        }
+        let queue = self
+            .game
+            .next
+            .iter()
+            .take(self.encoding.queue.min(self.ruleset.preview))
+            .flat_map(|&shape| one_hot(Some(shape)))
+            .collect();
+        let mut hold = Vec::new();
+        if self.encoding.hold {
+            hold.extend(one_hot(self.game.hold));
+            hold.push(f32::from(u8::from(self.game.can_hold())));
+        }
+        Observation {
+            planes,
+            queue,
+            hold,
+        }
+    }
+
+    /// Lists the placement actions of the current piece.
+    fn list_placements(&mut self) {
+        self.placements.clear();
+        if self.actions != ActionSpace::Placements || !matches!(self.game.state, GameState::Playing)
+        {
+            return;
+        }
+        let Some(piece) = &self.game.current_piece else {
+            return;
+        };
+        let board = &self.game.board;
+        let actions = movegen::placements_from(board, piece)
+            .into_iter()
+            .map(|placement| PlacementAction {
+                hold: false,
+                placement,
+            });
+        self.placements.extend(actions);
+        let swapped = self.game.hold.or(self.game.next.front().copied());
+        if let Some(shape) = swapped.filter(|_| self.game.can_hold()) {
+            let actions = movegen::placements(board, shape, &self.ruleset)
+                .into_iter()
+                .map(|placement| PlacementAction {
+                    hold: true,
+                    placement,
+                });
+            self.placements.extend(actions);
+        }
+    }
+
+    /// Moves the piece to a placement, locks it and runs the game until the
+    /// next piece spawns.
+    fn place(&mut self, action: usize) -> Result<(), String> {
+        let PlacementAction { hold, placement } = self.placements[action].clone();
+        if hold {
+            self.game.try_hold()?;
+        }
+        for step in placement.path {
+            match step {
+                Move::Left => self.game.try_move(Direction::Left)?,
+                Move::Right => self.game.try_move(Direction::Right)?,
+                Move::Down => self.game.try_move(Direction::Down)?,
+                Move::RotateCw => self.game.try_rotate_clock()?,
+                Move::RotateCcw => self.game.try_rotate_counter()?,
+                Move::Drop => self.game.hard_lock(),
+            }
+        }
+        let reached = self
+            .game
+            .current_piece
+            .as_ref()
+            .is_some_and(|piece| same_cells(piece, &placement.piece));
+        if !reached {
+            return Err("The placement could not be reached".to_string());
+        }
+        self.game.lock_current_piece()?;
+        while matches!(self.game.state, GameState::Playing) && self.game.current_piece.is_none() {
+            self.game.update(Input::NONE);
+        }
+        Ok(())
+    }
+}
+
+/// Returns the one-hot encoding of a shape, all zeros for `None`.
+fn one_hot(shape: Option<Shape>) -> [f32; 7] {
+    SHAPES.map(|known| f32::from(u8::from(Some(known) == shape)))
+}
+
+fn same_cells(piece: &Tetromino, other: &Tetromino) -> bool {
+    piece.cells().eq(other.cells())
+}
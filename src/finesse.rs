@@ -0,0 +1,175 @@
+//! Finesse module judging how efficiently pieces are placed.
+//!
+//! Finesse is placing every piece with the fewest key presses: a tap moves
+//! a piece one column, holding a direction (DAS) moves it to the wall, and
+//! each rotation is one press. [`minimal_keys`] finds the shortest sequence
+//! reaching a placement with a hard drop, which [`Game`](crate::game::Game)
+//! compares with the presses the player spent on the piece, counting a
+//! fault whenever more were spent.
+
+use std::collections::{HashSet, VecDeque};
+
+use crate::board::Board;
+use crate::movegen::{self, Move};
+use crate::tetromino::Tetromino;
+
+/// How a game judges finesse.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FinesseMode {
+    /// Finesse is not judged.
+    Off,
+    /// Faults are counted and shown.
+    Count,
+    /// Faults are counted, and a piece placed with a fault is taken back to
+    /// be placed again.
+    Strict,
+}
+
+impl FinesseMode {
+    /// Every mode, in the order the menu cycles through them.
+    pub const ALL: [FinesseMode; 3] = [FinesseMode::Off, FinesseMode::Count, FinesseMode::Strict];
+
+    /// The display name of the mode.
+    pub fn name(&self) -> &'static str {
+        match self {
+            FinesseMode::Off => "Off",
+            FinesseMode::Count => "Count",
+            FinesseMode::Strict => "Strict",
+        }
+    }
+}
+
+/// One key press of a finesse sequence.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Key {
+    Left,
+    Right,
+    /// Holding left until the piece reaches the wall or the stack.
+    DasLeft,
+    /// Holding right until the piece reaches the wall or the stack.
+    DasRight,
+    RotateCw,
+    RotateCcw,
+}
+
+impl Key {
+    /// Every key, in the order the search tries them.
+    pub const ALL: [Key; 6] = [
+        Key::RotateCw,
+        Key::RotateCcw,
+        Key::Left,
+        Key::Right,
+        Key::DasLeft,
+        Key::DasRight,
+    ];
+
+    /// The short name of the key, as shown to the player.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Key::Left => "L",
+            Key::Right => "R",
+            Key::DasLeft => "DAS L",
+            Key::DasRight => "DAS R",
+            Key::RotateCw => "CW",
+            Key::RotateCcw => "CCW",
+        }
+    }
+}
+
+/// How a placed piece was judged.
+#[derive(Debug, Clone)]
+pub struct Finesse {
+    /// The piece as it locked.
+    pub piece: Tetromino,
+    /// The movement and rotation presses spent on the piece.
+    pub inputs: u32,
+    /// A shortest key sequence reaching the same placement.
+    pub keys: Vec<Key>,
+    /// The game frame the piece locked on.
+    pub frame: u64,
+}
+
+impl Finesse {
+    /// Returns whether more presses were spent than needed.
+    pub fn is_fault(&self) -> bool {
+        self.inputs as usize > self.keys.len()
+    }
+
+    /// Returns the shortest key sequence as text, e.g. `"CW, DAS L"`.
+    pub fn describe(&self) -> String {
+        if self.keys.is_empty() {
+            return "Drop".to_string();
+        }
+        let names: Vec<&str> = self.keys.iter().map(Key::name).collect();
+        names.join(", ")
+    }
+}
+
+/// Returns the fewest key presses moving a piece from where it spawned to
+/// above a placement, so that a hard drop puts it there.
+///
+/// # Arguments
+///
+/// * `board` - The board the piece is played on
+/// * `start` - The piece where it spawned
+/// * `target` - The piece at its resting position
+///
+/// # Returns
+///
+/// The keys, or `None` if the placement cannot be reached without soft
+/// dropping, e.g. a tuck under an overhang or a T-spin.
+///
+/// # Examples
+///
+/// ```
+/// # use rust_tetris::board::Board;
+/// # use rust_tetris::finesse::{Key, minimal_keys};
+/// # use rust_tetris::tetromino::{RotationSystem, Shape, Tetromino};
+/// let board = Board::new();
+/// let start = Tetromino::with_rotation_system((3, 0), Shape::O, RotationSystem::Srs);
+/// let mut target = start.clone();
+/// target.pos = (0, 18);
+/// assert_eq!(minimal_keys(&board, &start, &target), Some(vec![Key::DasLeft]));
+/// ```
+pub fn minimal_keys(board: &Board, start: &Tetromino, target: &Tetromino) -> Option<Vec<Key>> {
+    if !board.can_place(start) {
+        return None;
+    }
+    let goal = target.row_masks();
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::from([(start.clone(), Vec::new())]);
+    while let Some((piece, keys)) = queue.pop_front() {
+        if !seen.insert((piece.pos, piece.rotation)) {
+            continue;
+        }
+        let landed = movegen::apply(board, &piece, Move::Drop).unwrap_or_else(|| piece.clone());
+        if landed.row_masks() == goal {
+            return Some(keys);
+        }
+        for key in Key::ALL {
+            if let Some(next) = press(board, &piece, key) {
+                let mut keys = keys.clone();
+                keys.push(key);
+                queue.push_back((next, keys));
+            }
+        }
+    }
+    None
+}
+
+/// Returns the piece after a key press, or `None` if it does not move.
+fn press(board: &Board, piece: &Tetromino, key: Key) -> Option<Tetromino> {
+    let (step, repeat) = match key {
+        Key::Left => (Move::Left, false),
+        Key::Right => (Move::Right, false),
+        Key::DasLeft => (Move::Left, true),
+        Key::DasRight => (Move::Right, true),
+        Key::RotateCw => (Move::RotateCw, false),
+        Key::RotateCcw => (Move::RotateCcw, false),
+    };
+    let mut next = movegen::apply(board, piece, step)?;
+    while repeat && let Some(further) = movegen::apply(board, &next, step) {
+        next = further;
+    }
+    Some(next)
+}
@@ -1,10 +1,101 @@
+use std::collections::VecDeque;
+
 use crate::board::Board;
-use crate::tetromino::{Direction, Shape, Tetromino};
+use crate::finesse::{self, Finesse, FinesseMode};
+use crate::garbage::Garbage;
+use crate::input::Input;
+use crate::master::MAX_LEVEL;
+use crate::randomizer::Randomizer;
+use crate::ruleset::{Gravity, HardDrop, Ruleset};
+use crate::stats::Stats;
+use crate::tetromino::{Direction, RotationSystem, Shape, Tetromino};
+
+/// The lines cleared in a sprint, timed by [`Game::sprint_frame`].
+pub const SPRINT_LINES: u32 = 40;
 
 #[derive(Debug, Copy, Clone)]
 pub enum GameState {
     Playing,
     GameOver,
+    /// The mode's goal was reached, e.g. level 999 in Master.
+    Cleared,
+}
+
+/// What the game is doing between pieces.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Phase {
+    /// The active piece is falling and can be controlled.
+    Falling,
+    /// The game is paused for the given number of frames after a line clear.
+    LineClear(u32),
+    /// The game waits the given number of frames before spawning the next piece.
+    Are(u32),
+}
+
+/// How a T piece was spun into its final position.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum TSpin {
+    None,
+    /// Three corners around the T are filled, but not both in front of it.
+    Mini,
+    /// Three corners around the T are filled, including both in front of it.
+    Full,
+}
+
+/// A piece lock that cleared lines, as passed to the ruleset for scoring.
+#[derive(Debug, Copy, Clone)]
+pub struct LineClear {
+    /// The number of lines cleared at once.
+    pub lines: u32,
+    /// The level before the clear.
+    pub level: u32,
+    /// The combo multiplier: 1 for a clear after a piece that cleared
+    /// nothing, increased by `2 * lines - 2` for every consecutive clear.
+    pub combo: u32,
+    /// Frames soft drop was held while the piece was falling.
+    pub soft_drop_frames: u32,
+    /// Whether the clear left the board empty.
+    pub perfect_clear: bool,
+    /// Whether the piece was a T spun into place.
+    pub tspin: TSpin,
+    /// How many pieces in a row cleared lines before this one.
+    pub streak: u32,
+    /// Whether this and the previous clear were both difficult: a four line
+    /// clear or a T-spin clear.
+    pub back_to_back: bool,
+}
+
+impl LineClear {
+    /// Returns whether the clear counts towards back-to-back.
+    pub fn is_difficult(&self) -> bool {
+        self.lines >= 4 || self.tspin != TSpin::None
+    }
+}
+
+/// Time and score when the level first reached a new section (100 levels).
+#[derive(Debug, Copy, Clone)]
+pub struct SectionSplit {
+    pub level: u32,
+    pub frame: u64,
+    pub score: u32,
+}
+
+/// The state of a game when a piece spawned, restored by undo and redo.
+#[derive(Clone)]
+pub struct Snapshot {
+    board: Board,
+    current_piece: Option<Tetromino>,
+    next: VecDeque<Shape>,
+    randomizer: Randomizer,
+    score: u32,
+    lines: u32,
+    level: u32,
+    combo: u32,
+    pieces: u32,
+    hold: Option<Shape>,
+    hold_used: bool,
+    streak: u32,
+    back_to_back: bool,
 }
 
 pub struct Game {
@@ -12,37 +103,567 @@ pub struct Game {
     pub current_piece: Option<Tetromino>,
     pub state: GameState,
     pub score: u32,
+    pub ruleset: Ruleset,
+    pub level: u32,
+    pub lines: u32,
+    /// Upcoming pieces, the front being dealt next.
+    pub next: VecDeque<Shape>,
+    /// Frames simulated since the game started.
+    pub frame: u64,
+    pub phase: Phase,
+    pub section_splits: Vec<SectionSplit>,
+    /// The frame the game reached [`SPRINT_LINES`] lines, its sprint time.
+    pub sprint_frame: Option<u64>,
+    /// Pieces locked so far.
+    pub pieces: u32,
+    /// The clear made by the last locked piece, `None` if it cleared nothing.
+    pub last_clear: Option<LineClear>,
+    /// The held piece, when the ruleset allows holding.
+    pub hold: Option<Shape>,
+    /// Garbage pushed into the board when the next piece locks without
+    /// clearing lines.
+    pub incoming: VecDeque<Garbage>,
+    /// The falling pieces of other players sharing the board, which the
+    /// current piece cannot overlap.
+    pub obstacles: Vec<Tetromino>,
+    /// Pieces placed with more presses than needed, see [`FinesseMode`].
+    pub finesse_faults: u32,
+    /// How the last piece locked was judged, when finesse is judged and the
+    /// piece could be judged.
+    pub last_finesse: Option<Finesse>,
+    /// Running totals of the game, kept through undo and redo.
+    pub stats: Stats,
+    randomizer: Randomizer,
+    spawn_column: Option<i32>,
+    held: Input,
+    gravity_counter: u32,
+    das_counter: u32,
+    lock_counter: u32,
+    lock_resets: u32,
+    hold_used: bool,
+    streak: u32,
+    back_to_back: bool,
+    combo: u32,
+    soft_drop_frames: u32,
+    last_move_rotation: bool,
+    /// Movement and rotation presses spent on the current piece.
+    piece_inputs: u32,
+    spawn_snapshot: Option<Snapshot>,
+    undo_stack: Vec<Snapshot>,
+    redo_stack: Vec<Snapshot>,
 }
 
 impl Game {
-    pub fn new() -> Self {
+    pub fn new(ruleset: Ruleset, seed: u64) -> Self {
+        Self::from_position(
+            ruleset,
+            Board::new(),
+            Randomizer::new(ruleset.randomizer, seed),
+        )
+    }
+
+    /// Creates a game starting from a prepared board and piece generator.
+    ///
+    /// # Arguments
+    ///
+    /// * `ruleset` - The rules to play by
+    /// * `board` - The starting board
+    /// * `randomizer` - The generator dealing the pieces
+    pub fn from_position(ruleset: Ruleset, board: Board, randomizer: Randomizer) -> Self {
         let mut game = Self {
-            board: Board::new(),
+            board,
             current_piece: None,
             state: GameState::Playing,
             score: 0,
+            ruleset,
+            level: ruleset.start_level,
+            lines: 0,
+            next: VecDeque::new(),
+            frame: 0,
+            phase: Phase::Falling,
+            section_splits: Vec::new(),
+            sprint_frame: None,
+            pieces: 0,
+            last_clear: None,
+            hold: None,
+            incoming: VecDeque::new(),
+            obstacles: Vec::new(),
+            finesse_faults: 0,
+            last_finesse: None,
+            stats: Stats::default(),
+            randomizer,
+            spawn_column: None,
+            held: Input::NONE,
+            gravity_counter: 0,
+            das_counter: 0,
+            lock_counter: 0,
+            lock_resets: 0,
+            hold_used: false,
+            streak: 0,
+            back_to_back: false,
+            combo: 1,
+            soft_drop_frames: 0,
+            last_move_rotation: false,
+            piece_inputs: 0,
+            spawn_snapshot: None,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
         };
-        game.set_random_piece();
+        game.fill_next();
+        game.spawn_next_piece();
         game
     }
 
-    pub fn set_random_piece(&mut self) {
-        let rand_piece_index = fastrand::u8(0..7); //7 different shapes
-        let mut counter: u8 = 0;
-        self.current_piece = Some(Tetromino::new((3, 0), match_shape(rand_piece_index)));
-        while counter < 7 {
-            if let Some(temp_piece) = &self.current_piece
-                && self.board.can_place(temp_piece)
-            {
+    fn fill_next(&mut self) {
+        while self.next.len() < self.ruleset.preview.max(1) {
+            match self.randomizer.next_shape() {
+                Some(shape) => self.next.push_back(shape),
+                None => break,
+            }
+        }
+    }
+
+    pub fn spawn_next_piece(&mut self) {
+        self.hold_used = false;
+        if !self.spawn_from_queue() {
+            return;
+        }
+        // Every placement can be undone back to where its piece spawned
+        if let Some(previous) = self.spawn_snapshot.take()
+            && self.ruleset.undo
+        {
+            self.undo_stack.push(previous);
+            self.redo_stack.clear();
+        }
+        self.take_spawn_snapshot();
+    }
+
+    /// Spawns the first piece of the queue.
+    ///
+    /// # Returns
+    ///
+    /// `false` if a fixed piece sequence ran out, ending the game.
+    fn spawn_from_queue(&mut self) -> bool {
+        let Some(shape) = self.next.pop_front() else {
+            self.state = GameState::GameOver;
+            return false;
+        };
+        self.fill_next();
+        self.spawn_piece(shape);
+        true
+    }
+
+    /// Remembers the current piece as it spawned, after any hold, for undo
+    /// and for strict finesse to take pieces back to.
+    fn take_spawn_snapshot(&mut self) {
+        if self.ruleset.undo || self.ruleset.finesse == FinesseMode::Strict {
+            self.spawn_snapshot = Some(self.snapshot());
+        }
+    }
+
+    /// Returns a piece of the given shape at the spawn position.
+    fn spawn_position(&self, shape: Shape) -> Tetromino {
+        let scale = self.ruleset.piece_scale();
+        let spawn_x = self
+            .spawn_column
+            .unwrap_or_else(|| default_spawn_column(&self.board, &self.ruleset));
+        let mut piece = Tetromino::with_rotation_system((spawn_x, 0), shape, self.ruleset.rotation);
+        piece.scale = scale;
+        piece
+    }
+
+    /// Sets the column pieces spawn in, centred on the board by default.
+    /// The current piece is moved there as well.
+    ///
+    /// # Arguments
+    ///
+    /// * `column` - The column of the left edge of the spawned pieces'
+    ///   bounding box
+    pub fn set_spawn_column(&mut self, column: i32) {
+        self.spawn_column = Some(column);
+        if let Some(shape) = self.current_piece.as_ref().map(|piece| piece.shape) {
+            self.current_piece = Some(self.spawn_position(shape));
+        }
+    }
+
+    fn spawn_piece(&mut self, shape: Shape) {
+        let piece = self.spawn_position(shape);
+        if !self.board.can_place(&piece) {
+            if self.ruleset.top_out_clears {
+                self.board = Board::new();
+            } else {
+                self.state = GameState::GameOver;
+            }
+        }
+        self.current_piece = Some(piece);
+        self.reset_piece_counters();
+    }
+
+    /// Swaps the current piece with the held one, or with the next piece if
+    /// nothing is held yet. Only one hold is allowed per piece.
+    ///
+    /// A piece taken back by strict finesse returns as it was after the hold.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_tetris::finesse::FinesseMode;
+    /// # use rust_tetris::game::Game;
+    /// # use rust_tetris::input::Input;
+    /// # use rust_tetris::ruleset::Ruleset;
+    /// let ruleset = Ruleset { finesse: FinesseMode::Strict, ..Ruleset::versus() };
+    /// let mut game = Game::new(ruleset, 3);
+    /// game.try_hold().unwrap();
+    /// let held = (game.hold, game.current_piece.as_ref().map(|piece| piece.shape));
+    /// for input in [Input::LEFT, Input::RIGHT, Input::LEFT, Input::RIGHT, Input::HARD_DROP] {
+    ///     game.update(input);
+    ///     game.update(Input::NONE);
+    /// }
+    /// assert_eq!(game.finesse_faults, 1);
+    /// assert_eq!((game.hold, game.current_piece.as_ref().map(|piece| piece.shape)), held);
+    /// assert!(!game.can_hold());
+    /// ```
+    pub fn try_hold(&mut self) -> Result<(), String> {
+        if !self.ruleset.hold || self.hold_used {
+            return Err("Hold unavailable".to_string());
+        }
+        let spawned = self.hold.or(self.next.front().copied());
+        if spawned.is_some_and(|shape| self.overlaps_obstacle(&self.spawn_position(shape))) {
+            return Err("Spawn area occupied".to_string());
+        }
+        let piece = self.current_piece.take().ok_or("No current piece")?;
+        match self.hold.replace(piece.shape) {
+            Some(shape) => self.spawn_piece(shape),
+            None => {
+                self.spawn_from_queue();
+            }
+        }
+        self.hold_used = true;
+        self.take_spawn_snapshot();
+        Ok(())
+    }
+
+    fn reset_piece_counters(&mut self) {
+        self.phase = Phase::Falling;
+        self.gravity_counter = 0;
+        self.lock_counter = 0;
+        self.lock_resets = 0;
+        self.soft_drop_frames = 0;
+        self.last_move_rotation = false;
+        self.piece_inputs = 0;
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            board: self.board.clone(),
+            current_piece: self.current_piece.clone(),
+            next: self.next.clone(),
+            randomizer: self.randomizer.clone(),
+            score: self.score,
+            lines: self.lines,
+            level: self.level,
+            combo: self.combo,
+            pieces: self.pieces,
+            hold: self.hold,
+            hold_used: self.hold_used,
+            streak: self.streak,
+            back_to_back: self.back_to_back,
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.board = snapshot.board.clone();
+        self.current_piece = snapshot.current_piece.clone();
+        self.next = snapshot.next.clone();
+        self.randomizer = snapshot.randomizer.clone();
+        self.score = snapshot.score;
+        self.lines = snapshot.lines;
+        if self.lines < SPRINT_LINES {
+            self.sprint_frame = None;
+        }
+        self.level = snapshot.level;
+        self.combo = snapshot.combo;
+        self.pieces = snapshot.pieces;
+        self.hold = snapshot.hold;
+        self.hold_used = snapshot.hold_used;
+        self.streak = snapshot.streak;
+        self.back_to_back = snapshot.back_to_back;
+        self.last_clear = None;
+        self.state = GameState::Playing;
+        self.reset_piece_counters();
+        self.spawn_snapshot = Some(snapshot);
+    }
+
+    /// Takes back the last placement, returning to the moment the previous
+    /// piece spawned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_tetris::board::BOARD_HEIGHT;
+    /// # use rust_tetris::game::Game;
+    /// # use rust_tetris::input::Input;
+    /// # use rust_tetris::ruleset::Ruleset;
+    /// let mut game = Game::new(Ruleset { undo: true, ..Ruleset::versus() }, 1);
+    /// let state = |game: &Game| {
+    ///     let rows: Vec<u32> = (0..BOARD_HEIGHT).map(|y| game.board.row(y)).collect();
+    ///     let shape = game.current_piece.as_ref().map(|piece| piece.shape);
+    ///     (rows, shape, game.next.clone(), game.hold, game.score, game.pieces)
+    /// };
+    /// let drop = |game: &mut Game| {
+    ///     game.update(Input::HARD_DROP);
+    ///     game.update(Input::NONE);
+    /// };
+    /// let start = state(&game);
+    /// drop(&mut game);
+    /// // A hold is not a placement, so it is kept when the drop is undone
+    /// game.try_hold().unwrap();
+    /// let held = state(&game);
+    /// drop(&mut game);
+    /// assert_ne!(state(&game), held);
+    ///
+    /// game.undo().unwrap();
+    /// assert_eq!(state(&game), held);
+    /// game.undo().unwrap();
+    /// assert_eq!(state(&game), start);
+    /// assert!(game.undo().is_err());
+    ///
+    /// game.redo().unwrap();
+    /// assert_eq!(state(&game), held);
+    /// ```
+    pub fn undo(&mut self) -> Result<(), String> {
+        let snapshot = self.undo_stack.pop().ok_or("Nothing to undo")?;
+        if let Some(current) = self.spawn_snapshot.take() {
+            self.redo_stack.push(current);
+        }
+        self.restore(snapshot);
+        Ok(())
+    }
+
+    /// Replays a placement taken back by [`undo`](Game::undo).
+    pub fn redo(&mut self) -> Result<(), String> {
+        let snapshot = self.redo_stack.pop().ok_or("Nothing to redo")?;
+        if let Some(current) = self.spawn_snapshot.take() {
+            self.undo_stack.push(current);
+        }
+        self.restore(snapshot);
+        Ok(())
+    }
+
+    fn set_level(&mut self, level: u32) {
+        if level / 100 > self.level / 100 || (level == MAX_LEVEL && self.level < MAX_LEVEL) {
+            self.section_splits.push(SectionSplit {
+                level,
+                frame: self.frame,
+                score: self.score,
+            });
+        }
+        self.level = level;
+    }
+
+    /// Advances the game by one frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The buttons held during this frame
+    pub fn update(&mut self, input: Input) {
+        if !matches!(self.state, GameState::Playing) {
+            return;
+        }
+        self.frame += 1;
+        let pressed = input.pressed_since(self.held);
+        self.held = input;
+        self.stats.record_keys(pressed);
+        let moves = [
+            Input::LEFT,
+            Input::RIGHT,
+            Input::ROTATE_CW,
+            Input::ROTATE_CCW,
+        ];
+        self.piece_inputs += moves
+            .iter()
+            .filter(|&&button| pressed.contains(button))
+            .count() as u32;
+        let timings = self.ruleset.timings_at(self.level);
+
+        match self.phase {
+            Phase::LineClear(frames) | Phase::Are(frames) if frames > 1 => {
+                self.charge_das(input, timings.das);
+                self.phase = match self.phase {
+                    Phase::LineClear(_) => Phase::LineClear(frames - 1),
+                    _ => Phase::Are(frames - 1),
+                };
                 return;
             }
-            counter += 1;
-            self.current_piece = Some(Tetromino::new(
-                (3, 0),
-                match_shape((rand_piece_index + counter) % 7),
-            ));
+            Phase::LineClear(_) => {
+                self.charge_das(input, timings.das);
+                self.wait_or_spawn(timings.line_are);
+                return;
+            }
+            Phase::Are(_) => {
+                self.charge_das(input, timings.das);
+                self.spawn_after_are();
+                return;
+            }
+            Phase::Falling => {}
+        }
+
+        if pressed.contains(Input::HOLD) {
+            let _ = self.try_hold();
+        }
+        let before = self
+            .current_piece
+            .as_ref()
+            .map(|piece| (piece.pos, piece.rotation));
+        if pressed.contains(Input::ROTATE_CW) {
+            let _ = self.try_rotate_clock();
+        }
+        if pressed.contains(Input::ROTATE_CCW) {
+            let _ = self.try_rotate_counter();
+        }
+        self.auto_shift(input, pressed, timings.das, timings.arr);
+        let after = self
+            .current_piece
+            .as_ref()
+            .map(|piece| (piece.pos, piece.rotation));
+        // Moving a grounded piece restarts its lock delay a limited number of times
+        if before != after && self.lock_counter > 0 && self.lock_resets < timings.lock_resets {
+            self.lock_counter = 0;
+            self.lock_resets += 1;
+        }
+        if pressed.contains(Input::HARD_DROP) {
+            match self.ruleset.hard_drop {
+                HardDrop::None => {}
+                HardDrop::Sonic => self.hard_lock(),
+                HardDrop::Lock => {
+                    self.hard_lock();
+                    let _ = self.lock_current_piece();
+                }
+            }
+        }
+
+        let soft_drop = input.contains(Input::SOFT_DROP);
+        if soft_drop {
+            self.soft_drop_frames += 1;
+        }
+        let gravity = if soft_drop {
+            self.ruleset.soft_drop_gravity(self.level)
+        } else {
+            self.ruleset.gravity(self.level)
+        };
+        let rows = match gravity {
+            Gravity::Frames(frames) => {
+                self.gravity_counter += 1;
+                if self.gravity_counter >= frames {
+                    self.gravity_counter = 0;
+                    1
+                } else {
+                    0
+                }
+            }
+            Gravity::Subrows(subrows) => {
+                self.gravity_counter += subrows;
+                let rows = self.gravity_counter / 256;
+                self.gravity_counter %= 256;
+                rows
+            }
+        };
+
+        match timings.lock_delay {
+            None => {
+                if rows > 0 {
+                    self.tick_down();
+                }
+            }
+            Some(lock_delay) => {
+                for _ in 0..rows {
+                    if self.try_move(Direction::Down).is_err() {
+                        break;
+                    }
+                    self.lock_counter = 0;
+                }
+                if self.is_grounded() {
+                    self.lock_counter += 1;
+                    if self.lock_counter >= lock_delay
+                        || (soft_drop && self.ruleset.soft_drop_locks)
+                    {
+                        let _ = self.lock_current_piece();
+                    }
+                }
+            }
+        }
+        let level = self.ruleset.level_for(self.lines, self.frame);
+        if level > self.level {
+            self.set_level(level);
+        }
+    }
+
+    fn wait_or_spawn(&mut self, are: u32) {
+        if are > 0 {
+            self.phase = Phase::Are(are);
+        } else {
+            self.spawn_after_are();
+        }
+    }
+
+    fn spawn_after_are(&mut self) {
+        // Wait for other players' pieces to leave the spawn area
+        if let Some(&shape) = self.next.front()
+            && self.overlaps_obstacle(&self.spawn_position(shape))
+        {
+            self.phase = Phase::Are(1);
+            return;
+        }
+        self.spawn_next_piece();
+        let level = self.ruleset.level_after_spawn(self.level);
+        self.set_level(level);
+    }
+
+    fn is_grounded(&self) -> bool {
+        self.current_piece.as_ref().is_some_and(|piece| {
+            let mut below = piece.clone();
+            below.move_piece(Direction::Down);
+            !fits(&self.board, &self.obstacles, &below)
+        })
+    }
+
+    fn overlaps_obstacle(&self, piece: &Tetromino) -> bool {
+        let cells: Vec<(i32, i32)> = piece.cells().collect();
+        self.obstacles
+            .iter()
+            .flat_map(|obstacle| obstacle.cells())
+            .any(|cell| cells.contains(&cell))
+    }
+
+    fn charge_das(&mut self, input: Input, das: u32) {
+        if input.contains(Input::LEFT) != input.contains(Input::RIGHT) {
+            self.das_counter = (self.das_counter + 1).min(das);
+        } else {
+            self.das_counter = 0;
+        }
+    }
+
+    fn auto_shift(&mut self, input: Input, pressed: Input, das: u32, arr: u32) {
+        let direction = match (input.contains(Input::LEFT), input.contains(Input::RIGHT)) {
+            (true, false) => Direction::Left,
+            (false, true) => Direction::Right,
+            _ => {
+                self.das_counter = 0;
+                return;
+            }
+        };
+        if pressed.contains(Input::LEFT) || pressed.contains(Input::RIGHT) {
+            self.das_counter = 0;
+            let _ = self.try_move(direction);
+            return;
+        }
+        self.das_counter += 1;
+        if self.das_counter >= das {
+            self.das_counter = das.saturating_sub(arr);
+            let _ = self.try_move(direction);
+            // An ARR of 0 shifts straight to the wall
+            while arr == 0 && self.try_move(direction).is_ok() {}
         }
-        self.state = GameState::GameOver;
     }
 
     pub fn try_move(&mut self, direction: Direction) -> Result<(), String> {
@@ -55,7 +676,8 @@ impl Game {
                 Direction::Up => Direction::Down,
             };
             piece.move_piece(direction);
-            if self.board.can_place(piece) {
+            if fits(&self.board, &self.obstacles, piece) {
+                self.last_move_rotation = false;
                 return Ok(());
             }
             piece.move_piece(old_direction);
@@ -68,7 +690,13 @@ impl Game {
         if let Some(piece) = &mut self.current_piece {
             //compute new position after movement
             piece.rotate_cw();
-            if self.board.can_place(piece) {
+            if try_kicks(
+                &self.board,
+                &self.obstacles,
+                piece,
+                (piece.rotation + 3) % 4,
+            ) {
+                self.last_move_rotation = true;
                 return Ok(());
             }
             piece.rotate_ccw();
@@ -80,7 +708,13 @@ impl Game {
         if let Some(piece) = &mut self.current_piece {
             //compute new position after movement
             piece.rotate_ccw();
-            if self.board.can_place(piece) {
+            if try_kicks(
+                &self.board,
+                &self.obstacles,
+                piece,
+                (piece.rotation + 1) % 4,
+            ) {
+                self.last_move_rotation = true;
                 return Ok(());
             }
             piece.rotate_cw();
@@ -89,32 +723,174 @@ impl Game {
         Err("No current piece".to_string())
     }
     pub fn lock_current_piece(&mut self) -> Result<(), String> {
-        if let Some(piece) = &self.current_piece {
-            self.board.lock_piece(piece);
-            let lines_cleared = self.board.clear_lines();
-            self.score += match lines_cleared {
-                1 => 100,
-                2 => 300,
-                3 => 500,
-                4 => 800,
-                _ => 0,
+        if let Some(piece) = self.current_piece.take() {
+            let tspin = if self.last_move_rotation {
+                detect_t_spin(&self.board, &piece)
+            } else {
+                TSpin::None
+            };
+            if self.ruleset.finesse != FinesseMode::Off && self.judge_finesse(&piece) {
+                return Ok(());
+            }
+            self.board.lock_piece(&piece, self.frame);
+            self.pieces += 1;
+            // In Big mode every line is two board rows
+            let lines_cleared = self.board.clear_lines() / piece.scale as u32;
+            let timings = self.ruleset.timings_at(self.level);
+            if lines_cleared == 0 {
+                self.combo = 1;
+                self.streak = 0;
+                self.last_clear = None;
+                self.stats.record_lock(tspin, None);
+                if self.push_incoming() {
+                    self.state = GameState::GameOver;
+                    return Ok(());
+                }
+                self.wait_or_spawn(timings.are);
+                return Ok(());
+            }
+            self.combo += 2 * lines_cleared - 2;
+            let mut clear = LineClear {
+                lines: lines_cleared,
+                level: self.level,
+                combo: self.combo,
+                soft_drop_frames: self.soft_drop_frames,
+                perfect_clear: self.board.is_empty(),
+                tspin,
+                streak: self.streak,
+                back_to_back: false,
             };
-            self.set_random_piece();
+            clear.back_to_back = self.back_to_back && clear.is_difficult();
+            self.back_to_back = clear.is_difficult();
+            self.streak += 1;
+            self.last_clear = Some(clear);
+            self.stats.record_lock(tspin, Some(&clear));
+            self.score += self.ruleset.line_score(&clear);
+            self.lines += lines_cleared;
+            if self.lines >= SPRINT_LINES && self.sprint_frame.is_none() {
+                self.sprint_frame = Some(self.frame);
+            }
+            let level = self.ruleset.level_after_clear(self.level, lines_cleared);
+            self.set_level(level);
+            if self.ruleset.is_complete(self.level) {
+                self.state = GameState::Cleared;
+            }
+            if timings.line_clear > 0 {
+                self.phase = Phase::LineClear(timings.line_clear);
+            } else {
+                self.wait_or_spawn(timings.line_are);
+            }
             return Ok(());
         }
         Err("No current piece or place to put it!".to_string())
     }
 
+    /// Compares the presses spent on a piece about to lock with the fewest
+    /// that place it, counting a fault when more were spent. Pieces that
+    /// were soft dropped are not judged.
+    ///
+    /// # Returns
+    ///
+    /// Whether the piece was taken back to be placed again, in strict mode.
+    fn judge_finesse(&mut self, piece: &Tetromino) -> bool {
+        if self.soft_drop_frames > 0 || !self.obstacles.is_empty() {
+            return false;
+        }
+        let start = self.spawn_position(piece.shape);
+        let Some(keys) = finesse::minimal_keys(&self.board, &start, piece) else {
+            return false;
+        };
+        let judged = Finesse {
+            piece: piece.clone(),
+            inputs: self.piece_inputs,
+            keys,
+            frame: self.frame,
+        };
+        let fault = judged.is_fault();
+        self.last_finesse = Some(judged);
+        if !fault {
+            return false;
+        }
+        self.finesse_faults += 1;
+        if self.ruleset.finesse == FinesseMode::Strict
+            && let Some(snapshot) = self.spawn_snapshot.clone()
+        {
+            self.restore(snapshot);
+            return true;
+        }
+        false
+    }
+
+    /// Returns whether the current piece can be swapped with the hold piece.
+    pub fn can_hold(&self) -> bool {
+        self.ruleset.hold && !self.hold_used
+    }
+
+    /// Returns how many pieces in a row have cleared lines.
+    pub fn streak(&self) -> u32 {
+        self.streak
+    }
+
+    /// Returns whether the last clear was difficult, so that the next
+    /// difficult clear is back-to-back.
+    pub fn back_to_back(&self) -> bool {
+        self.back_to_back
+    }
+
+    /// Returns the total garbage lines waiting in [`incoming`](Game::incoming).
+    pub fn pending_garbage(&self) -> u32 {
+        self.incoming.iter().map(|garbage| garbage.lines).sum()
+    }
+
+    /// Cancels an attack against the incoming garbage, oldest first.
+    ///
+    /// # Arguments
+    ///
+    /// * `lines` - The lines of the attack
+    ///
+    /// # Returns
+    ///
+    /// The lines left over once nothing is incoming any more.
+    pub fn cancel_garbage(&mut self, mut lines: u32) -> u32 {
+        while lines > 0 {
+            let Some(garbage) = self.incoming.front_mut() else {
+                break;
+            };
+            let cancelled = lines.min(garbage.lines);
+            garbage.lines -= cancelled;
+            lines -= cancelled;
+            if garbage.lines == 0 {
+                self.incoming.pop_front();
+            }
+        }
+        lines
+    }
+
+    /// Pushes all incoming garbage into the board.
+    ///
+    /// # Returns
+    ///
+    /// Whether any filled cell was pushed out of the top of the board.
+    fn push_incoming(&mut self) -> bool {
+        let mut overflow = false;
+        while let Some(garbage) = self.incoming.pop_front() {
+            overflow |= self.board.push_garbage(garbage.lines, garbage.hole_column);
+        }
+        overflow
+    }
+
     pub fn hard_lock(&mut self) {
         if let Some(piece) = &mut self.current_piece {
-            let mut temp_piece = Tetromino::new(piece.pos, piece.shape);
-            temp_piece.rotation = piece.rotation;
-            while self.board.can_place(&temp_piece) {
+            let mut temp_piece = piece.clone();
+            while fits(&self.board, &self.obstacles, &temp_piece) {
                 temp_piece.move_piece(Direction::Down);
             }
             if temp_piece.pos != piece.pos {
                 temp_piece.move_piece(Direction::Up);
             }
+            if temp_piece.pos != piece.pos {
+                self.last_move_rotation = false;
+            }
             piece.pos = temp_piece.pos;
         }
     }
@@ -126,6 +902,80 @@ impl Game {
     }
 }
 
+/// Returns the column pieces spawn in by default: the left edge of their
+/// bounding box when centred on the board, leaning left.
+///
+/// # Arguments
+///
+/// * `board` - The board pieces spawn on
+/// * `ruleset` - The rules played by, which decide the size of the pieces
+pub fn default_spawn_column(board: &Board, ruleset: &Ruleset) -> i32 {
+    (board.width() as i32 / ruleset.piece_scale() - 3) / 2
+}
+
+/// Detects a T-spin with the three-corner rule: at least three of the four
+/// cells diagonal to the centre of the T must be filled or out of bounds.
+pub(crate) fn detect_t_spin(board: &Board, piece: &Tetromino) -> TSpin {
+    if !matches!(piece.shape, Shape::T) || piece.scale != 1 {
+        return TSpin::None;
+    }
+    // The centre of the T and the two corners on the side it points to
+    let ((cx, cy), front) = match (piece.rotation_system, piece.rotation) {
+        (RotationSystem::Srs, 0) => ((1, 1), [(-1, -1), (1, -1)]),
+        (RotationSystem::Srs, 1) => ((1, 1), [(1, -1), (1, 1)]),
+        (RotationSystem::Srs, 2) => ((1, 1), [(-1, 1), (1, 1)]),
+        (RotationSystem::Srs, _) => ((1, 1), [(-1, -1), (-1, 1)]),
+        (_, 0) => ((1, 0), [(-1, 1), (1, 1)]),
+        (_, 1) => ((1, 1), [(-1, -1), (-1, 1)]),
+        (_, 2) => ((1, 1), [(-1, -1), (1, -1)]),
+        (_, _) => ((0, 1), [(1, -1), (1, 1)]),
+    };
+    let (cx, cy) = (piece.pos.0 + cx, piece.pos.1 + cy);
+    let filled = |(dx, dy): (i32, i32)| !board.pos_free(cx + dx, cy + dy);
+    let corners = [(-1, -1), (1, -1), (-1, 1), (1, 1)]
+        .into_iter()
+        .filter(|&corner| filled(corner))
+        .count();
+    if corners < 3 {
+        TSpin::None
+    } else if front.into_iter().all(filled) {
+        TSpin::Full
+    } else {
+        TSpin::Mini
+    }
+}
+
+/// Checks whether a piece can be placed on the board without overlapping
+/// any of the obstacles.
+fn fits(board: &Board, obstacles: &[Tetromino], piece: &Tetromino) -> bool {
+    board.can_place(piece)
+        && obstacles.iter().all(|obstacle| {
+            obstacle
+                .cells()
+                .all(|cell| piece.cells().all(|other| other != cell))
+        })
+}
+
+/// Checks whether a freshly rotated piece fits, trying the kicks of its
+/// rotation system if it does not. The piece is left at the kicked position
+/// on success and at its original position on failure.
+pub(crate) fn try_kicks(
+    board: &Board,
+    obstacles: &[Tetromino],
+    piece: &mut Tetromino,
+    from: u8,
+) -> bool {
+    let origin = piece.pos;
+    for &(dx, dy) in piece.kicks(from) {
+        piece.pos = (origin.0 + dx, origin.1 + dy);
+        if fits(board, obstacles, piece) {
+            return true;
+        }
+    }
+    piece.pos = origin;
+    false
+}
+
 pub fn match_shape(shape_index: u8) -> Shape {
     match shape_index {
         0 => Shape::I,
@@ -0,0 +1,147 @@
+//! Garbage module deciding where the holes in garbage rows go.
+//!
+//! A [`GarbageHoles`] generator owns its own seeded RNG, like a
+//! [`Randomizer`](crate::randomizer::Randomizer), so the garbage a match
+//! produces only depends on its seed and the attacks made.
+
+/// Garbage rows sharing a hole column, waiting to be pushed into a board
+/// from below with [`Board::push_garbage`](crate::board::Board::push_garbage).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Garbage {
+    pub lines: u32,
+    pub hole_column: usize,
+}
+
+/// How the holes of garbage rows are placed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HoleStrategy {
+    /// Every row of an attack has its hole in the same column.
+    Clean,
+    /// Every row has its hole in a random column.
+    Random,
+    /// Every row after the first of an attack moves the hole to a new
+    /// random column with this percentage of chance.
+    Messy(u32),
+}
+
+impl HoleStrategy {
+    /// The strategies offered by the versus menu, from cleanest to messiest.
+    pub const CHOICES: [HoleStrategy; 4] = [
+        HoleStrategy::Clean,
+        HoleStrategy::Messy(30),
+        HoleStrategy::Messy(70),
+        HoleStrategy::Random,
+    ];
+
+    /// Parses a strategy written as `clean`, `random` or `messy:PERCENT`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_tetris::garbage::HoleStrategy;
+    /// assert_eq!(HoleStrategy::parse("messy:30"), Ok(HoleStrategy::Messy(30)));
+    /// assert!(HoleStrategy::parse("messy:150").is_err());
+    /// ```
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text.split_once(':') {
+            None if text == "clean" => Ok(HoleStrategy::Clean),
+            None if text == "random" => Ok(HoleStrategy::Random),
+            Some(("messy", percent)) => match percent.parse() {
+                Ok(percent @ 0..=100) => Ok(HoleStrategy::Messy(percent)),
+                _ => Err(format!("Messiness '{}' is not a percentage", percent)),
+            },
+            _ => Err(format!("Unknown garbage strategy '{}'", text)),
+        }
+    }
+
+    /// A short description of the strategy for the UI.
+    pub fn describe(&self) -> String {
+        match self {
+            HoleStrategy::Clean => "Clean".to_string(),
+            HoleStrategy::Random => "Random".to_string(),
+            HoleStrategy::Messy(percent) => format!("Messy {}%", percent),
+        }
+    }
+}
+
+/// A seeded generator of garbage hole columns.
+#[derive(Clone)]
+pub struct GarbageHoles {
+    strategy: HoleStrategy,
+    rng: fastrand::Rng,
+}
+
+impl GarbageHoles {
+    /// Creates a new generator.
+    ///
+    /// # Arguments
+    ///
+    /// * `strategy` - How the holes are placed
+    /// * `seed` - The seed for the internal RNG
+    pub fn new(strategy: HoleStrategy, seed: u64) -> Self {
+        Self {
+            strategy,
+            rng: fastrand::Rng::with_seed(seed),
+        }
+    }
+
+    /// Splits an attack into garbage to push, one entry per run of rows
+    /// sharing a hole column, in the order they must be pushed.
+    ///
+    /// # Arguments
+    ///
+    /// * `lines` - The number of garbage rows in the attack
+    /// * `width` - The width of the board the garbage is sent to
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_tetris::garbage::{GarbageHoles, HoleStrategy};
+    /// let mut holes = GarbageHoles::new(HoleStrategy::Clean, 0);
+    /// let garbage = holes.attack(3, 10);
+    /// assert_eq!(garbage.len(), 1);
+    /// assert_eq!(garbage[0].lines, 3);
+    ///
+    /// let mut holes = GarbageHoles::new(HoleStrategy::Random, 0);
+    /// let garbage = holes.attack(50, 16);
+    /// assert!(garbage.iter().any(|garbage| garbage.hole_column >= 10));
+    /// assert!(garbage.iter().all(|garbage| garbage.hole_column < 16));
+    /// ```
+    pub fn attack(&mut self, lines: u32, width: usize) -> Vec<Garbage> {
+        let mut garbage: Vec<Garbage> = Vec::new();
+        for hole_column in self.columns(lines, width) {
+            match garbage.last_mut() {
+                Some(last) if last.hole_column == hole_column => last.lines += 1,
+                _ => garbage.push(Garbage {
+                    lines: 1,
+                    hole_column,
+                }),
+            }
+        }
+        garbage
+    }
+
+    /// Returns the hole column of every row of an attack.
+    fn columns(&mut self, lines: u32, width: usize) -> Vec<usize> {
+        let mut column = self.rng.usize(0..width);
+        (0..lines)
+            .map(|line| {
+                match self.strategy {
+                    HoleStrategy::Clean => {}
+                    HoleStrategy::Random => column = self.rng.usize(0..width),
+                    HoleStrategy::Messy(percent) => {
+                        if line > 0 && self.rng.u32(0..100) < percent {
+                            column = self.new_column(column, width);
+                        }
+                    }
+                }
+                column
+            })
+            .collect()
+    }
+
+    /// Picks a random column of a board `width` wide other than `column`.
+    fn new_column(&mut self, column: usize, width: usize) -> usize {
+        (column + self.rng.usize(1..width)) % width
+    }
+}
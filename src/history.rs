@@ -0,0 +1,337 @@
+//! History module keeping every finished game, to follow progress over time.
+//!
+//! Games are appended to a file in the user's data directory, see
+//! [`leaderboard::data_dir`], one line per game with tab separated fields:
+//!
+//! ```text
+//! mode  date  frames  score  lines  pieces  keys  attack  faults  cleared  sprint
+//! ```
+//!
+//! where `date` is in seconds since the Unix epoch, `cleared` is `1` when
+//! the game reached its mode's goal and `sprint` is the frames taken to clear
+//! [`SPRINT_LINES`] lines, or `-` when the game cleared fewer. Lines are only
+//! ever appended, so the file can grow across sessions without being
+//! rewritten.
+//!
+//! [`SPRINT_LINES`]: crate::game::SPRINT_LINES
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::game::{Game, GameState};
+use crate::leaderboard;
+use crate::ruleset::{FRAME_RATE, Mode};
+
+/// Seconds in a day, the unit progress is plotted in.
+pub const DAY: u64 = 86_400;
+
+/// One finished game.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub mode: Mode,
+    /// When the game ended, in seconds since the Unix epoch.
+    pub date: u64,
+    /// How long the game lasted.
+    pub frames: u64,
+    pub score: u32,
+    pub lines: u32,
+    pub pieces: u32,
+    pub keys: u32,
+    pub attack: u32,
+    pub finesse_faults: u32,
+    /// Whether the game reached its mode's goal rather than topping out.
+    pub cleared: bool,
+    /// How long the game took to clear
+    /// [`SPRINT_LINES`](crate::game::SPRINT_LINES) lines, if it did.
+    pub sprint_frames: Option<u64>,
+}
+
+impl Record {
+    /// Creates the record of a finished game.
+    ///
+    /// # Arguments
+    ///
+    /// * `game` - The game
+    /// * `date` - When it ended, in seconds since the Unix epoch
+    pub fn new(game: &Game, date: u64) -> Self {
+        Self {
+            mode: game.ruleset.mode,
+            date,
+            frames: game.frame,
+            score: game.score,
+            lines: game.lines,
+            pieces: game.stats.pieces,
+            keys: game.stats.keys,
+            attack: game.stats.attack,
+            finesse_faults: game.finesse_faults,
+            cleared: matches!(game.state, GameState::Cleared),
+            sprint_frames: game.sprint_frame,
+        }
+    }
+
+    /// Returns the pieces locked per second.
+    pub fn pps(&self) -> f64 {
+        if self.frames == 0 {
+            return 0.0;
+        }
+        f64::from(self.pieces) * FRAME_RATE / self.frames as f64
+    }
+
+    /// Returns the record as a line of the history file, without the line
+    /// break.
+    pub fn to_line(&self) -> String {
+        let sprint = self
+            .sprint_frames
+            .map_or("-".to_string(), |frames| frames.to_string());
+        format!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            self.mode.key(),
+            self.date,
+            self.frames,
+            self.score,
+            self.lines,
+            self.pieces,
+            self.keys,
+            self.attack,
+            self.finesse_faults,
+            u8::from(self.cleared),
+            sprint
+        )
+    }
+
+    /// Parses a line of the history file. Lines written before sprint times
+    /// were kept have no `sprint` field.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_tetris::history::Record;
+    /// # use rust_tetris::ruleset::Mode;
+    /// let record = Record::parse("master\t86400\t3600\t1200\t40\t100\t300\t12\t0\t1\t3000").unwrap();
+    /// assert_eq!(record.mode, Mode::Master);
+    /// assert!(record.cleared);
+    /// assert_eq!(record.sprint_frames, Some(3000));
+    /// assert_eq!(record.pps(), 100.0 / 60.0);
+    /// assert_eq!(Record::parse(&record.to_line()), Ok(record));
+    ///
+    /// let old = Record::parse("standard\t86400\t3600\t1200\t40\t100\t300\t12\t0\t0").unwrap();
+    /// assert_eq!(old.sprint_frames, None);
+    /// ```
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() == 10 {
+            fields.push("-");
+        }
+        let [
+            mode,
+            date,
+            frames,
+            score,
+            lines,
+            pieces,
+            keys,
+            attack,
+            faults,
+            cleared,
+            sprint,
+        ] = fields[..]
+        else {
+            return Err(format!("Expected 11 fields, found '{}'", line));
+        };
+        let number = |field: &str| {
+            field
+                .parse::<u64>()
+                .map_err(|_| format!("Invalid number '{}'", field))
+        };
+        Ok(Self {
+            mode: Mode::from_key(mode).ok_or(format!("Unknown mode '{}'", mode))?,
+            date: number(date)?,
+            frames: number(frames)?,
+            score: number(score)? as u32,
+            lines: number(lines)? as u32,
+            pieces: number(pieces)? as u32,
+            keys: number(keys)? as u32,
+            attack: number(attack)? as u32,
+            finesse_faults: number(faults)? as u32,
+            cleared: number(cleared)? != 0,
+            sprint_frames: match sprint {
+                "-" => None,
+                frames => Some(number(frames)?),
+            },
+        })
+    }
+}
+
+/// A measure of progress, worked out for every day games were played.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Metric {
+    /// The fastest [`SPRINT_LINES`](crate::game::SPRINT_LINES) lines of the
+    /// day, in seconds.
+    Sprint,
+    /// The average pieces per second of the day's games.
+    Pps,
+    /// The best score of the day.
+    Score,
+}
+
+impl Metric {
+    /// Every metric, in the order they are plotted.
+    pub const ALL: [Metric; 3] = [Metric::Sprint, Metric::Pps, Metric::Score];
+
+    /// The display name of the metric.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Metric::Sprint => "Best 40 lines time",
+            Metric::Pps => "Average PPS",
+            Metric::Score => "Best score",
+        }
+    }
+
+    /// Returns whether lower values are better.
+    pub fn lower_is_better(&self) -> bool {
+        *self == Metric::Sprint
+    }
+}
+
+/// Every finished game, and where they are stored.
+#[derive(Debug, Clone)]
+pub struct History {
+    path: PathBuf,
+    records: Vec<Record>,
+}
+
+impl History {
+    /// Creates an empty history stored at a path.
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            records: Vec::new(),
+        }
+    }
+
+    /// Returns where the history is kept, inside the user's data directory.
+    pub fn path() -> Option<PathBuf> {
+        leaderboard::data_dir().map(|dir| dir.join("history.txt"))
+    }
+
+    /// Loads a history file, starting an empty one if it does not exist.
+    ///
+    /// Lines that cannot be parsed are skipped with a warning, so that one
+    /// damaged game does not lose the others.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let mut history = Self::new(path.to_path_buf());
+        if !path.exists() {
+            return Ok(history);
+        }
+        let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        for (number, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match Record::parse(line) {
+                Ok(record) => history.records.push(record),
+                Err(err) => eprintln!(
+                    "Skipping line {} of {}: {}",
+                    number + 1,
+                    path.display(),
+                    err
+                ),
+            }
+        }
+        Ok(history)
+    }
+
+    /// Adds a game without writing it to the history file.
+    pub fn push(&mut self, record: Record) {
+        self.records.push(record);
+    }
+
+    /// Adds a game and appends it to the history file, creating the file
+    /// and its directory if needed.
+    ///
+    /// The game is kept in memory even if writing it fails.
+    pub fn append(&mut self, record: Record) -> Result<(), String> {
+        let line = format!("{}\n", record.to_line());
+        self.push(record);
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|err| err.to_string())
+    }
+
+    /// Returns the games of a mode, oldest first.
+    pub fn records(&self, mode: Mode) -> Vec<&Record> {
+        self.records
+            .iter()
+            .filter(|record| record.mode == mode)
+            .collect()
+    }
+
+    /// Returns a metric of a mode for every day it was played.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - The mode
+    /// * `metric` - The metric
+    ///
+    /// # Returns
+    ///
+    /// The days, in days since the Unix epoch, with the metric's value,
+    /// oldest first. Days without a game the metric counts are left out.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::path::PathBuf;
+    /// # use rust_tetris::history::{DAY, History, Metric, Record};
+    /// # use rust_tetris::ruleset::Mode;
+    /// let mut history = History::new(PathBuf::from("history.txt"));
+    /// for (date, score, sprint) in [(DAY, 100, "-"), (DAY + 60, 300, "3600"), (3 * DAY, 200, "-")] {
+    ///     let line = format!("standard\t{date}\t6000\t{score}\t40\t0\t0\t0\t0\t0\t{sprint}");
+    ///     history.push(Record::parse(&line).unwrap());
+    /// }
+    /// assert_eq!(history.daily(Mode::Standard, Metric::Score), [(1, 300.0), (3, 200.0)]);
+    /// assert_eq!(history.daily(Mode::Standard, Metric::Sprint), [(1, 60.0)]);
+    /// ```
+    pub fn daily(&self, mode: Mode, metric: Metric) -> Vec<(u64, f64)> {
+        let mut days: Vec<(u64, Vec<&Record>)> = Vec::new();
+        for record in self.records(mode) {
+            if metric == Metric::Sprint && record.sprint_frames.is_none() {
+                continue;
+            }
+            let day = record.date / DAY;
+            match days.iter_mut().find(|(other, _)| *other == day) {
+                Some((_, records)) => records.push(record),
+                None => days.push((day, vec![record])),
+            }
+        }
+        days.sort_by_key(|&(day, _)| day);
+
+        days.into_iter()
+            .map(|(day, records)| {
+                let value = match metric {
+                    Metric::Sprint => records
+                        .iter()
+                        .filter_map(|record| record.sprint_frames)
+                        .map(|frames| frames as f64 / FRAME_RATE)
+                        .fold(f64::INFINITY, f64::min),
+                    Metric::Pps => {
+                        records.iter().map(|record| record.pps()).sum::<f64>()
+                            / records.len() as f64
+                    }
+                    Metric::Score => records
+                        .iter()
+                        .map(|record| f64::from(record.score))
+                        .fold(0.0, f64::max),
+                };
+                (day, value)
+            })
+            .collect()
+    }
+}
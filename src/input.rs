@@ -0,0 +1,71 @@
+//! Input module describing the buttons held during a single game frame.
+//!
+//! The game is simulated in fixed frames, and every frame is driven by an
+//! [`Input`] value: a small bitmask of the buttons currently held down.
+//! Edge detection (pressed this frame vs. held since earlier) is done by
+//! the [`Game`](crate::game::Game) itself so that the simulation only
+//! depends on the sequence of inputs it was given.
+
+use std::ops::{BitOr, BitOrAssign};
+
+/// The set of buttons held down during one frame.
+///
+/// # Examples
+///
+/// ```
+/// # use rust_tetris::input::Input;
+/// let input = Input::LEFT | Input::SOFT_DROP;
+/// assert!(input.contains(Input::LEFT));
+/// assert!(!input.contains(Input::RIGHT));
+/// ```
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Input(pub u8);
+
+impl Input {
+    /// No buttons held.
+    pub const NONE: Input = Input(0);
+    /// Shift the piece left.
+    pub const LEFT: Input = Input(1 << 0);
+    /// Shift the piece right.
+    pub const RIGHT: Input = Input(1 << 1);
+    /// Soft drop (faster gravity while held).
+    pub const SOFT_DROP: Input = Input(1 << 2);
+    /// Hard drop, when the ruleset allows it.
+    pub const HARD_DROP: Input = Input(1 << 3);
+    /// Rotate clockwise.
+    pub const ROTATE_CW: Input = Input(1 << 4);
+    /// Rotate counter-clockwise.
+    pub const ROTATE_CCW: Input = Input(1 << 5);
+    /// Swap the current piece with the held one, when the ruleset allows it.
+    pub const HOLD: Input = Input(1 << 6);
+    /// Switch to the next targeting strategy in a royale match.
+    pub const TARGET: Input = Input(1 << 7);
+
+    /// Returns `true` if every button in `other` is held in `self`.
+    pub fn contains(self, other: Input) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns the buttons held in `self` that were not held in `previous`.
+    ///
+    /// # Arguments
+    ///
+    /// * `previous` - The input of the preceding frame
+    pub fn pressed_since(self, previous: Input) -> Input {
+        Input(self.0 & !previous.0)
+    }
+}
+
+impl BitOr for Input {
+    type Output = Input;
+
+    fn bitor(self, rhs: Input) -> Input {
+        Input(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Input {
+    fn bitor_assign(&mut self, rhs: Input) {
+        self.0 |= rhs.0;
+    }
+}
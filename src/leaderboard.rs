@@ -0,0 +1,303 @@
+//! Leaderboard module keeping the best games of every mode.
+//!
+//! Scores are stored in the user's data directory, see [`data_dir`], one
+//! line per entry with tab separated fields:
+//!
+//! ```text
+//! mode  score  lines  frames  date  seed  replay  name
+//! ```
+//!
+//! where `date` is in seconds since the Unix epoch and `replay` is the file
+//! name of the game's [`Replay`](crate::replay::Replay) in the `replays`
+//! directory next to the scores, or `-` when there is none.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::ruleset::Mode;
+
+/// The modes with leaderboards: the single player modes that end.
+pub const MODES: [Mode; 6] = [
+    Mode::Standard,
+    Mode::Nes,
+    Mode::Master,
+    Mode::Invisible,
+    Mode::Fading,
+    Mode::Big,
+];
+
+/// How many entries are kept for every mode.
+pub const MAX_ENTRIES: usize = 10;
+
+/// The longest name an entry can have, in characters.
+pub const MAX_NAME: usize = 12;
+
+/// One game on a leaderboard.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub name: String,
+    pub score: u32,
+    pub lines: u32,
+    /// How long the game lasted.
+    pub frames: u64,
+    /// When the game was played, in seconds since the Unix epoch.
+    pub date: u64,
+    pub seed: u64,
+    /// The file name of the replay, in [`Leaderboard::replay_dir`].
+    pub replay: Option<String>,
+}
+
+impl Entry {
+    /// Returns whether the entry ranks above another: a higher score, or
+    /// the same score in less time.
+    fn beats(&self, other: &Entry) -> bool {
+        (self.score, std::cmp::Reverse(self.frames))
+            > (other.score, std::cmp::Reverse(other.frames))
+    }
+}
+
+/// The best games of every mode, and where they are stored.
+#[derive(Debug, Clone)]
+pub struct Leaderboard {
+    path: PathBuf,
+    entries: Vec<(Mode, Entry)>,
+}
+
+/// Returns the directory the game keeps its data in, inside the user's
+/// data directory.
+pub fn data_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("rust_tetris"))
+}
+
+/// Returns the current time in seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs())
+}
+
+/// Formats a time in seconds since the Unix epoch as a `YYYY-MM-DD` date.
+///
+/// # Examples
+///
+/// ```
+/// # use rust_tetris::leaderboard::format_date;
+/// assert_eq!(format_date(0), "1970-01-01");
+/// assert_eq!(format_date(951_782_400), "2000-02-29");
+/// ```
+pub fn format_date(secs: u64) -> String {
+    // Days to a civil date, after Howard Hinnant's algorithm
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+impl Leaderboard {
+    /// Creates an empty leaderboard stored at a path.
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Returns where the leaderboard is kept, inside the user's data
+    /// directory.
+    pub fn path() -> Option<PathBuf> {
+        data_dir().map(|dir| dir.join("scores.txt"))
+    }
+
+    /// Loads a leaderboard file, starting an empty one if it does not exist.
+    ///
+    /// Lines that cannot be parsed are skipped with a warning, so that one
+    /// damaged entry does not lose the others.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let mut leaderboard = Self::new(path.to_path_buf());
+        if !path.exists() {
+            return Ok(leaderboard);
+        }
+        let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        for (number, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match parse_entry(line) {
+                Ok((mode, entry)) => {
+                    leaderboard.insert(mode, entry);
+                }
+                Err(err) => eprintln!(
+                    "Skipping line {} of {}: {}",
+                    number + 1,
+                    path.display(),
+                    err
+                ),
+            }
+        }
+        Ok(leaderboard)
+    }
+
+    /// Writes the leaderboard to its file, creating its directory if needed.
+    pub fn save(&self) -> Result<(), String> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+        }
+        let lines: Vec<String> = self
+            .entries
+            .iter()
+            .map(|(mode, entry)| {
+                format!(
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                    mode.key(),
+                    entry.score,
+                    entry.lines,
+                    entry.frames,
+                    entry.date,
+                    entry.seed,
+                    entry.replay.as_deref().unwrap_or("-"),
+                    entry.name
+                )
+            })
+            .collect();
+        fs::write(&self.path, lines.concat()).map_err(|err| err.to_string())
+    }
+
+    /// Returns the directory replays of the entries are kept in.
+    pub fn replay_dir(&self) -> PathBuf {
+        self.path.with_file_name("replays")
+    }
+
+    /// Returns the entries of a mode, best first.
+    pub fn entries(&self, mode: Mode) -> Vec<&Entry> {
+        self.entries
+            .iter()
+            .filter(|(entry_mode, _)| *entry_mode == mode)
+            .map(|(_, entry)| entry)
+            .collect()
+    }
+
+    /// Returns whether a game would make it onto the leaderboard.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - The mode the game was played in
+    /// * `score` - The final score
+    /// * `frames` - How long the game lasted
+    pub fn qualifies(&self, mode: Mode, score: u32, frames: u64) -> bool {
+        let entries = self.entries(mode);
+        let candidate = Entry {
+            name: String::new(),
+            score,
+            lines: 0,
+            frames,
+            date: 0,
+            seed: 0,
+            replay: None,
+        };
+        entries.len() < MAX_ENTRIES || entries.iter().any(|entry| candidate.beats(entry))
+    }
+
+    /// Adds a game, dropping the worst entry of its mode when it is full.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - The mode the game was played in
+    /// * `entry` - The game
+    ///
+    /// # Returns
+    ///
+    /// The rank of the game among the entries of its mode, from `0`, or
+    /// `None` if it did not make it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::path::PathBuf;
+    /// # use rust_tetris::leaderboard::{Entry, Leaderboard};
+    /// # use rust_tetris::ruleset::Mode;
+    /// let mut leaderboard = Leaderboard::new(PathBuf::from("scores.txt"));
+    /// let entry = |score| Entry {
+    ///     name: "ANA".to_string(),
+    ///     score,
+    ///     lines: 0,
+    ///     frames: 0,
+    ///     date: 0,
+    ///     seed: 0,
+    ///     replay: None,
+    /// };
+    /// assert_eq!(leaderboard.insert(Mode::Standard, entry(100)), Some(0));
+    /// assert_eq!(leaderboard.insert(Mode::Standard, entry(300)), Some(0));
+    /// assert_eq!(leaderboard.insert(Mode::Nes, entry(200)), Some(0));
+    /// assert_eq!(leaderboard.entries(Mode::Standard)[1].score, 100);
+    /// ```
+    pub fn insert(&mut self, mode: Mode, mut entry: Entry) -> Option<usize> {
+        entry.name = clean_name(&entry.name);
+        let index = self
+            .entries
+            .iter()
+            .position(|(other_mode, other)| *other_mode == mode && entry.beats(other))
+            .unwrap_or(self.entries.len());
+        self.entries.insert(index, (mode, entry));
+        let rank = self.entries[..index]
+            .iter()
+            .filter(|(other_mode, _)| *other_mode == mode)
+            .count();
+
+        // Drop whatever fell past the end of the mode's board
+        let mut kept = 0;
+        self.entries.retain(|(other_mode, _)| {
+            kept += usize::from(*other_mode == mode);
+            *other_mode != mode || kept <= MAX_ENTRIES
+        });
+        (rank < MAX_ENTRIES).then_some(rank)
+    }
+}
+
+/// Returns a name that fits in a leaderboard file: no tabs or line breaks,
+/// at most [`MAX_NAME`] characters, and not empty.
+fn clean_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .filter(|char| !char.is_control())
+        .take(MAX_NAME)
+        .collect();
+    match name.trim() {
+        "" => "???".to_string(),
+        name => name.to_string(),
+    }
+}
+
+fn parse_entry(line: &str) -> Result<(Mode, Entry), String> {
+    let fields: Vec<&str> = line.splitn(8, '\t').collect();
+    let [mode, score, lines, frames, date, seed, replay, name] = fields[..] else {
+        return Err(format!("Expected 8 fields, found '{}'", line));
+    };
+    let number = |field: &str| {
+        field
+            .parse::<u64>()
+            .map_err(|_| format!("Invalid number '{}'", field))
+    };
+    let mode = Mode::from_key(mode).ok_or(format!("Unknown mode '{}'", mode))?;
+    let entry = Entry {
+        name: name.to_string(),
+        score: number(score)? as u32,
+        lines: number(lines)? as u32,
+        frames: number(frames)?,
+        date: number(date)?,
+        seed: number(seed)?,
+        replay: (replay != "-").then(|| replay.to_string()),
+    };
+    Ok((mode, entry))
+}
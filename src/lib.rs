@@ -0,0 +1,39 @@
+//! Game engine of Rust Tetris.
+//!
+//! Everything needed to simulate games headlessly lives here, so that both
+//! the macroquad client and the match server can use it. The client-only
+//! modules are behind the `gui` feature.
+
+pub mod board;
+pub mod bot;
+#[cfg(feature = "gui")]
+pub mod config;
+#[cfg(feature = "gui")]
+pub mod controls;
+pub mod coop;
+pub mod env;
+pub mod finesse;
+pub mod game;
+pub mod garbage;
+pub mod history;
+pub mod input;
+pub mod leaderboard;
+pub mod master;
+pub mod movegen;
+pub mod net;
+pub mod protocol;
+pub mod puzzle;
+pub mod randomizer;
+#[cfg(feature = "gui")]
+pub mod renderer;
+pub mod replay;
+pub mod royale;
+pub mod ruleset;
+pub mod server;
+#[cfg(feature = "gui")]
+pub mod settings;
+pub mod spectate;
+pub mod stats;
+pub mod tetromino;
+pub mod tune;
+pub mod versus;
@@ -1,92 +1,1146 @@
-mod board;
-mod game;
-mod renderer;
-mod tetromino;
+use rust_tetris::board::BOARD_WIDTH;
+use rust_tetris::bot::{Bot, Weights};
+use rust_tetris::config::Config;
+use rust_tetris::controls::KeyMap;
+use rust_tetris::coop::{COOP_WIDTHS, Coop};
+use rust_tetris::finesse::FinesseMode;
+use rust_tetris::game::{Game, GameState};
+use rust_tetris::garbage::HoleStrategy;
+use rust_tetris::history::{History, Metric, Record};
+use rust_tetris::input::Input;
+use rust_tetris::leaderboard::{self, Entry, Leaderboard, MAX_NAME};
+use rust_tetris::master;
+use rust_tetris::net::{Match, NetSession};
+use rust_tetris::puzzle::{self, Puzzle, PuzzleStatus};
+use rust_tetris::renderer::{
+    Layout, Visuals, draw_board, draw_coop_ui, draw_finesse_fault, draw_graph, draw_piece,
+    draw_stats, draw_summary, draw_ui, draw_versus_ui, draw_view,
+};
+use rust_tetris::replay::Replay;
+use rust_tetris::royale::{Royale, badge_bonus};
+use rust_tetris::ruleset::{FRAME_RATE, Mode, Ruleset, StackVisibility};
+use rust_tetris::settings::{Outcome, Settings};
+use rust_tetris::spectate::{BoardView, Broadcaster, DEFAULT_DELAY, Spectator};
+use rust_tetris::versus::Versus;
 
-//use crate::fastrand;
+use macroquad::prelude::*;
+use std::fs;
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
 
-use game::{Game, GameState};
-use renderer::{draw_board, draw_ui};
-use tetromino::Direction;
+/// The highest start level offered by the level select.
+const MAX_START_LEVEL: u32 = 19;
 
-use macroquad::prelude::*;
+/// How many pieces per second the versus bot places by default.
+const DEFAULT_BOT_PPS: f64 = 2.0;
 
-const INITIAL_FALL_DELAY: f64 = 0.5;
+/// The directory puzzle files are loaded from.
+const PUZZLE_DIR: &str = "puzzles";
+
+/// How often the config file is checked for changes, in seconds.
+const CONFIG_POLL: f64 = 1.0;
 
 fn window_conf() -> Conf {
+    let window = load_config(Config::path().as_deref()).window;
     Conf {
         window_title: "Rust Tetris".to_owned(),
-        window_width: 600,
-        window_height: 800,
+        window_width: window.width,
+        window_height: window.height,
         ..Default::default()
     }
 }
 
-#[macroquad::main(window_conf)]
-async fn main() {
-    let mut game = Game::new();
-    let mut last_fall_time = get_time();
-    let mut last_level_up_time = get_time();
-    let mut fall_delay = 0.5; // Seconds between automatic falls
-    let level_up_delay = 30.0; // Seconds between automatic falls
-    let mut level = 1;
-    loop {
-        // Handle input
-        if is_key_pressed(KeyCode::Left) {
-            let _ = game.try_move(Direction::Left);
+/// Loads the player's config, falling back to the defaults when it cannot
+/// be read.
+fn load_config(path: Option<&Path>) -> Config {
+    let Some(path) = path else {
+        eprintln!("No user config directory, using the default settings");
+        return Config::default();
+    };
+    Config::load(path).unwrap_or_else(|err| {
+        eprintln!("Could not load {}: {}", path.display(), err);
+        Config::default()
+    })
+}
+
+/// The key maps of the single player and the two local versus players.
+fn key_maps(config: &Config) -> (KeyMap, [KeyMap; 2]) {
+    (
+        config.keys.key_map(),
+        [config.versus_left.key_map(), config.versus_right.key_map()],
+    )
+}
+
+enum Screen {
+    Menu,
+    Playing,
+    PuzzleSelect,
+    Puzzle,
+    Versus,
+    Coop,
+    Connecting,
+    Online,
+    Watching,
+    Leaderboard,
+    Replay,
+    Progress,
+    Settings,
+}
+
+/// Draws the board of a game, revealing hidden stacks once it has ended.
+fn draw_game(game: &Game, layout: Layout, visuals: &Visuals) {
+    let stack = match game.state {
+        GameState::Playing => game.ruleset.stack,
+        _ => StackVisibility::Visible,
+    };
+    draw_board(
+        layout,
+        &game.board,
+        game.current_piece.as_ref(),
+        game.ruleset.ghost,
+        stack,
+        game.frame,
+        visuals,
+    );
+    draw_finesse_fault(game, layout);
+}
+
+/// Steps the game at a fixed frame rate for the time elapsed since the last
+/// rendered frame, recording the inputs when given a replay.
+fn run_frames(
+    game: &mut Game,
+    keys: &KeyMap,
+    mut replay: Option<&mut Replay>,
+    accumulator: &mut f64,
+) {
+    let input = keys.read();
+    *accumulator += get_frame_time() as f64;
+    while *accumulator >= 1.0 / FRAME_RATE {
+        if let Some(replay) = replay.as_deref_mut() {
+            replay.record(input);
         }
-        if is_key_pressed(KeyCode::Right) {
-            let _ = game.try_move(Direction::Right);
+        game.update(input);
+        *accumulator -= 1.0 / FRAME_RATE;
+    }
+}
+
+/// Starts a single player game on a random seed, and its replay.
+fn start_solo(ruleset: Ruleset) -> (Game, Replay) {
+    let seed = fastrand::u64(..);
+    (Game::new(ruleset, seed), Replay::new(&ruleset, seed))
+}
+
+/// Adds a finished game to the leaderboard and saves it with its replay.
+///
+/// # Returns
+///
+/// The rank of the game, from `0`.
+fn record_score(
+    leaderboard: &mut Leaderboard,
+    game: &Game,
+    replay: &Replay,
+    name: &str,
+) -> Option<usize> {
+    let date = leaderboard::now();
+    let file = format!("{}-{}-{}.txt", replay.mode.key(), date, replay.seed);
+    let saved = replay
+        .save(&leaderboard.replay_dir().join(&file))
+        .inspect_err(|err| eprintln!("Could not save replay: {}", err))
+        .is_ok();
+    let entry = Entry {
+        name: name.to_string(),
+        score: game.score,
+        lines: game.lines,
+        frames: game.frame,
+        date,
+        seed: replay.seed,
+        replay: saved.then_some(file),
+    };
+    let rank = leaderboard.insert(replay.mode, entry);
+    if let Err(err) = leaderboard.save() {
+        eprintln!("Could not save scores: {}", err);
+    }
+    rank
+}
+
+/// Steps a versus match like [`run_frames`], reading both players' keys, or
+/// letting the bot play the second player when there is one.
+fn run_versus_frames(
+    versus: &mut Versus,
+    keys: &[KeyMap; 2],
+    mut bot: Option<&mut Bot>,
+    accumulator: &mut f64,
+) {
+    let mut inputs = [keys[0].read(), keys[1].read()];
+    *accumulator += get_frame_time() as f64;
+    while *accumulator >= 1.0 / FRAME_RATE {
+        if let Some(bot) = bot.as_deref_mut() {
+            inputs[1] = bot.update(&versus.players[1]);
         }
-        if is_key_pressed(KeyCode::Down) {
-            fall_delay *= 0.5;
+        versus.update(inputs);
+        *accumulator -= 1.0 / FRAME_RATE;
+    }
+}
+
+fn draw_versus(versus: &Versus, visuals: &Visuals) {
+    clear_background(BLACK);
+    for (player, game) in versus.players.iter().enumerate() {
+        let layout = Layout::versus(player);
+        draw_game(game, layout, visuals);
+        draw_versus_ui(game, layout, player, game.pending_garbage(), visuals);
+    }
+    if let Some(winner) = versus.winner {
+        draw_text(
+            format!("PLAYER {} WINS", winner + 1),
+            150.0,
+            680.0,
+            50.0,
+            GREEN,
+        );
+    }
+}
+
+fn draw_coop(coop: &Coop, visuals: &Visuals) {
+    clear_background(BLACK);
+    let layout = Layout::coop(coop.board.width());
+    let [first, second] = &coop.players;
+    let stack = match first.state {
+        GameState::Playing => first.ruleset.stack,
+        _ => StackVisibility::Visible,
+    };
+    draw_board(
+        layout,
+        &coop.board,
+        first.current_piece.as_ref(),
+        first.ruleset.ghost,
+        stack,
+        first.frame,
+        visuals,
+    );
+    if let Some(piece) = &second.current_piece {
+        draw_piece(layout, &coop.board, piece, second.ruleset.ghost, visuals);
+    }
+    for (player, game) in coop.players.iter().enumerate() {
+        draw_coop_ui(game, layout, player, visuals);
+    }
+    draw_text(
+        format!(
+            "Score {}   Lines {}   Level {}",
+            first.score, first.lines, first.level
+        ),
+        layout.x,
+        710.0,
+        25.0,
+        WHITE,
+    );
+    if coop.is_over() {
+        draw_text("GAME OVER", 180.0, 400.0, 50.0, RED);
+    }
+}
+
+/// Draws a royale match from the point of view of the local player, with
+/// their opponents drawn small beside their board.
+fn draw_royale(royale: &Royale, local: usize, visuals: &Visuals) {
+    clear_background(BLACK);
+    let opponents = (0..royale.players.len()).filter(|&player| player != local);
+    let shown = iter::once(local)
+        .chain(opponents)
+        .take(Layout::ROYALE_SLOTS);
+    for (slot, player) in shown.enumerate() {
+        let layout = Layout::royale(slot);
+        let game = &royale.players[player];
+        draw_game(game, layout, visuals);
+        if slot == 0 {
+            draw_versus_ui(game, layout, player, game.pending_garbage(), visuals);
+            continue;
+        }
+        // Red for the players we target, orange for those targeting us
+        let color = if royale.targets[local].contains(&player) {
+            RED
+        } else if royale.targets[player].contains(&local) {
+            ORANGE
+        } else {
+            GRAY
+        };
+        draw_text(
+            format!("P{}  {} badges", player + 1, royale.badges[player]),
+            layout.x,
+            layout.y - 6.0,
+            16.0,
+            color,
+        );
+        if !royale.is_alive(player) {
+            draw_text(
+                format!("#{}", royale.placement(player)),
+                layout.x + 20.0,
+                layout.y + 90.0,
+                40.0,
+                RED,
+            );
         }
-        if is_key_released(KeyCode::Down) {
-            fall_delay *= 2.0;
+    }
+
+    let attackers = royale
+        .targets
+        .iter()
+        .filter(|targets| targets.contains(&local))
+        .count();
+    draw_text(
+        format!(
+            "Target: {} (Tab)   Attackers: {}",
+            royale.targeting[local].name(),
+            attackers
+        ),
+        30.0,
+        570.0,
+        20.0,
+        WHITE,
+    );
+    draw_text(
+        format!(
+            "KOs {}   Badges {} (+{}% attack)",
+            royale.kos[local],
+            royale.badges[local],
+            badge_bonus(royale.badges[local])
+        ),
+        30.0,
+        595.0,
+        20.0,
+        WHITE,
+    );
+    if let Some(winner) = royale.winner {
+        draw_text(
+            format!("PLAYER {} WINS", winner + 1),
+            150.0,
+            680.0,
+            50.0,
+            GREEN,
+        );
+    } else if !royale.is_alive(local) {
+        draw_text(
+            format!("KNOCKED OUT  #{}", royale.placement(local)),
+            100.0,
+            680.0,
+            50.0,
+            RED,
+        );
+    }
+}
+
+/// Command line options.
+struct Args {
+    /// `--host ADDR` or `--join ADDR`: whether to host, and the address.
+    net: Option<(bool, String)>,
+    /// `--broadcast ADDR`: where spectators can attach to the games played.
+    broadcast: Option<String>,
+    /// `--watch ADDR`: a broadcast to spectate.
+    watch: Option<String>,
+    /// `--bot-pps N`: the pieces per second of the versus bot.
+    bot_pps: f64,
+    /// `--bot-weights FILE`: the weights of the versus bot, as written by
+    /// the `tune` tool.
+    bot_weights: Weights,
+}
+
+fn parse_args() -> Args {
+    let mut args = Args {
+        net: None,
+        broadcast: None,
+        watch: None,
+        bot_pps: DEFAULT_BOT_PPS,
+        bot_weights: Weights::default(),
+    };
+    let mut words = std::env::args().skip(1);
+    while let Some(flag) = words.next() {
+        let Some(value) = words.next() else {
+            eprintln!("Missing value for {}", flag);
+            break;
+        };
+        match flag.as_str() {
+            "--host" => args.net = Some((true, value)),
+            "--join" => args.net = Some((false, value)),
+            "--broadcast" => args.broadcast = Some(value),
+            "--watch" => args.watch = Some(value),
+            "--bot-pps" => match value.parse() {
+                Ok(pps) if pps > 0.0 => args.bot_pps = pps,
+                _ => eprintln!("Invalid pieces per second {}", value),
+            },
+            "--bot-weights" => {
+                match fs::read_to_string(&value)
+                    .map_err(|err| err.to_string())
+                    .and_then(|text| Weights::parse(&text))
+                {
+                    Ok(weights) => args.bot_weights = weights,
+                    Err(err) => eprintln!("Could not load bot weights {}: {}", value, err),
+                }
+            }
+            _ => eprintln!("Ignoring unknown option {}", flag),
+        }
+    }
+    args
+}
+
+fn draw_spectator(spectator: &Spectator, visuals: &Visuals) {
+    clear_background(BLACK);
+    match spectator.views.as_slice() {
+        [] => {
+            draw_text("Waiting for a game...", 100.0, 300.0, 40.0, WHITE);
         }
-        if is_key_pressed(KeyCode::Up) || is_key_pressed(KeyCode::X) {
-            let _ = game.try_rotate_clock();
+        [view] if view.width > BOARD_WIDTH => draw_view(view, Layout::coop(view.width), visuals),
+        [view] => draw_view(view, Layout::SOLO, visuals),
+        [first, second] => {
+            draw_view(first, Layout::versus(0), visuals);
+            draw_view(second, Layout::versus(1), visuals);
         }
-        if is_key_pressed(KeyCode::Z) {
-            let _ = game.try_rotate_counter();
+        views => {
+            for (slot, view) in views.iter().enumerate().take(Layout::ROYALE_SLOTS) {
+                draw_view(view, Layout::royale(slot), visuals);
+            }
         }
-        if is_key_pressed(KeyCode::Space) {
-            game.hard_lock();
+    }
+    draw_text("SPECTATING", 50.0, 30.0, 25.0, YELLOW);
+    if let Some(err) = &spectator.error {
+        draw_text(err, 60.0, 740.0, 20.0, RED);
+    }
+    draw_text("Escape: menu", 60.0, 770.0, 20.0, GRAY);
+}
+
+fn draw_menu(
+    selected: usize,
+    start_level: u32,
+    garbage: HoleStrategy,
+    width: usize,
+    finesse: FinesseMode,
+) {
+    clear_background(BLACK);
+    draw_text("RUST TETRIS", 150.0, 150.0, 50.0, WHITE);
+    for (i, mode) in Mode::MENU.iter().enumerate() {
+        let color = if i == selected { YELLOW } else { GRAY };
+        draw_text(mode.name(), 180.0, 210.0 + i as f32 * 36.0, 30.0, color);
+    }
+    if Mode::MENU[selected].has_level_select() {
+        draw_text(
+            format!("Start level: < {} >", start_level),
+            180.0,
+            600.0,
+            30.0,
+            WHITE,
+        );
+    }
+    if Mode::MENU[selected] == Mode::Versus {
+        draw_text(
+            format!("Garbage: < {} >", garbage.describe()),
+            180.0,
+            600.0,
+            30.0,
+            WHITE,
+        );
+    }
+    if Mode::MENU[selected] == Mode::Coop {
+        draw_text(
+            format!("Board width: < {} >", width),
+            180.0,
+            600.0,
+            30.0,
+            WHITE,
+        );
+    }
+    draw_text(
+        format!(
+            "F: finesse {}   L: high scores   P: progress   S: settings",
+            finesse.name()
+        ),
+        60.0,
+        630.0,
+        20.0,
+        GRAY,
+    );
+    draw_text(
+        "Up/Down: mode   Left/Right: option   Enter: start",
+        60.0,
+        660.0,
+        20.0,
+        GRAY,
+    );
+}
+
+fn draw_puzzle_select(puzzles: &[Puzzle], selected: usize) {
+    clear_background(BLACK);
+    draw_text("PUZZLES", 200.0, 120.0, 50.0, WHITE);
+    if puzzles.is_empty() {
+        draw_text(
+            format!("No puzzles found in '{}'", PUZZLE_DIR),
+            100.0,
+            250.0,
+            25.0,
+            GRAY,
+        );
+    }
+    for (i, puzzle) in puzzles.iter().enumerate() {
+        let color = if i == selected { YELLOW } else { GRAY };
+        let y = 200.0 + i as f32 * 40.0;
+        draw_text(&puzzle.name, 80.0, y, 30.0, color);
+        draw_text(puzzle.goal.describe(), 380.0, y, 20.0, color);
+    }
+    draw_text(
+        "Up/Down: puzzle   Enter: start   Escape: back",
+        60.0,
+        760.0,
+        20.0,
+        GRAY,
+    );
+}
+
+fn draw_leaderboard(leaderboard: &Leaderboard, mode: Mode, selected: usize, error: Option<&str>) {
+    clear_background(BLACK);
+    draw_text("HIGH SCORES", 150.0, 100.0, 50.0, WHITE);
+    draw_text(format!("< {} >", mode.name()), 150.0, 150.0, 30.0, YELLOW);
+    let columns = [40.0, 80.0, 240.0, 340.0, 400.0, 490.0];
+    for (x, title) in columns
+        .iter()
+        .zip(["#", "Name", "Score", "Lines", "Time", "Date"])
+    {
+        draw_text(title, *x, 200.0, 20.0, GRAY);
+    }
+    let entries = leaderboard.entries(mode);
+    if entries.is_empty() {
+        draw_text("No scores yet", 80.0, 240.0, 25.0, GRAY);
+    }
+    for (i, entry) in entries.iter().enumerate() {
+        let color = if i == selected { YELLOW } else { WHITE };
+        let y = 240.0 + i as f32 * 32.0;
+        let cells = [
+            (i + 1).to_string(),
+            entry.name.clone(),
+            entry.score.to_string(),
+            entry.lines.to_string(),
+            master::format_time(entry.frames),
+            leaderboard::format_date(entry.date),
+        ];
+        for (x, cell) in columns.iter().zip(cells) {
+            draw_text(&cell, *x, y, 20.0, color);
         }
+    }
+    if let Some(err) = error {
+        draw_text(err, 40.0, 700.0, 20.0, RED);
+    }
+    draw_text(
+        "Left/Right: mode   Up/Down: score   Enter: replay   Escape: back",
+        20.0,
+        760.0,
+        20.0,
+        GRAY,
+    );
+}
+
+fn draw_progress(history: &History, mode: Mode) {
+    clear_background(BLACK);
+    draw_text("PROGRESS", 150.0, 70.0, 50.0, WHITE);
+    draw_text(format!("< {} >", mode.name()), 150.0, 110.0, 30.0, YELLOW);
+    let records = history.records(mode);
+    let played: u64 = records.iter().map(|record| record.frames).sum();
+    draw_text(
+        format!(
+            "Games {}   Time played {}",
+            records.len(),
+            master::format_time(played)
+        ),
+        150.0,
+        140.0,
+        20.0,
+        GRAY,
+    );
+    for (i, metric) in Metric::ALL.into_iter().enumerate() {
+        let format = |value: f64| match metric {
+            Metric::Sprint => master::format_time((value * FRAME_RATE) as u64),
+            Metric::Pps => format!("{:.2}", value),
+            Metric::Score => format!("{:.0}", value),
+        };
+        let title = if metric.lower_is_better() {
+            format!("{} (lower is better)", metric.name())
+        } else {
+            metric.name().to_string()
+        };
+        let rect = Rect::new(40.0, 200.0 + i as f32 * 190.0, 440.0, 130.0);
+        draw_graph(&title, &history.daily(mode, metric), format, rect);
+    }
+    draw_text("Left/Right: mode   Escape: back", 40.0, 780.0, 20.0, GRAY);
+}
 
-        let current_time = get_time();
-        if current_time - last_level_up_time > level_up_delay {
-            level += 1;
-            fall_delay *= 0.8;
-            last_level_up_time = current_time;
+#[macroquad::main(window_conf)]
+async fn main() {
+    let mut screen = Screen::Menu;
+    let mut selected = 0;
+    let mut start_level: u32 = 0;
+    let mut garbage_choice = 0;
+    let mut width_choice: usize = 0;
+    let mut finesse_choice = 0;
+    let mut ruleset = Ruleset::standard();
+    let (mut game, mut replay) = start_solo(ruleset);
+    let scores_path = Leaderboard::path().unwrap_or_else(|| PathBuf::from("scores.txt"));
+    let mut leaderboard = Leaderboard::load(&scores_path).unwrap_or_else(|err| {
+        eprintln!("Could not load scores: {}", err);
+        Leaderboard::new(scores_path.clone())
+    });
+    let history_path = History::path().unwrap_or_else(|| PathBuf::from("history.txt"));
+    let mut history = History::load(&history_path).unwrap_or_else(|err| {
+        eprintln!("Could not load history: {}", err);
+        History::new(history_path.clone())
+    });
+    let mut progress_mode = 0;
+    // Whether the end of the current game was added to the history and
+    // checked for a high score
+    let mut game_recorded = false;
+    let mut name_entry: Option<String> = None;
+    let mut player_name = String::new();
+    let mut rank: Option<usize> = None;
+    let mut board_mode = 0;
+    let mut board_selected: usize = 0;
+    let mut board_error: Option<String> = None;
+    let mut replay_inputs: Vec<Input> = Vec::new();
+    let mut accumulator = 0.0;
+    let mut puzzles: Vec<Puzzle> = Vec::new();
+    let mut selected_puzzle = 0;
+    let mut versus = Versus::new(Ruleset::versus(), 0);
+    let mut coop = Coop::new(Ruleset::coop(), 0, COOP_WIDTHS[0]);
+    let mut sparring: Option<Bot> = None;
+    let config_path = Config::path();
+    let mut config = load_config(config_path.as_deref());
+    let (mut solo_keys, mut versus_keys) = key_maps(&config);
+    let mut config_modified = config_path.as_deref().and_then(Config::modified);
+    let mut config_checked = get_time();
+    // The outcome of the last reload or save, shown on the menu
+    let mut config_notice: Option<String> = None;
+    let mut settings = Settings::new();
+    let mut connecting: Option<Receiver<Result<NetSession, String>>> = None;
+    // Set to stop a host or client still waiting for the other side
+    let cancel_connect = Arc::new(AtomicBool::new(false));
+    let mut session: Option<NetSession> = None;
+    let mut net_error: Option<String> = None;
+    let args = parse_args();
+    let mut broadcaster = args.broadcast.and_then(|addr| {
+        Broadcaster::start(&addr, DEFAULT_DELAY)
+            .inspect_err(|err| eprintln!("Could not broadcast on {}: {}", addr, err))
+            .ok()
+    });
+    let mut spectator: Option<Spectator> = None;
+    if let Some(addr) = args.watch {
+        match Spectator::connect(&addr) {
+            Ok(watching) => {
+                spectator = Some(watching);
+                screen = Screen::Watching;
+            }
+            Err(err) => eprintln!("Could not watch {}: {}", addr, err),
         }
-        if current_time - last_fall_time > fall_delay {
-            game.tick_down();
-            last_fall_time = current_time;
+    }
+    if let Some((host, addr)) = args.net {
+        let (sender, receiver) = mpsc::channel();
+        let cancel = Arc::clone(&cancel_connect);
+        thread::spawn(move || {
+            let started = if host {
+                NetSession::host(&addr, HoleStrategy::Clean, &cancel)
+            } else {
+                NetSession::join(&addr, &cancel)
+            };
+            let _ = sender.send(started);
+        });
+        connecting = Some(receiver);
+        screen = Screen::Connecting;
+    }
+    loop {
+        if let Some(path) = config_path.as_deref()
+            && get_time() - config_checked >= CONFIG_POLL
+        {
+            config_checked = get_time();
+            let modified = Config::modified(path);
+            if modified != config_modified {
+                config_modified = modified;
+                config_notice = Some(match Config::load(path) {
+                    Ok(reloaded) => {
+                        config = reloaded;
+                        (solo_keys, versus_keys) = key_maps(&config);
+                        "Settings reloaded".to_string()
+                    }
+                    Err(err) => format!("Settings not reloaded: {}", err),
+                });
+            }
         }
+        match screen {
+            Screen::Menu => {
+                if is_key_pressed(KeyCode::Up) {
+                    selected = (selected + Mode::MENU.len() - 1) % Mode::MENU.len();
+                }
+                if is_key_pressed(KeyCode::Down) {
+                    selected = (selected + 1) % Mode::MENU.len();
+                }
+                // Left and Right change the option of the selected mode only
+                let mode = Mode::MENU[selected];
+                let choices = HoleStrategy::CHOICES.len();
+                if is_key_pressed(KeyCode::Left) {
+                    if mode.has_level_select() {
+                        start_level = start_level.saturating_sub(1);
+                    }
+                    if mode == Mode::Versus {
+                        garbage_choice = (garbage_choice + choices - 1) % choices;
+                    }
+                    if mode == Mode::Coop {
+                        width_choice = width_choice.saturating_sub(1);
+                    }
+                }
+                if is_key_pressed(KeyCode::Right) {
+                    if mode.has_level_select() {
+                        start_level = (start_level + 1).min(MAX_START_LEVEL);
+                    }
+                    if mode == Mode::Versus {
+                        garbage_choice = (garbage_choice + 1) % choices;
+                    }
+                    if mode == Mode::Coop {
+                        width_choice = (width_choice + 1).min(COOP_WIDTHS.len() - 1);
+                    }
+                }
+                if is_key_pressed(KeyCode::F) {
+                    finesse_choice = (finesse_choice + 1) % FinesseMode::ALL.len();
+                }
+                if is_key_pressed(KeyCode::P) {
+                    screen = Screen::Progress;
+                }
+                if is_key_pressed(KeyCode::S) {
+                    settings = Settings::new();
+                    screen = Screen::Settings;
+                }
+                if is_key_pressed(KeyCode::L) {
+                    board_selected = 0;
+                    board_error = None;
+                    screen = Screen::Leaderboard;
+                }
+                if is_key_pressed(KeyCode::Enter) {
+                    if Mode::MENU[selected] == Mode::Puzzle {
+                        puzzles = puzzle::load_dir(Path::new(PUZZLE_DIR));
+                        selected_puzzle = 0;
+                        screen = Screen::PuzzleSelect;
+                    } else if Mode::MENU[selected] == Mode::Versus {
+                        ruleset = config.apply(Ruleset {
+                            garbage: HoleStrategy::CHOICES[garbage_choice],
+                            ..Ruleset::versus()
+                        });
+                        versus = Versus::new(ruleset, fastrand::u64(..));
+                        accumulator = 0.0;
+                        screen = Screen::Versus;
+                    } else if Mode::MENU[selected] == Mode::Coop {
+                        ruleset = config.apply(Ruleset::coop());
+                        coop = Coop::new(ruleset, fastrand::u64(..), COOP_WIDTHS[width_choice]);
+                        accumulator = 0.0;
+                        screen = Screen::Coop;
+                    } else {
+                        ruleset = config.apply(Ruleset {
+                            finesse: FinesseMode::ALL[finesse_choice],
+                            ..Ruleset::for_mode(Mode::MENU[selected], start_level)
+                        });
+                        (game, replay) = start_solo(ruleset);
+                        game_recorded = false;
+                        accumulator = 0.0;
+                        screen = Screen::Playing;
+                    }
+                }
+                draw_menu(
+                    selected,
+                    start_level,
+                    HoleStrategy::CHOICES[garbage_choice],
+                    COOP_WIDTHS[width_choice],
+                    FinesseMode::ALL[finesse_choice],
+                );
+                if let Some(notice) = &config_notice {
+                    draw_text(notice, 60.0, 700.0, 20.0, YELLOW);
+                }
+            }
+            Screen::PuzzleSelect => {
+                if is_key_pressed(KeyCode::Up) && selected_puzzle > 0 {
+                    selected_puzzle -= 1;
+                }
+                if is_key_pressed(KeyCode::Down) && selected_puzzle + 1 < puzzles.len() {
+                    selected_puzzle += 1;
+                }
+                if is_key_pressed(KeyCode::Enter) && selected_puzzle < puzzles.len() {
+                    game = puzzles[selected_puzzle].start(config.apply(Ruleset::puzzle()));
+                    ruleset = game.ruleset;
+                    accumulator = 0.0;
+                    screen = Screen::Puzzle;
+                }
+                if is_key_pressed(KeyCode::Escape) {
+                    screen = Screen::Menu;
+                }
+                draw_puzzle_select(&puzzles, selected_puzzle);
+            }
+            Screen::Puzzle => {
+                let puzzle = &puzzles[selected_puzzle];
+                let status = puzzle.status(&game);
+                if status == PuzzleStatus::InProgress {
+                    run_frames(&mut game, &solo_keys, None, &mut accumulator);
+                }
 
-        draw_board(&game.board, game.current_piece.as_ref());
-        draw_ui(game.score, level);
+                clear_background(BLACK);
+                draw_game(&game, Layout::SOLO, &config.visuals);
+                draw_ui(&game, &config.visuals);
+                draw_text(&puzzle.name, 50.0, 30.0, 25.0, WHITE);
+                draw_text(puzzle.goal.describe(), 50.0, 700.0, 25.0, YELLOW);
+                draw_text("R: retry   Escape: puzzles", 50.0, 740.0, 20.0, GRAY);
+                match status {
+                    PuzzleStatus::Passed => {
+                        draw_text("PASSED", 200.0, 300.0, 50.0, GREEN);
+                    }
+                    PuzzleStatus::Failed => {
+                        draw_text("FAILED", 200.0, 300.0, 50.0, RED);
+                    }
+                    PuzzleStatus::InProgress => {}
+                }
 
-        // Check game over
-        if matches!(game.state, GameState::GameOver) {
-            draw_text("GAME OVER", 200.0, 300.0, 50.0, RED);
-            draw_text(
-                &format!("Final Score: {}", game.score),
-                200.0,
-                360.0,
-                30.0,
-                WHITE,
-            );
-            draw_text("Press R to restart", 200.0, 400.0, 20.0, GRAY);
+                if is_key_pressed(KeyCode::R) {
+                    game = puzzle.start(config.apply(Ruleset::puzzle()));
+                    accumulator = 0.0;
+                }
+                if is_key_pressed(KeyCode::Escape) {
+                    screen = Screen::PuzzleSelect;
+                }
+            }
+            Screen::Playing => {
+                run_frames(&mut game, &solo_keys, Some(&mut replay), &mut accumulator);
+                if ruleset.undo {
+                    if is_key_pressed(KeyCode::U) {
+                        let _ = game.undo();
+                    }
+                    if is_key_pressed(KeyCode::Y) {
+                        let _ = game.redo();
+                    }
+                }
+                if ruleset.top_out_clears && is_key_pressed(KeyCode::Escape) {
+                    screen = Screen::Menu;
+                }
+
+                clear_background(BLACK);
+                draw_game(&game, Layout::SOLO, &config.visuals);
+                draw_ui(&game, &config.visuals);
+                draw_stats(&game, Layout::SOLO.x, 690.0);
+
+                // Check game over
+                if !matches!(game.state, GameState::Playing) {
+                    draw_rectangle(50.0, 170.0, 500.0, 520.0, Color::new(0.0, 0.0, 0.0, 0.85));
+                    if matches!(game.state, GameState::Cleared) {
+                        draw_text("COMPLETE", 200.0, 230.0, 50.0, GREEN);
+                    } else {
+                        draw_text("GAME OVER", 200.0, 230.0, 50.0, RED);
+                    }
+                    draw_text(
+                        format!("Final Score: {}", game.score),
+                        200.0,
+                        275.0,
+                        30.0,
+                        WHITE,
+                    );
+                    draw_summary(&game, 80.0, 320.0);
 
-            if is_key_pressed(KeyCode::R) {
-                game = Game::new();
-                last_fall_time = get_time();
-                level = 1;
-                last_level_up_time = get_time();
-                fall_delay = INITIAL_FALL_DELAY;
+                    if !game_recorded {
+                        game_recorded = true;
+                        rank = None;
+                        let record = Record::new(&game, leaderboard::now());
+                        if let Err(err) = history.append(record) {
+                            eprintln!("Could not save history: {}", err);
+                        }
+                        if leaderboard::MODES.contains(&ruleset.mode)
+                            && leaderboard.qualifies(ruleset.mode, game.score, game.frame)
+                        {
+                            // Drop the keys typed while playing
+                            while get_char_pressed().is_some() {}
+                            name_entry = Some(player_name.clone());
+                        }
+                    }
+                    if let Some(name) = name_entry.as_mut() {
+                        while let Some(char) = get_char_pressed() {
+                            if !char.is_control() && name.chars().count() < MAX_NAME {
+                                name.push(char);
+                            }
+                        }
+                        if is_key_pressed(KeyCode::Backspace) {
+                            name.pop();
+                        }
+                        draw_text("NEW HIGH SCORE", 200.0, 600.0, 25.0, YELLOW);
+                        draw_text(
+                            format!("Name: {}_   Enter: save", name),
+                            130.0,
+                            630.0,
+                            25.0,
+                            WHITE,
+                        );
+                        if is_key_pressed(KeyCode::Enter) {
+                            player_name = name.clone();
+                            rank = record_score(&mut leaderboard, &game, &replay, name);
+                            name_entry = None;
+                        }
+                    } else {
+                        if let Some(rank) = rank {
+                            draw_text(
+                                format!("High score #{}!", rank + 1),
+                                200.0,
+                                610.0,
+                                25.0,
+                                YELLOW,
+                            );
+                        }
+                        draw_text("Press R to restart", 200.0, 640.0, 20.0, GRAY);
+                        draw_text("Press Escape for menu", 200.0, 665.0, 20.0, GRAY);
+
+                        if is_key_pressed(KeyCode::R) {
+                            (game, replay) = start_solo(ruleset);
+                            game_recorded = false;
+                            accumulator = 0.0;
+                        }
+                        if is_key_pressed(KeyCode::Escape) {
+                            screen = Screen::Menu;
+                        }
+                    }
+                }
+            }
+            Screen::Versus => {
+                run_versus_frames(
+                    &mut versus,
+                    &versus_keys,
+                    sparring.as_mut(),
+                    &mut accumulator,
+                );
+                draw_versus(&versus, &config.visuals);
+                let second = if sparring.is_some() {
+                    "P2: bot   B: human P2".to_string()
+                } else {
+                    format!("P2: {}   B: bot P2", versus_keys[1].summary())
+                };
+                let first = format!("P1: {}", versus_keys[0].summary());
+                draw_text(&first, 60.0, 620.0, 20.0, GRAY);
+                draw_text(&second, 60.0, 645.0, 20.0, GRAY);
+                if is_key_pressed(KeyCode::B) {
+                    sparring = match sparring {
+                        Some(_) => None,
+                        None => Some(Bot::new(args.bot_weights, args.bot_pps)),
+                    };
+                }
+                if versus.winner.is_some() {
+                    draw_text("R: rematch   Escape: menu", 170.0, 720.0, 20.0, GRAY);
+                }
+                if versus.winner.is_some() && is_key_pressed(KeyCode::R) {
+                    versus = Versus::new(ruleset, fastrand::u64(..));
+                    sparring = sparring.map(|bot| Bot::new(bot.weights, bot.pps));
+                    accumulator = 0.0;
+                }
+                if is_key_pressed(KeyCode::Escape) {
+                    screen = Screen::Menu;
+                }
+            }
+            Screen::Coop => {
+                let inputs = [versus_keys[0].read(), versus_keys[1].read()];
+                accumulator += get_frame_time() as f64;
+                while accumulator >= 1.0 / FRAME_RATE {
+                    coop.update(inputs);
+                    accumulator -= 1.0 / FRAME_RATE;
+                }
+                draw_coop(&coop, &config.visuals);
+                for (i, keys) in versus_keys.iter().enumerate() {
+                    let controls = format!("P{}: {}", i + 1, keys.summary());
+                    draw_text(&controls, 60.0, 725.0 + i as f32 * 22.0, 20.0, GRAY);
+                }
+                if coop.is_over() {
+                    draw_text("R: restart   Escape: menu", 170.0, 790.0, 20.0, GRAY);
+                    if is_key_pressed(KeyCode::R) {
+                        coop = Coop::new(ruleset, fastrand::u64(..), coop.board.width());
+                        accumulator = 0.0;
+                    }
+                }
+                if is_key_pressed(KeyCode::Escape) {
+                    screen = Screen::Menu;
+                }
+            }
+            Screen::Connecting => {
+                clear_background(BLACK);
+                match connecting.as_ref().map(|receiver| receiver.try_recv()) {
+                    Some(Ok(Ok(started))) => {
+                        session = Some(started);
+                        accumulator = 0.0;
+                        screen = Screen::Online;
+                    }
+                    Some(Ok(Err(err))) => net_error = Some(err),
+                    _ => {}
+                }
+                match &net_error {
+                    Some(err) => {
+                        draw_text("CONNECTION FAILED", 100.0, 300.0, 40.0, RED);
+                        draw_text(err, 100.0, 340.0, 20.0, WHITE);
+                    }
+                    None => {
+                        draw_text("Waiting for opponent...", 100.0, 300.0, 40.0, WHITE);
+                    }
+                }
+                draw_text("Escape: menu", 100.0, 400.0, 20.0, GRAY);
+                if is_key_pressed(KeyCode::Escape) {
+                    cancel_connect.store(true, Ordering::Relaxed);
+                    connecting = None;
+                    screen = Screen::Menu;
+                }
+            }
+            Screen::Online => {
+                let Some(online) = session.as_mut() else {
+                    screen = Screen::Menu;
+                    continue;
+                };
+                let input = solo_keys.read();
+                accumulator += get_frame_time() as f64;
+                while accumulator >= 1.0 / FRAME_RATE {
+                    online.update(input);
+                    accumulator -= 1.0 / FRAME_RATE;
+                }
+                match &online.game {
+                    Match::Versus(versus) => draw_versus(versus, &config.visuals),
+                    Match::Royale(royale) => draw_royale(royale, online.player, &config.visuals),
+                }
+                draw_text(
+                    format!("You are player {}", online.player + 1),
+                    60.0,
+                    620.0,
+                    20.0,
+                    GRAY,
+                );
+                if let Some(err) = &online.error {
+                    draw_text(err, 60.0, 650.0, 20.0, RED);
+                }
+                if online.is_over() {
+                    draw_text("Escape: menu", 170.0, 720.0, 20.0, GRAY);
+                }
+                if is_key_pressed(KeyCode::Escape) {
+                    session = None;
+                    screen = Screen::Menu;
+                }
+            }
+            Screen::Leaderboard => {
+                let modes = leaderboard::MODES.len();
+                if is_key_pressed(KeyCode::Left) {
+                    board_mode = (board_mode + modes - 1) % modes;
+                    board_selected = 0;
+                }
+                if is_key_pressed(KeyCode::Right) {
+                    board_mode = (board_mode + 1) % modes;
+                    board_selected = 0;
+                }
+                let mode = leaderboard::MODES[board_mode];
+                let entries = leaderboard.entries(mode);
+                if is_key_pressed(KeyCode::Up) {
+                    board_selected = board_selected.saturating_sub(1);
+                }
+                if is_key_pressed(KeyCode::Down) && board_selected + 1 < entries.len() {
+                    board_selected += 1;
+                }
+                if is_key_pressed(KeyCode::Enter)
+                    && let Some(entry) = entries.get(board_selected)
+                {
+                    let loaded = entry
+                        .replay
+                        .as_ref()
+                        .ok_or("This score has no replay".to_string())
+                        .and_then(|file| Replay::load(&leaderboard.replay_dir().join(file)));
+                    match loaded {
+                        Ok(watched) => {
+                            game = watched.start();
+                            ruleset = game.ruleset;
+                            replay_inputs = watched.inputs().collect();
+                            replay_inputs.reverse();
+                            accumulator = 0.0;
+                            screen = Screen::Replay;
+                        }
+                        Err(err) => board_error = Some(err),
+                    }
+                }
+                if is_key_pressed(KeyCode::Escape) {
+                    screen = Screen::Menu;
+                }
+                draw_leaderboard(&leaderboard, mode, board_selected, board_error.as_deref());
             }
+            Screen::Replay => {
+                accumulator += get_frame_time() as f64;
+                while accumulator >= 1.0 / FRAME_RATE {
+                    if let Some(input) = replay_inputs.pop() {
+                        game.update(input);
+                    }
+                    accumulator -= 1.0 / FRAME_RATE;
+                }
+                clear_background(BLACK);
+                draw_game(&game, Layout::SOLO, &config.visuals);
+                draw_ui(&game, &config.visuals);
+                draw_stats(&game, Layout::SOLO.x, 690.0);
+                draw_text("REPLAY", 50.0, 30.0, 25.0, YELLOW);
+                if replay_inputs.is_empty() {
+                    draw_text("Replay over", 200.0, 300.0, 40.0, WHITE);
+                }
+                draw_text("Escape: back", 50.0, 780.0, 20.0, GRAY);
+                if is_key_pressed(KeyCode::Escape) {
+                    screen = Screen::Leaderboard;
+                }
+            }
+            Screen::Progress => {
+                let modes = leaderboard::MODES.len();
+                if is_key_pressed(KeyCode::Left) {
+                    progress_mode = (progress_mode + modes - 1) % modes;
+                }
+                if is_key_pressed(KeyCode::Right) {
+                    progress_mode = (progress_mode + 1) % modes;
+                }
+                if is_key_pressed(KeyCode::Escape) {
+                    screen = Screen::Menu;
+                }
+                draw_progress(&history, leaderboard::MODES[progress_mode]);
+            }
+            Screen::Settings => {
+                match settings.update(&mut config) {
+                    Outcome::None => {}
+                    Outcome::Changed => (solo_keys, versus_keys) = key_maps(&config),
+                    Outcome::Closed => {
+                        if let Some(path) = config_path.as_deref() {
+                            config_notice = Some(match config.save(path) {
+                                Ok(()) => format!("Settings saved to {}", path.display()),
+                                Err(err) => format!("Settings not saved: {}", err),
+                            });
+                            config_modified = Config::modified(path);
+                        }
+                        screen = Screen::Menu;
+                    }
+                }
+                settings.draw(&config);
+            }
+            Screen::Watching => {
+                let Some(watching) = spectator.as_mut() else {
+                    screen = Screen::Menu;
+                    continue;
+                };
+                watching.update();
+                draw_spectator(watching, &config.visuals);
+                if is_key_pressed(KeyCode::Escape) {
+                    spectator = None;
+                    screen = Screen::Menu;
+                }
+            }
+        }
+
+        if let Some(broadcaster) = broadcaster.as_mut() {
+            let views: Vec<BoardView> = match screen {
+                Screen::Playing | Screen::Puzzle => vec![BoardView::of(&game)],
+                Screen::Versus => versus.players.iter().map(BoardView::of).collect(),
+                Screen::Coop => vec![BoardView::of_coop(&coop)],
+                Screen::Online => session
+                    .iter()
+                    .flat_map(|online| online.game.players())
+                    .map(BoardView::of)
+                    .collect(),
+                _ => Vec::new(),
+            };
+            broadcaster.publish(views);
         }
 
         next_frame().await;
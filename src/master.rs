@@ -0,0 +1,146 @@
+//! Master mode tables and grading.
+//!
+//! Master is modelled on the arcade Master mode: the level rises with every
+//! piece and every cleared line, gravity ramps up to 20G, the delays shrink
+//! section by section (one section every 100 levels) and the final result
+//! is a grade rather than just a score.
+
+use crate::game::Game;
+use crate::ruleset::{FRAME_RATE, Gravity, Timings};
+
+/// The level at which a Master game is complete.
+pub const MAX_LEVEL: u32 = 999;
+
+/// Gravity in 1/256 rows per frame, as `(from level, gravity)` pairs.
+const GRAVITY: [(u32, u32); 30] = [
+    (0, 4),
+    (30, 6),
+    (35, 8),
+    (40, 10),
+    (50, 12),
+    (60, 16),
+    (70, 32),
+    (80, 48),
+    (90, 64),
+    (100, 80),
+    (120, 96),
+    (140, 112),
+    (160, 128),
+    (170, 144),
+    (200, 4),
+    (220, 32),
+    (230, 64),
+    (233, 96),
+    (236, 128),
+    (239, 160),
+    (243, 192),
+    (247, 224),
+    (251, 256),
+    (300, 512),
+    (330, 768),
+    (360, 1024),
+    (400, 1280),
+    (420, 1024),
+    (450, 768),
+    (500, 5120),
+];
+
+/// Score thresholds for every grade, from 9 up to S9.
+const GRADE_SCORES: [(u32, &str); 18] = [
+    (0, "9"),
+    (400, "8"),
+    (800, "7"),
+    (1400, "6"),
+    (2000, "5"),
+    (3500, "4"),
+    (5500, "3"),
+    (8000, "2"),
+    (12000, "1"),
+    (16000, "S1"),
+    (22000, "S2"),
+    (30000, "S3"),
+    (40000, "S4"),
+    (52000, "S5"),
+    (66000, "S6"),
+    (82000, "S7"),
+    (100000, "S8"),
+    (120000, "S9"),
+];
+
+/// Grand Master checkpoints as `(level, minimum score, maximum frames)`.
+const GM_CHECKPOINTS: [(u32, u32, u64); 3] = [
+    (300, 12000, 255 * FRAME_RATE as u64),
+    (500, 40000, 450 * FRAME_RATE as u64),
+    (MAX_LEVEL, 126000, 810 * FRAME_RATE as u64),
+];
+
+/// Returns the gravity at a Master level.
+pub fn gravity(level: u32) -> Gravity {
+    let subrows = GRAVITY
+        .iter()
+        .rev()
+        .find(|&&(from, _)| level >= from)
+        .map_or(4, |&(_, subrows)| subrows);
+    Gravity::Subrows(subrows)
+}
+
+/// Returns the delays for the section a Master level is in.
+pub fn timings(level: u32) -> Timings {
+    // (ARE, line ARE, DAS, lock delay, line clear)
+    let (are, line_are, das, lock, line_clear) = match level / 100 {
+        0..=4 => (25, 25, 14, 30, 40),
+        5 => (25, 25, 8, 30, 25),
+        6 => (25, 16, 8, 30, 16),
+        7 => (16, 12, 8, 30, 12),
+        8 => (12, 6, 8, 30, 6),
+        _ => (12, 6, 6, 17, 6),
+    };
+    Timings {
+        das,
+        arr: 1,
+        are,
+        line_are,
+        line_clear,
+        lock_delay: Some(lock),
+        lock_resets: 0,
+    }
+}
+
+/// Returns whether a level is a section stop, where only line clears can
+/// advance the level.
+pub fn is_level_stop(level: u32) -> bool {
+    level % 100 == 99 || level == MAX_LEVEL - 1
+}
+
+/// Returns the grade a Master game has earned so far.
+///
+/// The grade follows the score, except for Grand Master ("GM"), which also
+/// requires every checkpoint to have been passed fast enough and with a high
+/// enough score.
+pub fn grade(game: &Game) -> &'static str {
+    let passed_checkpoints = GM_CHECKPOINTS.iter().all(|&(level, score, frames)| {
+        game.section_splits
+            .iter()
+            .find(|split| split.level >= level)
+            .is_some_and(|split| split.score >= score && split.frame <= frames)
+    });
+    if passed_checkpoints {
+        return "GM";
+    }
+    GRADE_SCORES
+        .iter()
+        .rev()
+        .find(|&&(score, _)| game.score >= score)
+        .map_or("9", |&(_, grade)| grade)
+}
+
+/// Formats a frame count as `mm:ss:cc`.
+pub fn format_time(frames: u64) -> String {
+    let centis = frames * 100 / FRAME_RATE as u64;
+    format!(
+        "{:02}:{:02}:{:02}",
+        centis / 6000,
+        centis / 100 % 60,
+        centis % 100
+    )
+}
@@ -0,0 +1,251 @@
+//! Movegen module enumerating every placement a piece can reach.
+//!
+//! [`placements`] searches breadth first through every position and
+//! rotation a piece can be moved to from where it spawns, using the kicks of
+//! its rotation system, so soft drop tucks under overhangs and spins into
+//! slots are found along with plain drops. Every distinct resting position
+//! is returned once, with the sequence of [`Move`]s reaching it that has the
+//! fewest soft drops, then the fewest moves.
+
+use std::collections::{HashSet, VecDeque};
+
+use crate::board::{BOARD_HEIGHT, Board};
+use crate::game::{TSpin, default_spawn_column, detect_t_spin, try_kicks};
+use crate::ruleset::Ruleset;
+use crate::tetromino::{Direction, Shape, Tetromino};
+
+/// How far outside the board a piece's position can be, in cells.
+const MARGIN: i32 = 4;
+
+/// One step of the path to a placement.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Move {
+    Left,
+    Right,
+    RotateCw,
+    RotateCcw,
+    /// Move down a single row.
+    Down,
+    /// Soft drop all the way down, without locking.
+    Drop,
+}
+
+/// A position a piece can lock in, and how to get there.
+#[derive(Debug, Clone)]
+pub struct Placement {
+    /// The piece at its resting position.
+    pub piece: Tetromino,
+    /// The moves from the starting position, the lock itself not included.
+    pub path: Vec<Move>,
+    /// The T-spin the placement counts as, when the last move is a rotation.
+    pub tspin: TSpin,
+}
+
+/// Returns every placement reachable by a newly spawned piece.
+///
+/// # Arguments
+///
+/// * `board` - The board the piece is played on
+/// * `shape` - The shape of the piece
+/// * `ruleset` - The rules played by, which decide the rotation system,
+///   spawn position and scale of the piece
+///
+/// # Returns
+///
+/// The placements, empty if the piece cannot spawn.
+///
+/// # Examples
+///
+/// ```
+/// # use rust_tetris::board::Board;
+/// # use rust_tetris::movegen::placements;
+/// # use rust_tetris::ruleset::Ruleset;
+/// # use rust_tetris::tetromino::Shape;
+/// // An O piece fits in 9 columns of an empty board
+/// let found = placements(&Board::new(), Shape::O, &Ruleset::versus());
+/// assert_eq!(found.len(), 9);
+/// ```
+pub fn placements(board: &Board, shape: Shape, ruleset: &Ruleset) -> Vec<Placement> {
+    placements_from(board, &spawn(board, shape, ruleset))
+}
+
+/// Returns a piece where it spawns by default.
+///
+/// # Arguments
+///
+/// * `board` - The board the piece spawns on
+/// * `shape` - The shape of the piece
+/// * `ruleset` - The rules played by
+pub fn spawn(board: &Board, shape: Shape, ruleset: &Ruleset) -> Tetromino {
+    let column = default_spawn_column(board, ruleset);
+    let mut piece = Tetromino::with_rotation_system((column, 0), shape, ruleset.rotation);
+    piece.scale = ruleset.piece_scale();
+    piece
+}
+
+/// Returns every placement reachable from a piece's current position.
+///
+/// # Arguments
+///
+/// * `board` - The board the piece is played on
+/// * `start` - The piece, e.g. the current piece of a game
+///
+/// # Returns
+///
+/// The placements, empty if the piece does not fit where it is.
+pub fn placements_from(board: &Board, start: &Tetromino) -> Vec<Placement> {
+    search(board, start, true)
+}
+
+/// Returns the placements a newly spawned piece reaches by moving and
+/// rotating, then dropping straight down. Much faster than [`placements`],
+/// for when tucks and spins can be done without.
+///
+/// # Arguments
+///
+/// * `board` - The board the piece is played on
+/// * `shape` - The shape of the piece
+/// * `ruleset` - The rules played by
+///
+/// # Examples
+///
+/// ```
+/// # use rust_tetris::board::Board;
+/// # use rust_tetris::movegen::hard_drops;
+/// # use rust_tetris::ruleset::Ruleset;
+/// # use rust_tetris::tetromino::Shape;
+/// let found = hard_drops(&Board::new(), Shape::I, &Ruleset::versus());
+/// assert_eq!(found.len(), 17);
+/// ```
+pub fn hard_drops(board: &Board, shape: Shape, ruleset: &Ruleset) -> Vec<Placement> {
+    search(board, &spawn(board, shape, ruleset), false)
+}
+
+/// Searches for placements from a piece, moving on after dropping it only
+/// when `tucks` is set.
+fn search(board: &Board, start: &Tetromino, tucks: bool) -> Vec<Placement> {
+    if !board.can_place(start) {
+        return Vec::new();
+    }
+    // A state is a position, a rotation and whether the piece got there by
+    // rotating, which decides T-spins. Soft drops are slow, so the search
+    // goes one drop at a time: every state reachable with the taps of one
+    // level is found before the drops leading to the next.
+    // A piece that fits has its position at most 3 cells outside the board
+    let columns = board.width() as i32 + 2 * MARGIN;
+    let key = |piece: &Tetromino, spun: bool| {
+        let (x, y) = (piece.pos.0 + MARGIN, piece.pos.1 + MARGIN);
+        (((y * columns + x) * 4 + piece.rotation as i32) * 2 + i32::from(spun)) as usize
+    };
+    let mut seen = vec![false; (columns * (BOARD_HEIGHT as i32 + 2 * MARGIN) * 8) as usize];
+    let mut locks = HashSet::new();
+    let mut found = Vec::new();
+    // The state and move every visited state was reached from
+    let mut parents: Vec<Option<(usize, Move)>> = Vec::new();
+    let mut frontier = vec![(start.clone(), false, None)];
+
+    let mut dropped = false;
+    while !frontier.is_empty() {
+        let mut queue = VecDeque::from(frontier);
+        frontier = Vec::new();
+        while let Some((piece, spun, parent)) = queue.pop_front() {
+            if std::mem::replace(&mut seen[key(&piece, spun)], true) {
+                continue;
+            }
+            let index = parents.len();
+            parents.push(parent);
+            let mut below = piece.clone();
+            below.move_piece(Direction::Down);
+            if !board.can_place(&below) {
+                let tspin = if spun {
+                    detect_t_spin(board, &piece)
+                } else {
+                    TSpin::None
+                };
+                // Rotations covering the same cells lock the same way
+                if locks.insert((piece.row_masks(), tspin)) {
+                    found.push(Placement {
+                        piece: piece.clone(),
+                        path: path_to(&parents, index),
+                        tspin,
+                    });
+                }
+            }
+            if dropped && !tucks {
+                continue;
+            }
+
+            for step in [
+                Move::Left,
+                Move::Right,
+                Move::RotateCw,
+                Move::RotateCcw,
+                Move::Down,
+                Move::Drop,
+            ] {
+                // Dropping after moving down lands where dropping first would
+                let falling = matches!(parent, Some((_, Move::Down | Move::Drop)));
+                if (falling && step == Move::Drop) || (!tucks && step == Move::Down) {
+                    continue;
+                }
+                let Some(next) = apply(board, &piece, step) else {
+                    continue;
+                };
+                let spun = matches!(step, Move::RotateCw | Move::RotateCcw);
+                if seen[key(&next, spun)] {
+                    continue;
+                }
+                if matches!(step, Move::Down | Move::Drop) {
+                    frontier.push((next, spun, Some((index, step))));
+                } else {
+                    queue.push_back((next, spun, Some((index, step))));
+                }
+            }
+        }
+        dropped = true;
+    }
+    found
+}
+
+/// Returns the moves leading to a visited state, following its parents.
+fn path_to(parents: &[Option<(usize, Move)>], mut index: usize) -> Vec<Move> {
+    let mut path = Vec::new();
+    while let Some((parent, step)) = parents[index] {
+        path.push(step);
+        index = parent;
+    }
+    path.reverse();
+    path
+}
+
+/// Returns the piece after a move, or `None` if the move is blocked.
+///
+/// # Arguments
+///
+/// * `board` - The board the piece is played on
+/// * `piece` - The piece before the move
+/// * `step` - The move to make
+pub fn apply(board: &Board, piece: &Tetromino, step: Move) -> Option<Tetromino> {
+    let mut next = piece.clone();
+    match step {
+        Move::Left => next.move_piece(Direction::Left),
+        Move::Right => next.move_piece(Direction::Right),
+        Move::Down => next.move_piece(Direction::Down),
+        Move::RotateCw => {
+            next.rotate_cw();
+            return try_kicks(board, &[], &mut next, piece.rotation).then_some(next);
+        }
+        Move::RotateCcw => {
+            next.rotate_ccw();
+            return try_kicks(board, &[], &mut next, piece.rotation).then_some(next);
+        }
+        Move::Drop => {
+            while board.can_place(&next) {
+                next.move_piece(Direction::Down);
+            }
+            next.move_piece(Direction::Up);
+            return (next.pos != piece.pos).then_some(next);
+        }
+    }
+    board.can_place(&next).then_some(next)
+}
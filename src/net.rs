@@ -0,0 +1,489 @@
+//! Net module running multiplayer matches between processes over TCP.
+//!
+//! Every side runs the same deterministic [`Match`] simulation in lockstep:
+//! a frame is only simulated once the inputs of every player for it are
+//! known. Local inputs are scheduled [`INPUT_DELAY`] frames ahead, which
+//! gives them time to reach the other sides and hides the network latency.
+//!
+//! Two players can play directly, one of them hosting. Matches with more
+//! players go through a match server, see [`server`](crate::server), which
+//! relays every player's inputs to everyone else.
+//!
+//! The host agrees on the ruleset, player count and seed in the handshake,
+//! and every side reports the garbage it sends and the winner it sees, so
+//! that a desync is detected instead of silently diverging.
+
+use std::collections::VecDeque;
+use std::io::{BufReader, ErrorKind};
+use std::net::{Shutdown, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use crate::game::Game;
+use crate::garbage::HoleStrategy;
+use crate::input::Input;
+use crate::protocol::{Message, VERSION};
+use crate::royale::Royale;
+use crate::ruleset::{Mode, Ruleset};
+use crate::versus::Versus;
+
+/// How many frames local inputs are delayed by.
+pub const INPUT_DELAY: usize = 4;
+
+/// How long the other side may take to answer during the handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often a side waiting for the other checks whether it was cancelled.
+const CANCEL_POLL: Duration = Duration::from_millis(50);
+
+/// How many frames the local side may run ahead of the remote inputs
+/// before it stops accepting new inputs.
+const MAX_AHEAD: usize = 60;
+
+/// A match simulation that can be played over the network.
+pub enum Match {
+    Versus(Box<Versus>),
+    Royale(Box<Royale>),
+}
+
+impl Match {
+    /// Creates the match a ruleset calls for: a [`Royale`] for
+    /// [`Mode::Royale`] and a [`Versus`] otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `ruleset` - The rules every player plays by
+    /// * `seed` - The seed of the match
+    /// * `players` - The number of players, only used by royale matches
+    pub fn new(ruleset: Ruleset, seed: u64, players: usize) -> Self {
+        match ruleset.mode {
+            Mode::Royale => Match::Royale(Box::new(Royale::new(ruleset, seed, players))),
+            _ => Match::Versus(Box::new(Versus::new(ruleset, seed))),
+        }
+    }
+
+    /// Returns the game of every player.
+    pub fn players(&self) -> &[Game] {
+        match self {
+            Match::Versus(versus) => &versus.players,
+            Match::Royale(royale) => &royale.players,
+        }
+    }
+
+    /// Returns the total lines each player has sent.
+    pub fn sent(&self) -> &[u32] {
+        match self {
+            Match::Versus(versus) => &versus.sent,
+            Match::Royale(royale) => &royale.sent,
+        }
+    }
+
+    /// Returns the index of the winning player once the match is over.
+    pub fn winner(&self) -> Option<usize> {
+        match self {
+            Match::Versus(versus) => versus.winner,
+            Match::Royale(royale) => royale.winner,
+        }
+    }
+
+    /// Returns the last player beaten by the winner, once the match is over.
+    pub fn runner_up(&self) -> Option<usize> {
+        let winner = self.winner()?;
+        match self {
+            Match::Versus(_) => Some(1 - winner),
+            Match::Royale(royale) => royale
+                .knocked_out
+                .iter()
+                .rev()
+                .copied()
+                .find(|&player| player != winner),
+        }
+    }
+
+    /// Advances the match by one frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `inputs` - The buttons held by each player during this frame
+    pub fn update(&mut self, inputs: &[Input]) {
+        match self {
+            Match::Versus(versus) => {
+                let input = |player: usize| inputs.get(player).copied().unwrap_or_default();
+                versus.update([input(0), input(1)]);
+            }
+            Match::Royale(royale) => royale.update(inputs),
+        }
+    }
+}
+
+/// A match played against remote players.
+pub struct NetSession {
+    pub game: Match,
+    /// The index of the local player, `0` for the host.
+    pub player: usize,
+    /// Set once the connection fails or the sides disagree.
+    pub error: Option<String>,
+    stream: TcpStream,
+    messages: Receiver<Result<Message, String>>,
+    // The inputs of every player, the local one included, waiting to be
+    // simulated
+    inputs: Vec<VecDeque<Input>>,
+    sent_frames: u64,
+    received_frames: Vec<u64>,
+    simulated_frames: u64,
+    // Garbage reports of each remote player, as (frame, lines), that have
+    // not been matched against the local simulation yet, and vice versa
+    reported: Vec<VecDeque<(u64, u32)>>,
+    simulated: Vec<VecDeque<(u64, u32)>>,
+    remote_winner: Option<usize>,
+    /// Why the connection closed, once it has.
+    closed: Option<String>,
+}
+
+impl NetSession {
+    /// Waits for a player to connect and starts a versus match as the host.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The address to listen on, e.g. `0.0.0.0:7878`
+    /// * `garbage` - Where the holes of garbage go
+    /// * `cancel` - Set to stop waiting, which frees the address
+    ///
+    /// # Returns
+    ///
+    /// The running session, or a description of what went wrong.
+    pub fn host(addr: &str, garbage: HoleStrategy, cancel: &AtomicBool) -> Result<Self, String> {
+        let listener = TcpListener::bind(addr).map_err(|err| err.to_string())?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|err| err.to_string())?;
+        let mut stream = loop {
+            match listener.accept() {
+                Ok((stream, _)) => break stream,
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                    if cancel.load(Ordering::Relaxed) {
+                        return Err("Cancelled".to_string());
+                    }
+                    thread::sleep(CANCEL_POLL);
+                }
+                Err(err) => return Err(err.to_string()),
+            }
+        };
+        stream
+            .set_nonblocking(false)
+            .and_then(|()| stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT)))
+            .map_err(|err| err.to_string())?;
+        handshake(&mut stream)?;
+        let seed = fastrand::u64(..);
+        let mode = Mode::Versus;
+        Message::Setup {
+            player: 1,
+            players: 2,
+            seed,
+            mode,
+            garbage,
+        }
+        .write_to(&mut stream)?;
+        Self::start(stream, 0, Match::new(ruleset(mode, garbage), seed, 2))
+    }
+
+    /// Connects to a host or a match server and starts a match.
+    ///
+    /// A match server relays the other players' inputs and reports as if it
+    /// were them, see `src/bin/server.rs`.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The address of the host, e.g. `127.0.0.1:7878`
+    /// * `cancel` - Set to stop waiting for a match server to fill the room
+    ///
+    /// # Returns
+    ///
+    /// The running session, or a description of what went wrong.
+    pub fn join(addr: &str, cancel: &AtomicBool) -> Result<Self, String> {
+        let target = addr
+            .to_socket_addrs()
+            .map_err(|err| err.to_string())?
+            .next()
+            .ok_or(format!("Unknown address {}", addr))?;
+        let mut stream = TcpStream::connect_timeout(&target, HANDSHAKE_TIMEOUT)
+            .map_err(|err| err.to_string())?;
+        stream
+            .set_read_timeout(Some(HANDSHAKE_TIMEOUT))
+            .map_err(|err| err.to_string())?;
+        handshake(&mut stream)?;
+        // A match server only sends the setup once the room is full
+        wait_readable(&stream, cancel)?;
+        match Message::read_from(&mut stream)? {
+            Message::Setup {
+                player,
+                players,
+                seed,
+                mode,
+                garbage,
+            } => {
+                let game = Match::new(ruleset(mode, garbage), seed, players as usize);
+                if player as usize >= game.players().len() {
+                    return Err(format!("Player {} is not in the match", player + 1));
+                }
+                Self::start(stream, player as usize, game)
+            }
+            other => Err(format!("Expected setup, got {:?}", other)),
+        }
+    }
+
+    fn start(stream: TcpStream, player: usize, game: Match) -> Result<Self, String> {
+        stream
+            .set_nodelay(true)
+            .and_then(|()| stream.set_read_timeout(None))
+            .map_err(|err| err.to_string())?;
+        let mut reader = BufReader::new(stream.try_clone().map_err(|err| err.to_string())?);
+        let (sender, messages) = mpsc::channel();
+        thread::spawn(move || {
+            loop {
+                let message = Message::read_from(&mut reader);
+                let failed = message.is_err();
+                if sender.send(message).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        let players = game.players().len();
+        let mut session = Self {
+            game,
+            player,
+            error: None,
+            stream,
+            messages,
+            inputs: vec![VecDeque::new(); players],
+            sent_frames: 0,
+            received_frames: vec![0; players],
+            simulated_frames: 0,
+            reported: vec![VecDeque::new(); players],
+            simulated: vec![VecDeque::new(); players],
+            remote_winner: None,
+            closed: None,
+        };
+        // Every side starts with the same delay of empty inputs
+        for _ in 0..INPUT_DELAY {
+            session.send_input(Input::NONE);
+        }
+        Ok(session)
+    }
+
+    /// Returns whether the session can no longer advance.
+    pub fn is_over(&self) -> bool {
+        self.error.is_some() || self.game.winner().is_some()
+    }
+
+    /// Advances the session by one local frame.
+    ///
+    /// The local input is sent to the remote players, and every frame for
+    /// which all inputs are known is simulated.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The buttons held by the local player during this frame
+    pub fn update(&mut self, input: Input) {
+        if self.error.is_some() {
+            return;
+        }
+        if self.inputs[self.player].len() < MAX_AHEAD && self.game.winner().is_none() {
+            self.send_input(input);
+        }
+        self.receive();
+        while self.inputs.iter().all(|inputs| !inputs.is_empty()) && self.game.winner().is_none() {
+            self.simulate();
+        }
+        self.check_garbage();
+        if let (Some(local), Some(remote)) = (self.game.winner(), self.remote_winner)
+            && local != remote
+        {
+            self.fail(format!("Desync: winner {} vs {}", local + 1, remote + 1));
+        }
+        // Every input the remote side sent has been simulated by now, so the
+        // match cannot go on. The remote side may leave once it is over, and
+        // a match server also ends matches on its own, e.g. on a forfeit.
+        if let Some(reason) = self.closed.take()
+            && self.game.winner().is_none()
+        {
+            match self.remote_winner {
+                Some(winner) => self.fail(format!("Match ended: player {} wins", winner + 1)),
+                None => self.fail(reason),
+            }
+        }
+    }
+
+    fn send_input(&mut self, input: Input) {
+        let message = Message::Input {
+            player: self.player as u8,
+            frame: self.sent_frames,
+            input,
+        };
+        self.inputs[self.player].push_back(input);
+        self.sent_frames += 1;
+        self.send(message);
+    }
+
+    fn send(&mut self, message: Message) {
+        if let Err(err) = message.write_to(&mut self.stream) {
+            self.closed.get_or_insert(err);
+        }
+    }
+
+    fn receive(&mut self) {
+        loop {
+            let message = match self.messages.try_recv() {
+                Ok(Ok(message)) => message,
+                Err(TryRecvError::Empty) => return,
+                Ok(Err(err)) => {
+                    self.closed.get_or_insert(err);
+                    return;
+                }
+                Err(TryRecvError::Disconnected) => {
+                    self.closed.get_or_insert("Disconnected".to_string());
+                    return;
+                }
+            };
+            match message {
+                Message::Input {
+                    player,
+                    frame,
+                    input,
+                } if self.is_remote(player) && frame == self.received_frames[player as usize] => {
+                    self.inputs[player as usize].push_back(input);
+                    self.received_frames[player as usize] += 1;
+                }
+                Message::GarbageSent {
+                    player,
+                    frame,
+                    lines,
+                } if self.is_remote(player) => {
+                    self.reported[player as usize].push_back((frame, lines));
+                }
+                Message::GameOver { winner } => self.remote_winner = Some(winner as usize),
+                other => return self.fail(format!("Unexpected message {:?}", other)),
+            }
+        }
+    }
+
+    fn is_remote(&self, player: u8) -> bool {
+        let player = player as usize;
+        player != self.player && player < self.inputs.len()
+    }
+
+    /// Simulates the next frame with every player's input.
+    fn simulate(&mut self) {
+        let inputs: Vec<Input> = self
+            .inputs
+            .iter_mut()
+            .map(|inputs| inputs.pop_front().unwrap_or_default())
+            .collect();
+        let sent = self.game.sent().to_vec();
+        self.game.update(&inputs);
+        let frame = self.simulated_frames;
+        self.simulated_frames += 1;
+
+        for (player, sent) in sent.into_iter().enumerate() {
+            let lines = self.game.sent()[player] - sent;
+            if lines == 0 {
+                continue;
+            }
+            if player == self.player {
+                self.send(Message::GarbageSent {
+                    player: player as u8,
+                    frame,
+                    lines,
+                });
+            } else {
+                self.simulated[player].push_back((frame, lines));
+            }
+        }
+        if let Some(winner) = self.game.winner() {
+            self.send(Message::GameOver {
+                winner: winner as u8,
+            });
+        }
+    }
+
+    /// Matches the garbage each remote player reported against the garbage
+    /// the local simulation saw them send.
+    fn check_garbage(&mut self) {
+        for player in 0..self.reported.len() {
+            let (reported, simulated) = (&mut self.reported[player], &mut self.simulated[player]);
+            while let (Some(&report), Some(&expected)) = (reported.front(), simulated.front()) {
+                if report != expected {
+                    return self.fail(format!(
+                        "Desync: player {} sent {} lines on frame {}, expected {} on frame {}",
+                        player + 1,
+                        report.1,
+                        report.0,
+                        expected.1,
+                        expected.0
+                    ));
+                }
+                reported.pop_front();
+                simulated.pop_front();
+            }
+        }
+    }
+
+    fn fail(&mut self, error: String) {
+        if self.error.is_none() {
+            self.error = Some(error);
+        }
+    }
+}
+
+impl Drop for NetSession {
+    fn drop(&mut self) {
+        // Unblocks the reader thread
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+/// Exchanges `Hello` messages and checks both sides speak the same version.
+pub fn handshake(stream: &mut TcpStream) -> Result<(), String> {
+    Message::Hello { version: VERSION }.write_to(stream)?;
+    match Message::read_from(stream)? {
+        Message::Hello { version } if version == VERSION => Ok(()),
+        Message::Hello { version } => Err(format!(
+            "Version mismatch: local {}, remote {}",
+            VERSION, version
+        )),
+        other => Err(format!("Expected hello, got {:?}", other)),
+    }
+}
+
+/// Blocks until data arrives on a stream or the wait is cancelled, leaving
+/// the stream with the handshake timeout.
+fn wait_readable(stream: &TcpStream, cancel: &AtomicBool) -> Result<(), String> {
+    stream
+        .set_read_timeout(Some(CANCEL_POLL))
+        .map_err(|err| err.to_string())?;
+    loop {
+        match stream.peek(&mut [0]) {
+            Ok(0) => return Err("Disconnected".to_string()),
+            Ok(_) => break,
+            Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                if cancel.load(Ordering::Relaxed) {
+                    return Err("Cancelled".to_string());
+                }
+            }
+            Err(err) => return Err(err.to_string()),
+        }
+    }
+    stream
+        .set_read_timeout(Some(HANDSHAKE_TIMEOUT))
+        .map_err(|err| err.to_string())
+}
+
+/// Returns the ruleset agreed on by a `Setup` message.
+pub fn ruleset(mode: Mode, garbage: HoleStrategy) -> Ruleset {
+    Ruleset {
+        garbage,
+        ..Ruleset::for_mode(mode, 0)
+    }
+}
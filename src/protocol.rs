@@ -0,0 +1,226 @@
+//! Protocol module defining the messages exchanged by networked games.
+//!
+//! Every message is a tag byte followed by its fields in big-endian order:
+//!
+//! | Tag | Message       | Fields                                  |
+//! |-----|---------------|-----------------------------------------|
+//! | 0   | `Hello`       | version `u16`                           |
+//! | 1   | `Setup`       | player `u8`, players `u8`, seed `u64`,  |
+//! |     |               | mode `u8`, garbage `u8 u8`              |
+//! | 2   | `Input`       | player `u8`, frame `u64`, buttons `u8`  |
+//! | 3   | `GarbageSent` | player `u8`, frame `u64`, lines `u32`   |
+//! | 4   | `GameOver`    | winner `u8`                             |
+//!
+//! The garbage strategy is encoded as a kind (`0` clean, `1` random,
+//! `2` messy) followed by the messiness percentage.
+
+use std::io::{ErrorKind, Read, Write};
+
+use crate::garbage::HoleStrategy;
+use crate::input::Input;
+use crate::ruleset::Mode;
+
+/// The protocol version, bumped on every incompatible change.
+pub const VERSION: u16 = 3;
+
+/// A message sent between two networked games.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Message {
+    /// The first message sent by both sides.
+    Hello { version: u16 },
+    /// Sent by the host or server to agree on the rules, the number of
+    /// players and the seed of the match, and to tell the receiver which
+    /// player it is.
+    Setup {
+        player: u8,
+        players: u8,
+        seed: u64,
+        mode: Mode,
+        garbage: HoleStrategy,
+    },
+    /// The buttons a player held during a frame.
+    Input {
+        player: u8,
+        frame: u64,
+        input: Input,
+    },
+    /// The garbage a player sent on a frame, used to detect desyncs.
+    GarbageSent { player: u8, frame: u64, lines: u32 },
+    /// The winner of the match as seen by the sender.
+    GameOver { winner: u8 },
+}
+
+impl Message {
+    /// Writes the message to a stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The stream to write to
+    pub fn write_to(&self, writer: &mut impl Write) -> Result<(), String> {
+        let mut bytes = Vec::new();
+        match *self {
+            Message::Hello { version } => {
+                bytes.push(0);
+                bytes.extend(version.to_be_bytes());
+            }
+            Message::Setup {
+                player,
+                players,
+                seed,
+                mode,
+                garbage,
+            } => {
+                bytes.push(1);
+                bytes.push(player);
+                bytes.push(players);
+                bytes.extend(seed.to_be_bytes());
+                let mode = Mode::ALL.iter().position(|&other| other == mode);
+                bytes.push(mode.unwrap_or(0) as u8);
+                bytes.extend(match garbage {
+                    HoleStrategy::Clean => [0, 0],
+                    HoleStrategy::Random => [1, 0],
+                    HoleStrategy::Messy(percent) => [2, percent.min(100) as u8],
+                });
+            }
+            Message::Input {
+                player,
+                frame,
+                input,
+            } => {
+                bytes.push(2);
+                bytes.push(player);
+                bytes.extend(frame.to_be_bytes());
+                bytes.push(input.0);
+            }
+            Message::GarbageSent {
+                player,
+                frame,
+                lines,
+            } => {
+                bytes.push(3);
+                bytes.push(player);
+                bytes.extend(frame.to_be_bytes());
+                bytes.extend(lines.to_be_bytes());
+            }
+            Message::GameOver { winner } => {
+                bytes.push(4);
+                bytes.push(winner);
+            }
+        }
+        writer.write_all(&bytes).map_err(|err| err.to_string())
+    }
+
+    /// Reads the next message from a stream, blocking until it has arrived.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - The stream to read from
+    ///
+    /// # Returns
+    ///
+    /// The message, or a description of the read error or malformed data.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_tetris::garbage::HoleStrategy;
+    /// # use rust_tetris::input::Input;
+    /// # use rust_tetris::protocol::{Message, VERSION};
+    /// # use rust_tetris::ruleset::Mode;
+    /// let messages = [
+    ///     Message::Hello { version: VERSION },
+    ///     Message::Setup {
+    ///         player: 1,
+    ///         players: 5,
+    ///         seed: u64::MAX - 7,
+    ///         mode: Mode::Royale,
+    ///         garbage: HoleStrategy::Messy(30),
+    ///     },
+    ///     Message::Setup {
+    ///         player: 0,
+    ///         players: 2,
+    ///         seed: 0,
+    ///         mode: Mode::Versus,
+    ///         garbage: HoleStrategy::Random,
+    ///     },
+    ///     Message::Input {
+    ///         player: 1,
+    ///         frame: 1 << 40,
+    ///         input: Input::LEFT | Input::HARD_DROP,
+    ///     },
+    ///     Message::GarbageSent { player: 2, frame: 600, lines: 4 },
+    ///     Message::GameOver { winner: 3 },
+    /// ];
+    /// let mut bytes = Vec::new();
+    /// for message in &messages {
+    ///     message.write_to(&mut bytes).unwrap();
+    /// }
+    /// let mut reader = bytes.as_slice();
+    /// for message in messages {
+    ///     assert_eq!(Message::read_from(&mut reader), Ok(message));
+    /// }
+    /// assert_eq!(Message::read_from(&mut reader), Err("Disconnected".to_string()));
+    /// assert!(Message::read_from(&mut [9u8].as_slice()).is_err());
+    /// ```
+    pub fn read_from(reader: &mut impl Read) -> Result<Self, String> {
+        let tag = read_array::<1>(reader)?[0];
+        match tag {
+            0 => Ok(Message::Hello {
+                version: u16::from_be_bytes(read_array(reader)?),
+            }),
+            1 => {
+                let [player, players] = read_array(reader)?;
+                let seed = u64::from_be_bytes(read_array(reader)?);
+                let [mode, kind, percent] = read_array(reader)?;
+                let mode = *Mode::ALL
+                    .get(mode as usize)
+                    .ok_or(format!("Unknown mode {}", mode))?;
+                let garbage = match kind {
+                    0 => HoleStrategy::Clean,
+                    1 => HoleStrategy::Random,
+                    2 => HoleStrategy::Messy(percent as u32),
+                    _ => return Err(format!("Unknown garbage strategy {}", kind)),
+                };
+                Ok(Message::Setup {
+                    player,
+                    players,
+                    seed,
+                    mode,
+                    garbage,
+                })
+            }
+            2 => {
+                let [player] = read_array(reader)?;
+                let frame = u64::from_be_bytes(read_array(reader)?);
+                let [input] = read_array(reader)?;
+                Ok(Message::Input {
+                    player,
+                    frame,
+                    input: Input(input),
+                })
+            }
+            3 => Ok(Message::GarbageSent {
+                player: read_array::<1>(reader)?[0],
+                frame: u64::from_be_bytes(read_array(reader)?),
+                lines: u32::from_be_bytes(read_array(reader)?),
+            }),
+            4 => Ok(Message::GameOver {
+                winner: read_array::<1>(reader)?[0],
+            }),
+            _ => Err(format!("Unknown message tag {}", tag)),
+        }
+    }
+}
+
+/// Reads exactly `N` bytes from a stream.
+pub(crate) fn read_array<const N: usize>(reader: &mut impl Read) -> Result<[u8; N], String> {
+    let mut bytes = [0; N];
+    reader
+        .read_exact(&mut bytes)
+        .map_err(|err| match err.kind() {
+            ErrorKind::UnexpectedEof => "Disconnected".to_string(),
+            ErrorKind::WouldBlock | ErrorKind::TimedOut => "Timed out".to_string(),
+            _ => err.to_string(),
+        })?;
+    Ok(bytes)
+}
@@ -0,0 +1,253 @@
+//! Puzzle module loading fixed training situations from files.
+//!
+//! A puzzle is a plain text file describing a starting board, the exact
+//! pieces the player gets and the goal to reach with them:
+//!
+//! ```text
+//! # Lines starting with '#' are comments
+//! name: T-Spin Double
+//! goal: tspin-double
+//! pieces: T
+//! board:
+//! ##...#####
+//! #....#####
+//! ##.#######
+//! ```
+//!
+//! Supported goals are `lines N`, `perfect-clear`, `tspin-double` and
+//! `survive N`. Board rows use `.` for empty cells and any other character
+//! for filled cells, and are aligned to the bottom of the board.
+
+use std::fs;
+use std::path::Path;
+
+use crate::board::{BOARD_HEIGHT, BOARD_WIDTH, Board};
+use crate::game::{Game, GameState, TSpin};
+use crate::randomizer::Randomizer;
+use crate::ruleset::Ruleset;
+use crate::tetromino::Shape;
+
+/// What the player must achieve to pass a puzzle.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Goal {
+    /// Clear at least this many lines.
+    Lines(u32),
+    /// Clear the whole board.
+    PerfectClear,
+    /// Clear two lines with a T-spin.
+    TSpinDouble,
+    /// Place this many pieces without topping out.
+    Survive(u32),
+}
+
+impl Goal {
+    /// A short description of the goal for the UI.
+    pub fn describe(&self) -> String {
+        match self {
+            Goal::Lines(lines) => format!("Clear {} lines", lines),
+            Goal::PerfectClear => "Perfect clear".to_string(),
+            Goal::TSpinDouble => "T-spin double".to_string(),
+            Goal::Survive(pieces) => format!("Survive {} pieces", pieces),
+        }
+    }
+}
+
+/// The progress of a puzzle attempt.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PuzzleStatus {
+    InProgress,
+    Passed,
+    Failed,
+}
+
+/// A puzzle loaded from a file.
+#[derive(Clone)]
+pub struct Puzzle {
+    pub name: String,
+    pub goal: Goal,
+    pub pieces: Vec<Shape>,
+    pub board: Board,
+}
+
+impl Puzzle {
+    /// Parses a puzzle from the text of a puzzle file.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The contents of the puzzle file
+    ///
+    /// # Returns
+    ///
+    /// The parsed puzzle, or a message describing the first problem found.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut name = None;
+        let mut goal = None;
+        let mut pieces = None;
+        let mut rows = Vec::new();
+        let mut in_board = false;
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') && !in_board {
+                continue;
+            }
+            if in_board {
+                rows.push(parse_row(line)?);
+                continue;
+            }
+            let (key, value) = line
+                .split_once(':')
+                .ok_or(format!("Expected 'key: value', found '{}'", line))?;
+            let value = value.trim();
+            match key.trim() {
+                "name" => name = Some(value.to_string()),
+                "goal" => goal = Some(parse_goal(value)?),
+                "pieces" => {
+                    pieces = Some(
+                        value
+                            .split_whitespace()
+                            .map(parse_shape)
+                            .collect::<Result<Vec<_>, _>>()?,
+                    )
+                }
+                "board" => in_board = true,
+                other => return Err(format!("Unknown key '{}'", other)),
+            }
+        }
+
+        if rows.len() > BOARD_HEIGHT {
+            return Err(format!("Board has more than {} rows", BOARD_HEIGHT));
+        }
+        let mut board = Board::new();
+        let top = (BOARD_HEIGHT - rows.len()) as i32;
+        for (y, row) in rows.iter().enumerate() {
+            for (x, &filled) in row.iter().enumerate() {
+                if filled {
+                    board.fill_pos(x as i32, top + y as i32);
+                }
+            }
+        }
+
+        Ok(Self {
+            name: name.ok_or("Missing 'name'")?,
+            goal: goal.ok_or("Missing 'goal'")?,
+            pieces: pieces.ok_or("Missing 'pieces'")?,
+            board,
+        })
+    }
+
+    /// Starts an attempt at the puzzle.
+    ///
+    /// # Arguments
+    ///
+    /// * `ruleset` - The rules to play with, [`Ruleset::puzzle`] with the
+    ///   player's handling applied
+    pub fn start(&self, ruleset: Ruleset) -> Game {
+        Game::from_position(
+            ruleset,
+            self.board.clone(),
+            Randomizer::sequence(self.pieces.clone()),
+        )
+    }
+
+    /// Checks whether an attempt has passed or failed the puzzle.
+    ///
+    /// Passing is checked first, so a goal reached with the last piece
+    /// passes even though the game then ends for lack of pieces.
+    ///
+    /// # Arguments
+    ///
+    /// * `game` - The attempt, started with [`start`](Puzzle::start)
+    pub fn status(&self, game: &Game) -> PuzzleStatus {
+        let passed = match self.goal {
+            Goal::Lines(lines) => game.lines >= lines,
+            Goal::PerfectClear => game.last_clear.is_some_and(|clear| clear.perfect_clear),
+            Goal::TSpinDouble => game
+                .last_clear
+                .is_some_and(|clear| clear.lines == 2 && clear.tspin == TSpin::Full),
+            Goal::Survive(pieces) => game.pieces >= pieces,
+        };
+        if passed {
+            PuzzleStatus::Passed
+        } else if matches!(game.state, GameState::Playing) {
+            PuzzleStatus::InProgress
+        } else {
+            PuzzleStatus::Failed
+        }
+    }
+}
+
+/// Loads every `.txt` puzzle in a directory, sorted by file name.
+///
+/// Files that fail to parse are skipped with a message on stderr.
+///
+/// # Arguments
+///
+/// * `dir` - The directory to search
+pub fn load_dir(dir: &Path) -> Vec<Puzzle> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+        .collect();
+    paths.sort();
+    paths
+        .iter()
+        .filter_map(|path| {
+            let parsed = fs::read_to_string(path)
+                .map_err(|err| err.to_string())
+                .and_then(|text| Puzzle::parse(&text));
+            match parsed {
+                Ok(puzzle) => Some(puzzle),
+                Err(err) => {
+                    eprintln!("Skipping puzzle {}: {}", path.display(), err);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+fn parse_row(line: &str) -> Result<[bool; BOARD_WIDTH], String> {
+    if line.chars().count() != BOARD_WIDTH {
+        return Err(format!(
+            "Board row '{}' is not {} cells wide",
+            line, BOARD_WIDTH
+        ));
+    }
+    let mut row = [false; BOARD_WIDTH];
+    for (cell, c) in row.iter_mut().zip(line.chars()) {
+        *cell = c != '.';
+    }
+    Ok(row)
+}
+
+fn parse_goal(value: &str) -> Result<Goal, String> {
+    let mut parts = value.split_whitespace();
+    let count = |part: Option<&str>| {
+        part.and_then(|n| n.parse().ok())
+            .ok_or(format!("Goal '{}' needs a count", value))
+    };
+    match parts.next() {
+        Some("lines") => Ok(Goal::Lines(count(parts.next())?)),
+        Some("perfect-clear") => Ok(Goal::PerfectClear),
+        Some("tspin-double") => Ok(Goal::TSpinDouble),
+        Some("survive") => Ok(Goal::Survive(count(parts.next())?)),
+        _ => Err(format!("Unknown goal '{}'", value)),
+    }
+}
+
+fn parse_shape(name: &str) -> Result<Shape, String> {
+    match name {
+        "I" => Ok(Shape::I),
+        "O" => Ok(Shape::O),
+        "T" => Ok(Shape::T),
+        "S" => Ok(Shape::S),
+        "Z" => Ok(Shape::Z),
+        "J" => Ok(Shape::J),
+        "L" => Ok(Shape::L),
+        _ => Err(format!("Unknown piece '{}'", name)),
+    }
+}
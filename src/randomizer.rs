@@ -0,0 +1,114 @@
+//! Randomizer module deciding the order in which pieces are dealt.
+//!
+//! Every [`Randomizer`] owns its own seeded RNG so that a game started from
+//! the same seed always deals the same sequence of pieces.
+
+use std::collections::VecDeque;
+
+use crate::game::match_shape;
+use crate::tetromino::Shape;
+
+/// The piece generation algorithm used by a ruleset.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RandomizerKind {
+    /// Every piece is picked uniformly at random.
+    Uniform,
+    /// The NES algorithm: roll a value in `0..8`, and reroll once in `0..7`
+    /// if it came up as the dummy value `7` or repeats the previous piece.
+    Nes,
+    /// The arcade algorithm: keep a history of the last 4 pieces (starting
+    /// as four Z pieces) and roll up to 4 times until the roll is not in
+    /// the history. The first piece is never S, Z or O.
+    Tgm,
+    /// Pieces are dealt from a fixed list, see [`Randomizer::sequence`].
+    Sequence,
+    /// The guideline "7-bag": all seven pieces are shuffled and dealt
+    /// before the next shuffle.
+    Bag,
+}
+
+/// How many times the TGM randomizer rolls before accepting a piece in its history.
+const TGM_ROLLS: usize = 4;
+
+/// A seeded piece generator.
+#[derive(Clone)]
+pub struct Randomizer {
+    kind: RandomizerKind,
+    rng: fastrand::Rng,
+    last: Option<u8>,
+    history: [u8; 4],
+    sequence: VecDeque<Shape>,
+    bag: Vec<u8>,
+}
+
+impl Randomizer {
+    /// Creates a new randomizer of the given kind.
+    ///
+    /// # Arguments
+    ///
+    /// * `kind` - The generation algorithm to use
+    /// * `seed` - The seed for the internal RNG
+    pub fn new(kind: RandomizerKind, seed: u64) -> Self {
+        Self {
+            kind,
+            rng: fastrand::Rng::with_seed(seed),
+            last: None,
+            history: [4; 4], // Z pieces
+            sequence: VecDeque::new(),
+            bag: Vec::new(),
+        }
+    }
+
+    /// Creates a randomizer that deals a fixed list of pieces and then runs out.
+    ///
+    /// # Arguments
+    ///
+    /// * `shapes` - The pieces to deal, in order
+    pub fn sequence(shapes: Vec<Shape>) -> Self {
+        Self {
+            sequence: shapes.into(),
+            ..Self::new(RandomizerKind::Sequence, 0)
+        }
+    }
+
+    /// Deals the next piece, or `None` once a fixed sequence has run out.
+    pub fn next_shape(&mut self) -> Option<Shape> {
+        let index = match self.kind {
+            RandomizerKind::Uniform => self.rng.u8(0..7), //7 different shapes
+            RandomizerKind::Nes => {
+                let roll = self.rng.u8(0..8);
+                if roll == 7 || Some(roll) == self.last {
+                    self.rng.u8(0..7)
+                } else {
+                    roll
+                }
+            }
+            RandomizerKind::Tgm if self.last.is_none() => {
+                // I, T, J or L
+                [0, 2, 5, 6][self.rng.usize(0..4)]
+            }
+            RandomizerKind::Tgm => {
+                let mut roll = self.rng.u8(0..7);
+                for _ in 1..TGM_ROLLS {
+                    if !self.history.contains(&roll) {
+                        break;
+                    }
+                    roll = self.rng.u8(0..7);
+                }
+                roll
+            }
+            RandomizerKind::Sequence => return self.sequence.pop_front(),
+            RandomizerKind::Bag => {
+                if self.bag.is_empty() {
+                    self.bag = (0..7).collect();
+                    self.rng.shuffle(&mut self.bag);
+                }
+                self.bag.pop().unwrap_or(0)
+            }
+        };
+        self.history.rotate_right(1);
+        self.history[0] = index;
+        self.last = Some(index);
+        Some(match_shape(index))
+    }
+}
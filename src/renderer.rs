@@ -1,79 +1,736 @@
 use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
 
 use crate::board::{BOARD_HEIGHT, BOARD_WIDTH, Board};
-use crate::tetromino::{Direction, Shape, Tetromino};
+use crate::finesse::FinesseMode;
+use crate::game::Game;
+use crate::history::DAY;
+use crate::leaderboard::format_date;
+use crate::master;
+use crate::ruleset::{Mode, StackVisibility};
+use crate::spectate::BoardView;
+use crate::stats::ClearType;
+use crate::tetromino::{Direction, Shape, Tetromino, get_shape};
 
-const BLOCK_SIZE: f32 = 30.0;
-const BOARD_OFFSET_X: f32 = 50.0;
-const BOARD_OFFSET_Y: f32 = 50.0;
+const PREVIEW_BLOCK_SIZE: f32 = 20.0;
 
-pub fn draw_board(board: &Board, current_piece: Option<&Tetromino>) {
-    let mut temp_piece: Tetromino;
-    clear_background(BLACK);
+/// How many frames a piece placed with a finesse fault flashes for.
+const FINESSE_FLASH_FRAMES: u64 = 40;
 
+/// The colours pieces are drawn in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    /// The guideline colours.
+    Classic,
+    /// Softer versions of the guideline colours.
+    Pastel,
+    /// Shades of grey, for players who find colour distracting.
+    Mono,
+}
+
+impl Theme {
+    /// Every theme, in the order settings cycle through them.
+    pub const ALL: [Theme; 3] = [Theme::Classic, Theme::Pastel, Theme::Mono];
+
+    /// The display name of the theme.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Theme::Classic => "Classic",
+            Theme::Pastel => "Pastel",
+            Theme::Mono => "Mono",
+        }
+    }
+
+    fn color(&self, shape: &Shape) -> Color {
+        match self {
+            Theme::Classic => match shape {
+                Shape::I => SKYBLUE,
+                Shape::O => YELLOW,
+                Shape::T => PURPLE,
+                Shape::S => GREEN,
+                Shape::Z => RED,
+                Shape::J => BLUE,
+                Shape::L => ORANGE,
+            },
+            Theme::Pastel => match shape {
+                Shape::I => Color::from_rgba(160, 220, 240, 255),
+                Shape::O => Color::from_rgba(250, 235, 150, 255),
+                Shape::T => Color::from_rgba(200, 170, 230, 255),
+                Shape::S => Color::from_rgba(170, 225, 170, 255),
+                Shape::Z => Color::from_rgba(240, 160, 160, 255),
+                Shape::J => Color::from_rgba(150, 170, 235, 255),
+                Shape::L => Color::from_rgba(245, 195, 140, 255),
+            },
+            Theme::Mono => {
+                let shade = match shape {
+                    Shape::I => 0.9,
+                    Shape::O => 0.8,
+                    Shape::T => 0.7,
+                    Shape::S => 0.6,
+                    Shape::Z => 0.5,
+                    Shape::J => 0.4,
+                    Shape::L => 0.3,
+                };
+                Color::new(shade, shade, shade, 1.0)
+            }
+        }
+    }
+}
+
+/// The player's display options, applied to every board drawn.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Visuals {
+    /// The most upcoming pieces shown, within what the mode shows.
+    pub preview: usize,
+    /// Whether ghost pieces are drawn, in modes that have them.
+    pub ghost: bool,
+    /// Whether the board's cells are outlined.
+    pub grid: bool,
+    pub theme: Theme,
+}
+
+impl Visuals {
+    /// Every option on: all previews, ghost pieces and the classic theme.
+    pub const DEFAULT: Visuals = Visuals {
+        preview: 5,
+        ghost: true,
+        grid: false,
+        theme: Theme::Classic,
+    };
+}
+
+impl Default for Visuals {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Where a board is drawn on screen and how large its blocks are.
+#[derive(Debug, Copy, Clone)]
+pub struct Layout {
+    pub x: f32,
+    pub y: f32,
+    pub block: f32,
+}
+
+impl Layout {
+    /// The layout of a single player game.
+    pub const SOLO: Layout = Layout {
+        x: 50.0,
+        y: 50.0,
+        block: 30.0,
+    };
+
+    /// How many boards fit on screen in a royale match.
+    pub const ROYALE_SLOTS: usize = 10;
+
+    /// The layout of one side of a versus match.
+    ///
+    /// # Arguments
+    ///
+    /// * `player` - The index of the player, `0` for the left side
+    pub fn versus(player: usize) -> Layout {
+        Layout {
+            x: 30.0 + player as f32 * 300.0,
+            y: 140.0,
+            block: 20.0,
+        }
+    }
+
+    /// The layout of a board in a royale match. Slot `0` is the local
+    /// player's board, the others are small boards in a grid beside it.
+    ///
+    /// # Arguments
+    ///
+    /// * `slot` - The slot of the board, below [`Layout::ROYALE_SLOTS`]
+    pub fn royale(slot: usize) -> Layout {
+        if slot == 0 {
+            return Self::versus(0);
+        }
+        let index = slot - 1;
+        Layout {
+            x: 300.0 + (index % 3) as f32 * 95.0,
+            y: 140.0 + (index / 3) as f32 * 190.0,
+            block: 8.0,
+        }
+    }
+
+    /// The layout of a co-op board, centred and scaled to fit the window.
+    ///
+    /// # Arguments
+    ///
+    /// * `columns` - The width of the board in cells
+    pub fn coop(columns: usize) -> Layout {
+        let block = (540.0 / columns as f32).min(27.0);
+        Layout {
+            x: (600.0 - columns as f32 * block) / 2.0,
+            y: 140.0,
+            block,
+        }
+    }
+
+    fn width(&self) -> f32 {
+        BOARD_WIDTH as f32 * self.block
+    }
+
+    fn height(&self) -> f32 {
+        BOARD_HEIGHT as f32 * self.block
+    }
+}
+
+pub fn draw_board(
+    layout: Layout,
+    board: &Board,
+    current_piece: Option<&Tetromino>,
+    ghost: bool,
+    stack: StackVisibility,
+    frame: u64,
+    visuals: &Visuals,
+) {
     draw_rectangle_lines(
-        BOARD_OFFSET_X,
-        BOARD_OFFSET_Y,
-        BOARD_WIDTH as f32 * BLOCK_SIZE,
-        BOARD_HEIGHT as f32 * BLOCK_SIZE,
+        layout.x,
+        layout.y,
+        board.width() as f32 * layout.block,
+        layout.height(),
         2.0,
         WHITE,
     );
+    if visuals.grid {
+        let line = Color::new(1.0, 1.0, 1.0, 0.1);
+        for x in 1..board.width() {
+            let line_x = layout.x + x as f32 * layout.block;
+            draw_line(
+                line_x,
+                layout.y,
+                line_x,
+                layout.y + layout.height(),
+                1.0,
+                line,
+            );
+        }
+        for y in 1..BOARD_HEIGHT {
+            let line_y = layout.y + y as f32 * layout.block;
+            let right = layout.x + board.width() as f32 * layout.block;
+            draw_line(layout.x, line_y, right, line_y, 1.0, line);
+        }
+    }
 
     for y in 0..BOARD_HEIGHT {
-        for x in 0..BOARD_WIDTH {
-            if board.grid[y][x] {
-                draw_block(x as i32, y as i32, GRAY);
+        for x in 0..board.width() {
+            let alpha = stack_alpha(stack, board.lock_frames[y][x], frame);
+            if board.is_filled(x, y) && alpha > 0.0 {
+                draw_block(layout, x as i32, y as i32, Color { a: alpha, ..GRAY });
             }
         }
     }
 
-    if let Some(piece) = current_piece
-        && board.can_place(piece)
-    {
-        temp_piece = Tetromino::new(piece.pos, piece.shape);
-        temp_piece.rotation = piece.rotation;
-        while board.can_place(&temp_piece) {
-            temp_piece.move_piece(Direction::Down);
-        }
+    if let Some(piece) = current_piece {
+        draw_piece(layout, board, piece, ghost, visuals);
+    }
+}
 
-        if temp_piece.pos != piece.pos {
-            temp_piece.move_piece(Direction::Up);
-        }
+/// Flashes the last piece placed with a finesse fault for a moment after it
+/// locked, or where it would have locked when strict finesse took it back.
+///
+/// # Arguments
+///
+/// * `game` - The game being played
+/// * `layout` - Where the game's board is drawn
+pub fn draw_finesse_fault(game: &Game, layout: Layout) {
+    let Some(finesse) = game
+        .last_finesse
+        .as_ref()
+        .filter(|finesse| finesse.is_fault())
+    else {
+        return;
+    };
+    let age = game.frame.saturating_sub(finesse.frame);
+    if age >= FINESSE_FLASH_FRAMES || age / 5 % 2 == 1 {
+        return;
+    }
+    for (x, y) in finesse.piece.cells() {
+        draw_block(layout, x, y, Color { a: 0.8, ..RED });
+    }
+}
 
-        let color = get_piece_color(&piece.shape);
-        for (x, y) in temp_piece.get_cords() {
-            draw_block(x, y, BLACK);
+/// Draws a falling piece, and its ghost where it would land.
+///
+/// # Arguments
+///
+/// * `layout` - Where the board is drawn
+/// * `board` - The board the piece falls on
+/// * `piece` - The falling piece
+/// * `ghost` - Whether the mode has a ghost piece, drawn unless the player
+///   turned ghosts off
+/// * `visuals` - The player's display options
+pub fn draw_piece(
+    layout: Layout,
+    board: &Board,
+    piece: &Tetromino,
+    ghost: bool,
+    visuals: &Visuals,
+) {
+    if !board.can_place(piece) {
+        return;
+    }
+    let mut temp_piece = piece.clone();
+    while board.can_place(&temp_piece) {
+        temp_piece.move_piece(Direction::Down);
+    }
+
+    if temp_piece.pos != piece.pos {
+        temp_piece.move_piece(Direction::Up);
+    }
+
+    let color = visuals.theme.color(&piece.shape);
+    if ghost && visuals.ghost {
+        for (x, y) in temp_piece.cells() {
+            draw_block(layout, x, y, BLACK);
         }
-        for (x, y) in piece.get_cords() {
-            draw_block(x, y, color);
+    }
+    for (x, y) in piece.cells() {
+        draw_block(layout, x, y, color);
+    }
+}
+
+/// Returns the opacity of a locked block, from `0.0` (hidden) to `1.0`.
+fn stack_alpha(stack: StackVisibility, lock_frame: u64, frame: u64) -> f32 {
+    match stack {
+        StackVisibility::Visible => 1.0,
+        StackVisibility::Invisible => 0.0,
+        StackVisibility::Fading(frames) => {
+            let age = frame.saturating_sub(lock_frame) as f32;
+            (1.0 - age / frames as f32).max(0.0)
         }
     }
 }
 
-fn draw_block(x: i32, y: i32, color: Color) {
-    let pixel_x = BOARD_OFFSET_X + x as f32 * BLOCK_SIZE;
-    let pixel_y = BOARD_OFFSET_Y + y as f32 * BLOCK_SIZE;
-    draw_rectangle(pixel_x, pixel_y, BLOCK_SIZE, BLOCK_SIZE, color);
-    draw_rectangle_lines(pixel_x, pixel_y, BLOCK_SIZE, BLOCK_SIZE, 2.0, WHITE);
+fn draw_block(layout: Layout, x: i32, y: i32, color: Color) {
+    let pixel_x = layout.x + x as f32 * layout.block;
+    let pixel_y = layout.y + y as f32 * layout.block;
+    draw_rectangle(pixel_x, pixel_y, layout.block, layout.block, color);
+    draw_rectangle_lines(
+        pixel_x,
+        pixel_y,
+        layout.block,
+        layout.block,
+        2.0,
+        Color {
+            a: color.a,
+            ..WHITE
+        },
+    );
 }
 
-fn get_piece_color(shape: &Shape) -> Color {
-    match shape {
-        Shape::I => SKYBLUE,
-        Shape::O => YELLOW,
-        Shape::T => PURPLE,
-        Shape::S => GREEN,
-        Shape::Z => RED,
-        Shape::J => BLUE,
-        Shape::L => ORANGE,
-    }
+/// Returns how many previews of a game are drawn.
+fn preview_count(game: &Game, visuals: &Visuals) -> usize {
+    game.ruleset.preview.min(visuals.preview)
 }
 
-pub fn draw_ui(score: u32, level: u32) {
-    let ui_x = BOARD_OFFSET_X + (BOARD_WIDTH as f32 * BLOCK_SIZE) + 30.0;
+pub fn draw_ui(game: &Game, visuals: &Visuals) {
+    let ui_x = Layout::SOLO.x + Layout::SOLO.width() + 30.0;
 
     draw_text("TETRIS", ui_x, 80.0, 40.0, WHITE);
-    draw_text(&format!("Score: {}", score), ui_x, 140.0, 30.0, WHITE);
-    draw_text(&format!("Level: {}", level), ui_x, 180.0, 30.0, WHITE);
+    draw_text(game.ruleset.mode.name(), ui_x, 110.0, 20.0, GRAY);
+    draw_text(format!("Score: {}", game.score), ui_x, 150.0, 30.0, WHITE);
+    draw_text(format!("Level: {}", game.level), ui_x, 190.0, 30.0, WHITE);
+    draw_text(format!("Lines: {}", game.lines), ui_x, 230.0, 30.0, WHITE);
+
+    draw_text("Next", ui_x, 280.0, 30.0, WHITE);
+    for (i, shape) in game
+        .next
+        .iter()
+        .take(preview_count(game, visuals))
+        .enumerate()
+    {
+        draw_preview(
+            shape,
+            ui_x,
+            300.0 + i as f32 * 3.0 * PREVIEW_BLOCK_SIZE,
+            PREVIEW_BLOCK_SIZE,
+            visuals,
+        );
+    }
+
+    if game.ruleset.mode == Mode::Master {
+        draw_master_ui(game, ui_x);
+    }
+    if game.ruleset.finesse != FinesseMode::Off {
+        draw_finesse_ui(game, ui_x);
+    }
+    if game.ruleset.undo {
+        draw_text("U: undo  Y: redo", ui_x, 740.0, 20.0, GRAY);
+    }
+    if game.ruleset.top_out_clears {
+        draw_text("Escape: menu", ui_x, 765.0, 20.0, GRAY);
+    }
+}
+
+/// Draws the live statistics of a game in two columns.
+///
+/// # Arguments
+///
+/// * `game` - The game being played
+/// * `x` - The left edge of the panel
+/// * `y` - The baseline of the first row
+pub fn draw_stats(game: &Game, x: f32, y: f32) {
+    let stats = &game.stats;
+    let rows = [
+        format!("Pieces {}", stats.pieces),
+        format!("APM {:.1}", stats.apm(game.frame)),
+        format!("PPS {:.2}", stats.pps(game.frame)),
+        format!("Combo {}", stats.max_combo),
+        format!("KPP {:.2}", stats.kpp()),
+        format!("B2B {}", stats.max_back_to_back),
+    ];
+    for (i, row) in rows.iter().enumerate() {
+        let row_x = x + (i % 2) as f32 * 150.0;
+        let row_y = y + (i / 2) as f32 * 24.0;
+        draw_text(row, row_x, row_y, 22.0, GRAY);
+    }
+}
+
+/// Draws the statistics of a finished game: the rates, records and every
+/// clear type made.
+///
+/// # Arguments
+///
+/// * `game` - The finished game
+/// * `x` - The left edge of the summary
+/// * `y` - The baseline of the first row
+pub fn draw_summary(game: &Game, x: f32, y: f32) {
+    let stats = &game.stats;
+    let mut rows = vec![
+        ("Time".to_string(), master::format_time(game.frame)),
+        ("Lines".to_string(), game.lines.to_string()),
+        ("Pieces".to_string(), stats.pieces.to_string()),
+        ("PPS".to_string(), format!("{:.2}", stats.pps(game.frame))),
+        ("KPP".to_string(), format!("{:.2}", stats.kpp())),
+        ("APM".to_string(), format!("{:.1}", stats.apm(game.frame))),
+        ("Attack".to_string(), stats.attack.to_string()),
+        ("Max combo".to_string(), stats.max_combo.to_string()),
+        ("Max B2B".to_string(), stats.max_back_to_back.to_string()),
+    ];
+    if game.ruleset.finesse != FinesseMode::Off {
+        rows.push(("Faults".to_string(), game.finesse_faults.to_string()));
+    }
+    for (i, (label, value)) in rows.iter().enumerate() {
+        let row_y = y + i as f32 * 24.0;
+        draw_text(label, x, row_y, 22.0, GRAY);
+        draw_text(value, x + 120.0, row_y, 22.0, WHITE);
+    }
+
+    let mut clears: Vec<(&str, u32)> = ClearType::ALL
+        .iter()
+        .map(|&kind| (kind.name(), stats.count(kind)))
+        .collect();
+    clears.push(("Perfect", stats.perfect_clears));
+    let made = clears.iter().filter(|(_, count)| *count > 0);
+    for (i, (name, count)) in made.enumerate() {
+        let row_y = y + i as f32 * 24.0;
+        draw_text(name, x + 240.0, row_y, 22.0, GRAY);
+        draw_text(count.to_string(), x + 380.0, row_y, 22.0, WHITE);
+    }
+}
+
+fn draw_finesse_ui(game: &Game, ui_x: f32) {
+    draw_text(
+        format!("Faults: {}", game.finesse_faults),
+        ui_x,
+        650.0,
+        25.0,
+        WHITE,
+    );
+    if let Some(finesse) = game
+        .last_finesse
+        .as_ref()
+        .filter(|finesse| finesse.is_fault())
+    {
+        draw_text(
+            format!("{} presses, {} needed", finesse.inputs, finesse.keys.len()),
+            ui_x,
+            675.0,
+            20.0,
+            RED,
+        );
+        draw_text(finesse.describe(), ui_x, 698.0, 20.0, GRAY);
+    }
+}
+
+fn draw_master_ui(game: &Game, ui_x: f32) {
+    let y = 300.0 + game.ruleset.preview as f32 * 3.0 * PREVIEW_BLOCK_SIZE + 30.0;
+    draw_text(
+        format!("Grade: {}", master::grade(game)),
+        ui_x,
+        y,
+        30.0,
+        YELLOW,
+    );
+    draw_text(master::format_time(game.frame), ui_x, y + 35.0, 25.0, WHITE);
+
+    let mut section_start = 0;
+    for (i, split) in game.section_splits.iter().enumerate() {
+        draw_text(
+            format!(
+                "{:03} {}",
+                split.level / 100 * 100,
+                master::format_time(split.frame - section_start)
+            ),
+            ui_x,
+            y + 70.0 + i as f32 * 22.0,
+            20.0,
+            GRAY,
+        );
+        section_start = split.frame;
+    }
+}
+
+fn draw_preview(shape: &Shape, x: f32, y: f32, size: f32, visuals: &Visuals) {
+    let color = visuals.theme.color(shape);
+    for (dx, dy) in get_shape(shape, 0) {
+        let pixel_x = x + dx as f32 * size;
+        let pixel_y = y + dy as f32 * size;
+        draw_rectangle(pixel_x, pixel_y, size, size, color);
+        draw_rectangle_lines(pixel_x, pixel_y, size, size, 2.0, WHITE);
+    }
+}
+
+/// Draws the hold piece, previews and incoming garbage around one side of a
+/// versus match.
+///
+/// # Arguments
+///
+/// * `game` - The player's game
+/// * `layout` - Where the player's board is drawn
+/// * `player` - The index of the player
+/// * `pending` - The garbage lines waiting for the player
+/// * `visuals` - The player's display options
+pub fn draw_versus_ui(game: &Game, layout: Layout, player: usize, pending: u32, visuals: &Visuals) {
+    let size = layout.block / 2.0;
+    draw_text(
+        format!("PLAYER {}", player + 1),
+        layout.x,
+        layout.y - 90.0,
+        30.0,
+        WHITE,
+    );
+    draw_text("Hold", layout.x, layout.y - 50.0, 20.0, GRAY);
+    if let Some(shape) = &game.hold {
+        draw_preview(shape, layout.x + 50.0, layout.y - 60.0, size, visuals);
+    }
+
+    let next_x = layout.x + layout.width() + 8.0;
+    for (i, shape) in game
+        .next
+        .iter()
+        .take(preview_count(game, visuals))
+        .enumerate()
+    {
+        draw_preview(
+            shape,
+            next_x,
+            layout.y + i as f32 * 3.0 * size,
+            size,
+            visuals,
+        );
+    }
+
+    draw_text(
+        format!(
+            "PPS {:.2}  APM {:.1}",
+            game.stats.pps(game.frame),
+            game.stats.apm(game.frame)
+        ),
+        layout.x,
+        layout.y + layout.height() + 22.0,
+        18.0,
+        GRAY,
+    );
+
+    // Incoming garbage as a bar along the left edge of the board
+    let bar = (pending as f32 * layout.block).min(layout.height());
+    draw_rectangle(
+        layout.x - 8.0,
+        layout.y + layout.height() - bar,
+        6.0,
+        bar,
+        RED,
+    );
+}
+
+/// Draws one player's hold piece and previews above their half of a co-op
+/// board.
+///
+/// # Arguments
+///
+/// * `game` - The player's game
+/// * `layout` - Where the shared board is drawn
+/// * `player` - The index of the player, `0` for the left half
+/// * `visuals` - The player's display options
+pub fn draw_coop_ui(game: &Game, layout: Layout, player: usize, visuals: &Visuals) {
+    let size = PREVIEW_BLOCK_SIZE * 0.6;
+    let x = layout.x + player as f32 * game.board.width() as f32 * layout.block / 2.0;
+    draw_text(format!("P{}", player + 1), x, layout.y - 70.0, 30.0, WHITE);
+    draw_text("Hold", x, layout.y - 40.0, 18.0, GRAY);
+    if let Some(shape) = &game.hold {
+        draw_preview(shape, x, layout.y - 32.0, size, visuals);
+    }
+    draw_text("Next", x + 70.0, layout.y - 40.0, 18.0, GRAY);
+    for (i, shape) in game
+        .next
+        .iter()
+        .take(preview_count(game, visuals))
+        .enumerate()
+    {
+        draw_preview(
+            shape,
+            x + 70.0 + i as f32 * 5.0 * size,
+            layout.y - 32.0,
+            size,
+            visuals,
+        );
+    }
+}
+
+/// Draws a spectated board with its score, hold piece and previews.
+///
+/// # Arguments
+///
+/// * `view` - The spectated board
+/// * `layout` - Where to draw the board
+/// * `visuals` - The player's display options
+pub fn draw_view(view: &BoardView, layout: Layout, visuals: &Visuals) {
+    let board = view.board();
+    let pieces = view.tetrominoes();
+    draw_board(
+        layout,
+        &board,
+        pieces.first(),
+        true,
+        StackVisibility::Visible,
+        0,
+        visuals,
+    );
+    // The pieces of the other players on a co-op board
+    for piece in pieces.iter().skip(1) {
+        draw_piece(layout, &board, piece, true, visuals);
+    }
+    let size = layout.block / 2.0;
+    if let Some(shape) = &view.hold {
+        draw_preview(shape, layout.x, layout.y - 50.0, size, visuals);
+    }
+    let next_x = layout.x + layout.width() + 8.0;
+    for (i, shape) in view.next.iter().take(visuals.preview).enumerate() {
+        draw_preview(
+            shape,
+            next_x,
+            layout.y + i as f32 * 3.0 * size,
+            size,
+            visuals,
+        );
+    }
+    // Small boards have no room for text
+    if layout.block < Layout::SOLO.block / 2.0 {
+        return;
+    }
+    draw_text(
+        format!(
+            "Score {}  Lines {}  Level {}",
+            view.score, view.lines, view.level
+        ),
+        layout.x,
+        layout.y + layout.height() + 25.0,
+        20.0,
+        WHITE,
+    );
+    if view.over {
+        draw_text(
+            "GAME OVER",
+            layout.x + 10.0,
+            layout.y + layout.height() / 2.0,
+            40.0,
+            RED,
+        );
+    }
+}
+
+/// Draws a line graph of daily values, with the first and last days and the
+/// lowest and highest values labelled.
+///
+/// # Arguments
+///
+/// * `title` - What the values are
+/// * `points` - The days, in days since the Unix epoch, and their values,
+///   oldest first
+/// * `format` - Formats a value for its label
+/// * `rect` - The area of the plot, labels are drawn around it
+pub fn draw_graph(title: &str, points: &[(u64, f64)], format: impl Fn(f64) -> String, rect: Rect) {
+    draw_text(title, rect.x, rect.y - 10.0, 22.0, WHITE);
+    draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 1.0, DARKGRAY);
+    let (Some(&(first, _)), Some(&(last, _))) = (points.first(), points.last()) else {
+        draw_text(
+            "No games yet",
+            rect.x + 10.0,
+            rect.y + rect.h / 2.0,
+            20.0,
+            GRAY,
+        );
+        return;
+    };
+    let low = points
+        .iter()
+        .map(|&(_, value)| value)
+        .fold(f64::INFINITY, f64::min);
+    let high = points
+        .iter()
+        .map(|&(_, value)| value)
+        .fold(f64::NEG_INFINITY, f64::max);
+
+    // A single day or value is drawn in the middle of its axis
+    let to_screen = |(day, value): (u64, f64)| {
+        let across = if last > first {
+            (day - first) as f32 / (last - first) as f32
+        } else {
+            0.5
+        };
+        let up = if high > low {
+            ((value - low) / (high - low)) as f32
+        } else {
+            0.5
+        };
+        vec2(rect.x + across * rect.w, rect.y + (1.0 - up) * rect.h)
+    };
+    let mut previous: Option<Vec2> = None;
+    for &point in points {
+        let position = to_screen(point);
+        if let Some(previous) = previous {
+            draw_line(previous.x, previous.y, position.x, position.y, 2.0, SKYBLUE);
+        }
+        draw_circle(position.x, position.y, 3.0, WHITE);
+        previous = Some(position);
+    }
+
+    draw_text(
+        format(high),
+        rect.x + rect.w + 6.0,
+        rect.y + 12.0,
+        18.0,
+        GRAY,
+    );
+    draw_text(
+        format(low),
+        rect.x + rect.w + 6.0,
+        rect.y + rect.h,
+        18.0,
+        GRAY,
+    );
+    let label_y = rect.y + rect.h + 18.0;
+    draw_text(format_date(first * DAY), rect.x, label_y, 18.0, GRAY);
+    if last > first {
+        let date = format_date(last * DAY);
+        let width = measure_text(&date, None, 18, 1.0).width;
+        draw_text(&date, rect.x + rect.w - width, label_y, 18.0, GRAY);
+    }
 }
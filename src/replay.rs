@@ -0,0 +1,227 @@
+//! Replay module recording games so they can be watched again.
+//!
+//! A game only depends on its ruleset, its seed and the [`Input`] of every
+//! frame, so a [`Replay`] stores just those, with runs of identical inputs
+//! collapsed. Replays are saved as text:
+//!
+//! ```text
+//! mode: nes
+//! level: 9
+//! finesse: Off
+//! seed: 1234
+//! handling: 8 0 20
+//! inputs:
+//! 0 45
+//! 1 3
+//! ```
+//!
+//! where `handling` is the player's DAS, ARR and soft drop factor, left out
+//! when the game was played with the mode's own, and every line after
+//! `inputs:` is the button bitmask and how many frames in a row it was held.
+
+use std::fs;
+use std::path::Path;
+
+use crate::finesse::FinesseMode;
+use crate::game::Game;
+use crate::input::Input;
+use crate::ruleset::{Handling, Mode, Ruleset};
+
+/// The inputs of a game from its start.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replay {
+    pub mode: Mode,
+    pub start_level: u32,
+    pub finesse: FinesseMode,
+    pub seed: u64,
+    pub handling: Option<Handling>,
+    /// Runs of frames with the same input.
+    inputs: Vec<(Input, u32)>,
+}
+
+impl Replay {
+    /// Starts recording a game.
+    ///
+    /// # Arguments
+    ///
+    /// * `ruleset` - The rules of the game, which must be those of its mode
+    ///   apart from the start level, finesse and handling
+    /// * `seed` - The seed the game was created with
+    pub fn new(ruleset: &Ruleset, seed: u64) -> Self {
+        Self {
+            mode: ruleset.mode,
+            start_level: ruleset.start_level,
+            finesse: ruleset.finesse,
+            seed,
+            handling: ruleset.handling,
+            inputs: Vec::new(),
+        }
+    }
+
+    /// Records the input of the next frame.
+    pub fn record(&mut self, input: Input) {
+        match self.inputs.last_mut() {
+            Some((last, frames)) if *last == input => *frames += 1,
+            _ => self.inputs.push((input, 1)),
+        }
+    }
+
+    /// Returns the number of frames recorded.
+    pub fn frames(&self) -> u64 {
+        self.inputs
+            .iter()
+            .map(|&(_, frames)| u64::from(frames))
+            .sum()
+    }
+
+    /// Returns the rules the game was played by.
+    pub fn ruleset(&self) -> Ruleset {
+        Ruleset {
+            finesse: self.finesse,
+            handling: self.handling,
+            ..Ruleset::for_mode(self.mode, self.start_level)
+        }
+    }
+
+    /// Returns the game as it was when recording started.
+    pub fn start(&self) -> Game {
+        Game::new(self.ruleset(), self.seed)
+    }
+
+    /// Returns the input of every frame, in order.
+    pub fn inputs(&self) -> impl Iterator<Item = Input> + '_ {
+        self.inputs
+            .iter()
+            .flat_map(|&(input, frames)| std::iter::repeat_n(input, frames as usize))
+    }
+
+    /// Parses a replay from its text format, see the module documentation.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_tetris::replay::Replay;
+    /// # use rust_tetris::ruleset::Handling;
+    /// let text = "mode: standard\nlevel: 1\nfinesse: Off\nseed: 7\ninputs:\n0 30\n8 2\n";
+    /// let mut replay = Replay::parse(text).unwrap();
+    /// assert_eq!(replay.frames(), 32);
+    /// assert_eq!(Replay::parse(&replay.to_text()), Ok(replay.clone()));
+    ///
+    /// replay.handling = Some(Handling { das: 8, arr: 0, sdf: 20 });
+    /// assert_eq!(Replay::parse(&replay.to_text()), Ok(replay));
+    /// ```
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut mode = None;
+        let mut start_level = 0;
+        let mut finesse = FinesseMode::Off;
+        let mut seed = None;
+        let mut handling = None;
+        let mut inputs = Vec::new();
+        let mut in_inputs = false;
+
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            if in_inputs {
+                let (bits, frames) = line
+                    .split_once(' ')
+                    .ok_or(format!("Expected 'input frames', found '{}'", line))?;
+                let bits = bits
+                    .parse()
+                    .map_err(|_| format!("Invalid input '{}'", bits))?;
+                let frames = frames
+                    .parse()
+                    .map_err(|_| format!("Invalid frame count '{}'", frames))?;
+                inputs.push((Input(bits), frames));
+                continue;
+            }
+            if line == "inputs:" {
+                in_inputs = true;
+                continue;
+            }
+            let (key, value) = line
+                .split_once(':')
+                .ok_or(format!("Expected 'key: value', found '{}'", line))?;
+            let value = value.trim();
+            match key.trim() {
+                "mode" => {
+                    mode = Some(Mode::from_key(value).ok_or(format!("Unknown mode '{}'", value))?)
+                }
+                "level" => {
+                    start_level = value
+                        .parse()
+                        .map_err(|_| format!("Invalid level '{}'", value))?
+                }
+                "finesse" => {
+                    finesse = FinesseMode::ALL
+                        .into_iter()
+                        .find(|finesse| finesse.name() == value)
+                        .ok_or(format!("Unknown finesse mode '{}'", value))?
+                }
+                "seed" => {
+                    seed = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("Invalid seed '{}'", value))?,
+                    )
+                }
+                "handling" => handling = Some(parse_handling(value)?),
+                other => return Err(format!("Unknown key '{}'", other)),
+            }
+        }
+        Ok(Self {
+            mode: mode.ok_or("Missing mode")?,
+            start_level,
+            finesse,
+            seed: seed.ok_or("Missing seed")?,
+            handling,
+            inputs,
+        })
+    }
+
+    /// Returns the replay in its text format.
+    pub fn to_text(&self) -> String {
+        let mut text = format!(
+            "mode: {}\nlevel: {}\nfinesse: {}\nseed: {}\n",
+            self.mode.key(),
+            self.start_level,
+            self.finesse.name(),
+            self.seed
+        );
+        if let Some(handling) = self.handling {
+            text.push_str(&format!(
+                "handling: {} {} {}\n",
+                handling.das, handling.arr, handling.sdf
+            ));
+        }
+        text.push_str("inputs:\n");
+        for (input, frames) in &self.inputs {
+            text.push_str(&format!("{} {}\n", input.0, frames));
+        }
+        text
+    }
+
+    /// Loads a replay file.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        Self::parse(&text)
+    }
+
+    /// Saves the replay to a file, creating its directory if needed.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+        }
+        fs::write(path, self.to_text()).map_err(|err| err.to_string())
+    }
+}
+
+fn parse_handling(value: &str) -> Result<Handling, String> {
+    let numbers: Vec<u32> = value
+        .split_whitespace()
+        .map(|number| number.parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| format!("Invalid handling '{}'", value))?;
+    let [das, arr, sdf] = numbers[..] else {
+        return Err(format!("Expected 'das arr sdf', found '{}'", value));
+    };
+    Ok(Handling { das, arr, sdf })
+}
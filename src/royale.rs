@@ -0,0 +1,297 @@
+//! Royale module pitting any number of games against each other.
+//!
+//! Attacks are computed like in [`Versus`](crate::versus::Versus) and cancel
+//! the attacker's own incoming garbage first, but what is left is routed by
+//! the attacker's [`Targeting`] strategy, which each player cycles through
+//! with [`Input::TARGET`]. Knocking a player out earns a badge, plus every
+//! badge the victim had collected, and badges raise the attacker's attack
+//! by the percentages in [`BADGE_BONUSES`]. The last player standing wins.
+//!
+//! All games are driven by [`Royale::update`] with one [`Input`] per player,
+//! so a match only depends on its seed and the inputs it was given.
+
+use crate::board::BOARD_HEIGHT;
+use crate::game::{Game, GameState};
+use crate::garbage::GarbageHoles;
+use crate::input::Input;
+use crate::ruleset::Ruleset;
+use crate::versus::attack;
+
+/// The attack bonus granted by badges, as (badges needed, extra percent).
+pub const BADGE_BONUSES: [(u32, u32); 4] = [(2, 25), (6, 50), (14, 75), (30, 100)];
+
+/// How a player picks whom their attacks are sent to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Targeting {
+    /// A random opponent, kept until they are knocked out.
+    Random,
+    /// Every opponent currently targeting the player, or a random one if
+    /// nobody is.
+    Attackers,
+    /// The opponent closest to topping out.
+    Kos,
+    /// The opponent with the most badges.
+    Badges,
+}
+
+impl Targeting {
+    /// Every strategy, in the order [`Input::TARGET`] cycles through them.
+    pub const ALL: [Targeting; 4] = [
+        Targeting::Random,
+        Targeting::Attackers,
+        Targeting::Kos,
+        Targeting::Badges,
+    ];
+
+    /// The display name of the strategy.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Targeting::Random => "Random",
+            Targeting::Attackers => "Attackers",
+            Targeting::Kos => "KOs",
+            Targeting::Badges => "Badges",
+        }
+    }
+
+    fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&other| other == self);
+        Self::ALL[index.map_or(0, |index| (index + 1) % Self::ALL.len())]
+    }
+}
+
+/// Returns the extra attack, in percent, granted by a number of badges.
+///
+/// # Examples
+///
+/// ```
+/// # use rust_tetris::royale::badge_bonus;
+/// assert_eq!(badge_bonus(1), 0);
+/// assert_eq!(badge_bonus(6), 50);
+/// assert_eq!(badge_bonus(100), 100);
+/// ```
+pub fn badge_bonus(badges: u32) -> u32 {
+    BADGE_BONUSES
+        .iter()
+        .rev()
+        .find(|&&(needed, _)| badges >= needed)
+        .map_or(0, |&(_, percent)| percent)
+}
+
+/// A local match between any number of players.
+pub struct Royale {
+    pub players: Vec<Game>,
+    /// The targeting strategy of each player.
+    pub targeting: Vec<Targeting>,
+    /// The players each player's attacks currently go to.
+    pub targets: Vec<Vec<usize>>,
+    /// The players each player has knocked out.
+    pub kos: Vec<u32>,
+    /// The badges each player has collected.
+    pub badges: Vec<u32>,
+    /// The total lines each player has sent.
+    pub sent: Vec<u32>,
+    /// The players knocked out so far, in order.
+    pub knocked_out: Vec<usize>,
+    /// The index of the winning player once the match is over.
+    pub winner: Option<usize>,
+    // The player who last sent garbage to each player, credited with the KO
+    last_attacker: Vec<Option<usize>>,
+    random_target: Vec<Option<usize>>,
+    held: Vec<Input>,
+    holes: GarbageHoles,
+    rng: fastrand::Rng,
+}
+
+impl Royale {
+    /// Creates a new match.
+    ///
+    /// Every player is dealt the same pieces and starts out targeting a
+    /// random opponent.
+    ///
+    /// # Arguments
+    ///
+    /// * `ruleset` - The rules every player plays by
+    /// * `seed` - The seed for the pieces, the garbage holes and the targets
+    /// * `players` - The number of players, at least 2
+    pub fn new(ruleset: Ruleset, seed: u64, players: usize) -> Self {
+        let players = players.max(2);
+        let mut royale = Self {
+            players: (0..players).map(|_| Game::new(ruleset, seed)).collect(),
+            targeting: vec![Targeting::Random; players],
+            targets: vec![Vec::new(); players],
+            kos: vec![0; players],
+            badges: vec![0; players],
+            sent: vec![0; players],
+            knocked_out: Vec::new(),
+            winner: None,
+            last_attacker: vec![None; players],
+            random_target: vec![None; players],
+            held: vec![Input::NONE; players],
+            holes: GarbageHoles::new(ruleset.garbage, seed),
+            rng: fastrand::Rng::with_seed(seed),
+        };
+        royale.retarget();
+        royale
+    }
+
+    /// Returns whether a player has not been knocked out.
+    pub fn is_alive(&self, player: usize) -> bool {
+        matches!(self.players[player].state, GameState::Playing)
+    }
+
+    /// Returns the placement of a player, `1` for the winner and for players
+    /// still in the match.
+    pub fn placement(&self, player: usize) -> usize {
+        match self.knocked_out.iter().position(|&other| other == player) {
+            Some(index) => self.players.len() - index,
+            None => 1,
+        }
+    }
+
+    /// Advances every game by one frame, routes garbage and knocks out
+    /// players who topped out.
+    ///
+    /// # Arguments
+    ///
+    /// * `inputs` - The buttons held by each player during this frame;
+    ///   missing inputs count as nothing held
+    pub fn update(&mut self, inputs: &[Input]) {
+        if self.winner.is_some() {
+            return;
+        }
+        for player in 0..self.players.len() {
+            let input = inputs.get(player).copied().unwrap_or_default();
+            if input
+                .pressed_since(self.held[player])
+                .contains(Input::TARGET)
+            {
+                self.targeting[player] = self.targeting[player].next();
+            }
+            self.held[player] = input;
+            if !self.is_alive(player) {
+                continue;
+            }
+
+            let pieces = self.players[player].pieces;
+            self.players[player].update(input);
+            if self.players[player].pieces == pieces {
+                continue;
+            }
+            if let Some(clear) = self.players[player].last_clear {
+                let boosted = attack(&clear) * (100 + badge_bonus(self.badges[player])) / 100;
+                let lines = self.players[player].cancel_garbage(boosted);
+                self.send(player, lines);
+            }
+        }
+        self.knock_out();
+        self.retarget();
+    }
+
+    fn send(&mut self, attacker: usize, lines: u32) {
+        if lines == 0 {
+            return;
+        }
+        for &target in &self.targets[attacker] {
+            self.sent[attacker] += lines;
+            self.last_attacker[target] = Some(attacker);
+            let width = self.players[target].board.width();
+            self.players[target]
+                .incoming
+                .extend(self.holes.attack(lines, width));
+        }
+    }
+
+    /// Records the players who topped out this frame, credits their KOs and
+    /// decides the winner once at most one player is left.
+    fn knock_out(&mut self) {
+        let fallen: Vec<usize> = (0..self.players.len())
+            .filter(|&player| !self.is_alive(player) && !self.knocked_out.contains(&player))
+            .collect();
+        for &victim in &fallen {
+            self.knocked_out.push(victim);
+            if let Some(attacker) = self.last_attacker[victim] {
+                self.kos[attacker] += 1;
+                self.badges[attacker] += 1 + self.badges[victim];
+            }
+        }
+
+        let mut alive = (0..self.players.len()).filter(|&player| self.is_alive(player));
+        match (alive.next(), alive.next()) {
+            (Some(winner), None) => {
+                self.winner = Some(winner);
+                self.knocked_out.push(winner);
+            }
+            // Everyone left topped out on the same frame: whoever sent the
+            // most wins
+            (None, _) => {
+                let winner = fallen
+                    .iter()
+                    .copied()
+                    .max_by_key(|&player| (self.sent[player], usize::MAX - player));
+                if let Some(winner) = winner {
+                    self.knocked_out.retain(|&player| player != winner);
+                    self.knocked_out.push(winner);
+                }
+                self.winner = winner;
+            }
+            _ => {}
+        }
+    }
+
+    /// Picks the targets of every player still in the match.
+    fn retarget(&mut self) {
+        let previous = std::mem::take(&mut self.targets);
+        self.targets = vec![Vec::new(); self.players.len()];
+        if self.winner.is_some() {
+            return;
+        }
+        for player in 0..self.players.len() {
+            if !self.is_alive(player) {
+                continue;
+            }
+            let opponents: Vec<usize> = (0..self.players.len())
+                .filter(|&other| other != player && self.is_alive(other))
+                .collect();
+            let by_danger = |&other: &usize| (danger(&self.players[other]), usize::MAX - other);
+            self.targets[player] = match self.targeting[player] {
+                Targeting::Random => Vec::new(),
+                Targeting::Attackers => opponents
+                    .iter()
+                    .copied()
+                    .filter(|&other| previous[other].contains(&player))
+                    .collect(),
+                Targeting::Kos => opponents
+                    .iter()
+                    .copied()
+                    .max_by_key(by_danger)
+                    .into_iter()
+                    .collect(),
+                Targeting::Badges => opponents
+                    .iter()
+                    .copied()
+                    .max_by_key(|other| (self.badges[*other], by_danger(other)))
+                    .into_iter()
+                    .collect(),
+            };
+            if self.targets[player].is_empty() {
+                let target = self.random_target[player]
+                    .filter(|target| opponents.contains(target))
+                    .or_else(|| {
+                        (!opponents.is_empty())
+                            .then(|| opponents[self.rng.usize(..opponents.len())])
+                    });
+                self.random_target[player] = target;
+                self.targets[player] = target.into_iter().collect();
+            }
+        }
+    }
+}
+
+/// Returns how close a game is to topping out: the height of its stack plus
+/// the garbage waiting for it.
+fn danger(game: &Game) -> u32 {
+    let empty_rows = (0..BOARD_HEIGHT)
+        .take_while(|&y| game.board.row(y) == 0)
+        .count();
+    (BOARD_HEIGHT - empty_rows) as u32 + game.pending_garbage()
+}
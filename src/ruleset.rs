@@ -0,0 +1,652 @@
+//! Ruleset module describing how a game mode plays.
+//!
+//! A [`Ruleset`] bundles everything that differs between modes: the gravity
+//! curve, how lines are scored, how the level advances, which randomizer
+//! deals the pieces, the rotation system and the handling and delay
+//! timings. All timings are in frames, see [`FRAME_RATE`].
+
+use crate::finesse::FinesseMode;
+use crate::game::LineClear;
+use crate::garbage::HoleStrategy;
+use crate::master;
+use crate::randomizer::RandomizerKind;
+use crate::tetromino::RotationSystem;
+
+/// The number of simulation frames per second.
+pub const FRAME_RATE: f64 = 60.0;
+
+/// Frames per row for NES levels 0 through 29 (level 29 and above is 1).
+const NES_GRAVITY: [u32; 30] = [
+    48, 43, 38, 33, 28, 23, 18, 13, 8, 6, 5, 5, 5, 4, 4, 4, 3, 3, 3, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+    1,
+];
+
+/// The selectable game modes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Mode {
+    /// The original mode: time-based levels, hard drop and ghost piece.
+    Standard,
+    /// The 1989 NES ruleset.
+    Nes,
+    /// Arcade-style Master: 20G, shrinking delays and grades.
+    Master,
+    /// Relaxed practice: no top out and unlimited undo.
+    Zen,
+    /// Fixed board layouts and piece sequences with a goal.
+    Puzzle,
+    /// Locked blocks disappear immediately.
+    Invisible,
+    /// Locked blocks fade out a few seconds after locking.
+    Fading,
+    /// Every mino is a 2x2 block on a half-resolution board.
+    Big,
+    /// Two players side by side, sending garbage to each other.
+    Versus,
+    /// Any number of players online, sending garbage to the targets they pick.
+    Royale,
+    /// Two players with a piece each on one wide board.
+    Coop,
+}
+
+impl Mode {
+    /// Every mode.
+    pub const ALL: [Mode; 11] = [
+        Mode::Standard,
+        Mode::Nes,
+        Mode::Master,
+        Mode::Zen,
+        Mode::Puzzle,
+        Mode::Invisible,
+        Mode::Fading,
+        Mode::Big,
+        Mode::Versus,
+        Mode::Royale,
+        Mode::Coop,
+    ];
+
+    /// The modes offered by the menu, in menu order. Royale matches are
+    /// only played online, against a match server.
+    pub const MENU: [Mode; 10] = [
+        Mode::Standard,
+        Mode::Nes,
+        Mode::Master,
+        Mode::Zen,
+        Mode::Puzzle,
+        Mode::Invisible,
+        Mode::Fading,
+        Mode::Big,
+        Mode::Versus,
+        Mode::Coop,
+    ];
+
+    /// The display name of the mode.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Standard => "Standard",
+            Mode::Nes => "Classic (NES)",
+            Mode::Master => "Master",
+            Mode::Zen => "Zen",
+            Mode::Puzzle => "Puzzle",
+            Mode::Invisible => "Invisible",
+            Mode::Fading => "Fading",
+            Mode::Big => "Big",
+            Mode::Versus => "Versus",
+            Mode::Royale => "Royale",
+            Mode::Coop => "Co-op",
+        }
+    }
+
+    /// The short name of the mode in files, e.g. `"nes"`.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Mode::Standard => "standard",
+            Mode::Nes => "nes",
+            Mode::Master => "master",
+            Mode::Zen => "zen",
+            Mode::Puzzle => "puzzle",
+            Mode::Invisible => "invisible",
+            Mode::Fading => "fading",
+            Mode::Big => "big",
+            Mode::Versus => "versus",
+            Mode::Royale => "royale",
+            Mode::Coop => "coop",
+        }
+    }
+
+    /// Returns the mode with a short name, see [`key`](Mode::key).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_tetris::ruleset::Mode;
+    /// assert_eq!(Mode::from_key("nes"), Some(Mode::Nes));
+    /// assert_eq!(Mode::from_key("tetris"), None);
+    /// ```
+    pub fn from_key(key: &str) -> Option<Mode> {
+        Mode::ALL.into_iter().find(|mode| mode.key() == key)
+    }
+
+    /// Returns whether the mode lets the player pick a start level.
+    pub fn has_level_select(&self) -> bool {
+        matches!(self, Mode::Nes)
+    }
+}
+
+/// The speed at which the active piece falls.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Gravity {
+    /// The piece falls one row every given number of frames.
+    Frames(u32),
+    /// The piece falls the given number of 1/256 rows every frame, so 256
+    /// is 1G and 5120 is 20G.
+    Subrows(u32),
+}
+
+/// The gravity a ruleset uses at a given level.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GravityCurve {
+    /// 30 frames at level 1, sped up by 20% every level.
+    Standard,
+    /// The NES frame table.
+    Nes,
+    /// The Master table, reaching 20G at level 500.
+    Master,
+}
+
+/// What the hard drop button does.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum HardDrop {
+    /// Nothing: there is no hard drop.
+    None,
+    /// Drop the piece to the bottom without locking it ("sonic drop").
+    Sonic,
+    /// Drop the piece to the bottom and lock it immediately.
+    Lock,
+}
+
+/// Handling and delay timings, in frames.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Timings {
+    /// Frames a direction must be held before auto shift starts.
+    pub das: u32,
+    /// Frames between auto shift moves.
+    pub arr: u32,
+    /// Frames between a piece locking and the next one spawning.
+    pub are: u32,
+    /// ARE used instead of `are` after a line clear.
+    pub line_are: u32,
+    /// Frames the game pauses after clearing lines.
+    pub line_clear: u32,
+    /// Frames a grounded piece waits before locking. When `None`, the piece
+    /// falls at most one row per frame and locks as soon as gravity fails to
+    /// move it down.
+    pub lock_delay: Option<u32>,
+    /// How many moves and rotations of a grounded piece restart its lock
+    /// delay. Moving down a row always restarts it.
+    pub lock_resets: u32,
+}
+
+/// A player's own handling, replacing a mode's auto shift and soft drop.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "gui", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "gui", serde(default))]
+pub struct Handling {
+    /// Frames a direction must be held before auto shift starts.
+    pub das: u32,
+    /// Frames between auto shift moves, `0` moving straight to the wall.
+    pub arr: u32,
+    /// Soft drop factor: how many times faster than gravity soft drop is.
+    pub sdf: u32,
+}
+
+impl Default for Handling {
+    /// The standard mode's handling.
+    fn default() -> Self {
+        Self {
+            das: 10,
+            arr: 2,
+            sdf: 2,
+        }
+    }
+}
+
+/// How locked blocks are shown while the game is running. The whole stack
+/// is always revealed once the game ends.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StackVisibility {
+    /// Locked blocks are always drawn.
+    Visible,
+    /// Locked blocks are hidden as soon as they lock.
+    Invisible,
+    /// Locked blocks fade out over the given number of frames after locking.
+    Fading(u32),
+}
+
+/// How cleared lines are turned into points.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Scoring {
+    /// 100/300/500/800 regardless of level.
+    Standard,
+    /// 40/100/300/1200 multiplied by `level + 1`.
+    Nes,
+    /// `(ceil((level + lines) / 4) + soft drop frames) * lines * combo * bravo`,
+    /// where bravo is 4 for a cleared board and 1 otherwise.
+    Master,
+}
+
+/// How the level advances during a game.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Leveling {
+    /// One level every given number of frames.
+    Timed(u64),
+    /// The NES transition: the first level up happens after
+    /// `min(start * 10 + 10, max(100, start * 10 - 50))` lines, then one
+    /// level every 10 lines.
+    Nes,
+    /// One level per piece and one per cleared line, where only line clears
+    /// can pass a section stop (see [`master::is_level_stop`]).
+    Master,
+    /// The level never changes.
+    Fixed,
+}
+
+/// The full description of how a mode plays.
+#[derive(Debug, Copy, Clone)]
+pub struct Ruleset {
+    /// The mode this ruleset implements.
+    pub mode: Mode,
+    /// The level the game starts on.
+    pub start_level: u32,
+    /// The gravity curve.
+    pub gravity: GravityCurve,
+    /// The line clear scoring.
+    pub scoring: Scoring,
+    /// The level progression.
+    pub leveling: Leveling,
+    /// The piece generation algorithm.
+    pub randomizer: RandomizerKind,
+    /// The rotation system.
+    pub rotation: RotationSystem,
+    /// What hard drop does.
+    pub hard_drop: HardDrop,
+    /// Whether soft dropping a grounded piece locks it at once, skipping
+    /// the rest of its lock delay as in Master.
+    pub soft_drop_locks: bool,
+    /// Whether the player can hold a piece.
+    pub hold: bool,
+    /// Whether the ghost piece is drawn.
+    pub ghost: bool,
+    /// How many upcoming pieces are shown.
+    pub preview: usize,
+    /// The handling and delay timings.
+    pub timings: Timings,
+    /// Whether topping out clears the board instead of ending the game.
+    pub top_out_clears: bool,
+    /// Whether placements can be undone and redone.
+    pub undo: bool,
+    /// How locked blocks are shown.
+    pub stack: StackVisibility,
+    /// Whether pieces are played at double scale, see [`Mode::Big`].
+    pub big: bool,
+    /// Where the holes of received garbage go.
+    pub garbage: HoleStrategy,
+    /// How finesse is judged.
+    pub finesse: FinesseMode,
+    /// The player's handling, `None` playing with the mode's own.
+    pub handling: Option<Handling>,
+}
+
+impl Ruleset {
+    /// The original ruleset of the game.
+    pub fn standard() -> Self {
+        Self {
+            mode: Mode::Standard,
+            start_level: 1,
+            gravity: GravityCurve::Standard,
+            scoring: Scoring::Standard,
+            leveling: Leveling::Timed(30 * FRAME_RATE as u64),
+            randomizer: RandomizerKind::Uniform,
+            rotation: RotationSystem::Classic,
+            hard_drop: HardDrop::Sonic,
+            soft_drop_locks: false,
+            hold: false,
+            ghost: true,
+            preview: 1,
+            timings: Timings {
+                das: 10,
+                arr: 2,
+                are: 0,
+                line_are: 0,
+                line_clear: 0,
+                lock_delay: None,
+                lock_resets: 0,
+            },
+            top_out_clears: false,
+            undo: false,
+            stack: StackVisibility::Visible,
+            big: false,
+            garbage: HoleStrategy::Clean,
+            finesse: FinesseMode::Off,
+            handling: None,
+        }
+    }
+
+    /// The NES ruleset: no hard drop, no ghost, one preview and DAS 16/6.
+    ///
+    /// # Arguments
+    ///
+    /// * `start_level` - The level selected before the game starts
+    pub fn nes(start_level: u32) -> Self {
+        Self {
+            mode: Mode::Nes,
+            start_level,
+            gravity: GravityCurve::Nes,
+            scoring: Scoring::Nes,
+            leveling: Leveling::Nes,
+            randomizer: RandomizerKind::Nes,
+            rotation: RotationSystem::Classic,
+            hard_drop: HardDrop::None,
+            soft_drop_locks: false,
+            hold: false,
+            ghost: false,
+            preview: 1,
+            timings: Timings {
+                das: 16,
+                arr: 6,
+                are: 0,
+                line_are: 0,
+                line_clear: 0,
+                lock_delay: None,
+                lock_resets: 0,
+            },
+            top_out_clears: false,
+            undo: false,
+            stack: StackVisibility::Visible,
+            big: false,
+            garbage: HoleStrategy::Clean,
+            finesse: FinesseMode::Off,
+            handling: None,
+        }
+    }
+
+    /// The Master ruleset: ARS rotation, sonic drop and section-based delays.
+    pub fn master() -> Self {
+        Self {
+            mode: Mode::Master,
+            start_level: 0,
+            gravity: GravityCurve::Master,
+            scoring: Scoring::Master,
+            leveling: Leveling::Master,
+            randomizer: RandomizerKind::Tgm,
+            rotation: RotationSystem::Ars,
+            hard_drop: HardDrop::Sonic,
+            soft_drop_locks: true,
+            hold: false,
+            ghost: true,
+            preview: 1,
+            timings: master::timings(0),
+            top_out_clears: false,
+            undo: false,
+            stack: StackVisibility::Visible,
+            big: false,
+            garbage: HoleStrategy::Clean,
+            finesse: FinesseMode::Off,
+            handling: None,
+        }
+    }
+
+    /// The Zen ruleset: standard handling at a constant speed, where topping
+    /// out clears the board and every placement can be undone.
+    pub fn zen() -> Self {
+        Self {
+            mode: Mode::Zen,
+            leveling: Leveling::Fixed,
+            preview: 3,
+            top_out_clears: true,
+            undo: true,
+            ..Self::standard()
+        }
+    }
+
+    /// The Puzzle ruleset: SRS rotation at a constant speed with a long
+    /// preview, played on a board and piece sequence from a puzzle file.
+    pub fn puzzle() -> Self {
+        Self {
+            mode: Mode::Puzzle,
+            leveling: Leveling::Fixed,
+            randomizer: RandomizerKind::Sequence,
+            rotation: RotationSystem::Srs,
+            preview: 5,
+            ..Self::standard()
+        }
+    }
+
+    /// The Invisible ruleset: standard play where locked blocks vanish.
+    pub fn invisible() -> Self {
+        Self {
+            mode: Mode::Invisible,
+            stack: StackVisibility::Invisible,
+            ..Self::standard()
+        }
+    }
+
+    /// The Fading ruleset: standard play where locked blocks fade out over
+    /// four seconds.
+    pub fn fading() -> Self {
+        Self {
+            mode: Mode::Fading,
+            stack: StackVisibility::Fading(4 * FRAME_RATE as u32),
+            ..Self::standard()
+        }
+    }
+
+    /// The Big ruleset: standard play with 2x2 minos, where two board rows
+    /// count as one line.
+    pub fn big() -> Self {
+        Self {
+            mode: Mode::Big,
+            big: true,
+            ..Self::standard()
+        }
+    }
+
+    /// The Versus ruleset: guideline-style play with SRS, a 7-bag, hold,
+    /// five previews, locking hard drop and a lock delay with move resets.
+    pub fn versus() -> Self {
+        Self {
+            mode: Mode::Versus,
+            leveling: Leveling::Fixed,
+            randomizer: RandomizerKind::Bag,
+            rotation: RotationSystem::Srs,
+            hard_drop: HardDrop::Lock,
+            hold: true,
+            preview: 5,
+            timings: Timings {
+                lock_delay: Some(30),
+                lock_resets: 15,
+                ..Self::standard().timings
+            },
+            ..Self::standard()
+        }
+    }
+
+    /// The Royale ruleset: the Versus rules, played by more players.
+    pub fn royale() -> Self {
+        Self {
+            mode: Mode::Royale,
+            ..Self::versus()
+        }
+    }
+
+    /// The Co-op ruleset: the Versus handling with timed levels and three
+    /// previews per player.
+    pub fn coop() -> Self {
+        Self {
+            mode: Mode::Coop,
+            leveling: Self::standard().leveling,
+            preview: 3,
+            ..Self::versus()
+        }
+    }
+
+    /// Creates the default ruleset for a mode.
+    ///
+    /// # Arguments
+    ///
+    /// * `mode` - The mode to create the ruleset for
+    /// * `start_level` - The selected start level, ignored by modes without level select
+    pub fn for_mode(mode: Mode, start_level: u32) -> Self {
+        match mode {
+            Mode::Standard => Self::standard(),
+            Mode::Nes => Self::nes(start_level),
+            Mode::Master => Self::master(),
+            Mode::Zen => Self::zen(),
+            Mode::Puzzle => Self::puzzle(),
+            Mode::Invisible => Self::invisible(),
+            Mode::Fading => Self::fading(),
+            Mode::Big => Self::big(),
+            Mode::Versus => Self::versus(),
+            Mode::Royale => Self::royale(),
+            Mode::Coop => Self::coop(),
+        }
+    }
+
+    /// Returns the size of a mino in board cells.
+    pub fn piece_scale(&self) -> i32 {
+        if self.big { 2 } else { 1 }
+    }
+
+    /// Returns the gravity at a level.
+    pub fn gravity(&self, level: u32) -> Gravity {
+        match self.gravity {
+            GravityCurve::Standard => {
+                let frames = 30.0 * 0.8_f64.powi(level.saturating_sub(1) as i32);
+                Gravity::Frames((frames.round() as u32).max(1))
+            }
+            GravityCurve::Nes => {
+                Gravity::Frames(NES_GRAVITY[(level as usize).min(NES_GRAVITY.len() - 1)])
+            }
+            GravityCurve::Master => master::gravity(level),
+        }
+    }
+
+    /// Returns the gravity while soft dropping at a level.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_tetris::ruleset::{Gravity, Handling, Ruleset};
+    /// let mut ruleset = Ruleset::standard();
+    /// assert_eq!(ruleset.soft_drop_gravity(1), Gravity::Frames(15));
+    /// ruleset.handling = Some(Handling { sdf: 60, ..Handling::default() });
+    /// assert_eq!(ruleset.soft_drop_gravity(1), Gravity::Subrows(512));
+    /// ```
+    pub fn soft_drop_gravity(&self, level: u32) -> Gravity {
+        if let Some(handling) = self.handling {
+            let sdf = handling.sdf.max(1);
+            return match self.gravity(level) {
+                Gravity::Frames(frames) if frames >= sdf => Gravity::Frames(frames / sdf),
+                Gravity::Frames(frames) => Gravity::Subrows(256 * sdf / frames),
+                Gravity::Subrows(subrows) => Gravity::Subrows(subrows * sdf),
+            };
+        }
+        match (self.gravity, self.gravity(level)) {
+            (GravityCurve::Standard, Gravity::Frames(frames)) => {
+                Gravity::Frames((frames / 2).max(1))
+            }
+            (GravityCurve::Nes, Gravity::Frames(frames)) => Gravity::Frames(frames.min(2)),
+            (_, Gravity::Subrows(subrows)) => Gravity::Subrows(subrows.max(256)),
+            (_, gravity) => gravity,
+        }
+    }
+
+    /// Returns the handling and delay timings at a level.
+    pub fn timings_at(&self, level: u32) -> Timings {
+        let mut timings = match self.mode {
+            Mode::Master => master::timings(level),
+            _ => self.timings,
+        };
+        if let Some(handling) = self.handling {
+            timings.das = handling.das;
+            timings.arr = handling.arr;
+        }
+        timings
+    }
+
+    /// Returns the points awarded for clearing lines.
+    ///
+    /// # Arguments
+    ///
+    /// * `clear` - The line clear to score
+    pub fn line_score(&self, clear: &LineClear) -> u32 {
+        let (lines, level) = (clear.lines, clear.level);
+        match self.scoring {
+            Scoring::Standard => match lines {
+                1 => 100,
+                2 => 300,
+                3 => 500,
+                4 => 800,
+                _ => 0,
+            },
+            Scoring::Nes => {
+                let base = match lines {
+                    1 => 40,
+                    2 => 100,
+                    3 => 300,
+                    4 => 1200,
+                    _ => 0,
+                };
+                base * (level + 1)
+            }
+            Scoring::Master => {
+                let bravo = if clear.perfect_clear { 4 } else { 1 };
+                ((level + lines).div_ceil(4) + clear.soft_drop_frames) * lines * clear.combo * bravo
+            }
+        }
+    }
+
+    /// Returns the level after a new piece spawns.
+    pub fn level_after_spawn(&self, level: u32) -> u32 {
+        match self.leveling {
+            Leveling::Master if !master::is_level_stop(level) => level + 1,
+            _ => level,
+        }
+    }
+
+    /// Returns whether reaching a level completes the game.
+    pub fn is_complete(&self, level: u32) -> bool {
+        self.leveling == Leveling::Master && level >= master::MAX_LEVEL
+    }
+
+    /// Returns the level after clearing lines.
+    pub fn level_after_clear(&self, level: u32, lines: u32) -> u32 {
+        match self.leveling {
+            Leveling::Master => (level + lines).min(master::MAX_LEVEL),
+            _ => level,
+        }
+    }
+
+    /// Returns the level a game should be on.
+    ///
+    /// # Arguments
+    ///
+    /// * `lines` - Total lines cleared so far
+    /// * `frame` - Frames elapsed since the game started
+    pub fn level_for(&self, lines: u32, frame: u64) -> u32 {
+        match self.leveling {
+            Leveling::Timed(frames_per_level) => {
+                self.start_level + (frame / frames_per_level) as u32
+            }
+            Leveling::Nes => {
+                let start = self.start_level;
+                let first = (start * 10 + 10).min(100.max((start * 10).saturating_sub(50)));
+                if lines < first {
+                    start
+                } else {
+                    start + 1 + (lines - first) / 10
+                }
+            }
+            Leveling::Master | Leveling::Fixed => self.start_level,
+        }
+    }
+}
@@ -0,0 +1,457 @@
+//! Server module hosting multiplayer matches between remote clients.
+//!
+//! Clients connect exactly like they would to a hosting player (see
+//! [`NetSession::join`](crate::net::NetSession::join)) and are grouped in
+//! the order they arrive: in pairs for versus matches, or in larger groups
+//! for royale matches. Every group plays in its own room, on its own thread.
+//! The room relays each player's inputs to the others and re-simulates the
+//! match itself, so the garbage it relays and the winner it announces come
+//! from its own simulation rather than from the clients.
+//!
+//! A client whose garbage reports disagree with that simulation, or who
+//! disconnects, forfeits: the room stops listening to them and plays their
+//! remaining frames with [`FORFEIT_INPUTS`], relaying those to everyone so
+//! that their game tops out the same way on every side.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fs::OpenOptions;
+use std::io::{BufReader, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::garbage::HoleStrategy;
+use crate::input::Input;
+use crate::net::{Match, handshake, ruleset};
+use crate::protocol::Message;
+use crate::ruleset::Mode;
+
+/// The inputs a forfeiting player is played with, repeated until their game
+/// tops out: hard dropping every other frame stacks pieces up quickly.
+pub const FORFEIT_INPUTS: [Input; 2] = [Input::HARD_DROP, Input::NONE];
+
+/// The options a server runs with.
+pub struct ServerConfig {
+    /// The address to listen on.
+    pub addr: String,
+    /// Where the holes of garbage go in every match.
+    pub garbage: HoleStrategy,
+    /// How many players play in every match. Two players play versus
+    /// matches, more play royale matches.
+    pub players: usize,
+    /// A CSV file every match result is appended to, as
+    /// `room,players,winner,frames,reason` with the players separated by `;`.
+    pub results: Option<PathBuf>,
+}
+
+/// The wins and losses of one client.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Record {
+    pub wins: u32,
+    pub losses: u32,
+}
+
+/// How a match ended.
+#[derive(Debug, Clone)]
+pub enum Outcome {
+    /// The runner-up topped out.
+    TopOut,
+    /// The runner-up left or was caught disagreeing with the server.
+    Forfeit(String),
+}
+
+/// The result of a finished match.
+#[derive(Debug, Clone)]
+pub struct MatchResult {
+    pub room: u64,
+    pub players: Vec<String>,
+    pub winner: usize,
+    pub frames: u64,
+    pub outcome: Outcome,
+}
+
+/// The results of every match played on a server.
+#[derive(Default)]
+pub struct Results {
+    pub records: BTreeMap<String, Record>,
+    pub matches: Vec<MatchResult>,
+    path: Option<PathBuf>,
+}
+
+impl Results {
+    /// Records a finished match, appending it to the results file if any.
+    pub fn record(&mut self, result: MatchResult) {
+        let winner = &result.players[result.winner];
+        for (player, name) in result.players.iter().enumerate() {
+            let record = self.records.entry(name.clone()).or_default();
+            if player == result.winner {
+                record.wins += 1;
+            } else {
+                record.losses += 1;
+            }
+        }
+        if let Some(path) = &self.path {
+            let reason = match &result.outcome {
+                Outcome::TopOut => "top out".to_string(),
+                Outcome::Forfeit(reason) => format!("forfeit: {}", reason),
+            };
+            let line = format!(
+                "{},{},{},{},{}\n",
+                result.room,
+                result.players.join(";"),
+                winner,
+                result.frames,
+                reason
+            );
+            let written = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut file| file.write_all(line.as_bytes()));
+            if let Err(err) = written {
+                eprintln!("Could not write results to {}: {}", path.display(), err);
+            }
+        }
+        self.matches.push(result);
+    }
+}
+
+/// A client that has completed the handshake.
+struct Client {
+    stream: TcpStream,
+    name: String,
+}
+
+/// Runs a server, accepting clients until the process is stopped.
+///
+/// A connection that fails to be accepted is logged and skipped.
+///
+/// # Arguments
+///
+/// * `config` - The options to run with
+///
+/// # Returns
+///
+/// A description of the error if the server could not listen.
+pub fn run(config: ServerConfig) -> Result<(), String> {
+    let listener = TcpListener::bind(&config.addr).map_err(|err| err.to_string())?;
+    println!("Listening on {}", config.addr);
+    let results = Arc::new(Mutex::new(Results {
+        path: config.results,
+        ..Results::default()
+    }));
+    let (waiting, queue) = mpsc::channel();
+    let (garbage, players) = (config.garbage, config.players.clamp(2, u8::MAX as usize));
+    thread::spawn(move || matchmake(queue, garbage, players, results));
+
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                eprintln!("Could not accept a connection: {}", err);
+                continue;
+            }
+        };
+        let waiting: Sender<Client> = waiting.clone();
+        thread::spawn(move || {
+            let name = stream
+                .peer_addr()
+                .map_or("unknown".to_string(), |addr| addr.to_string());
+            match handshake(&mut stream) {
+                Ok(()) => {
+                    println!("{} is waiting for a match", name);
+                    let _ = waiting.send(Client { stream, name });
+                }
+                Err(err) => eprintln!("Handshake with {} failed: {}", name, err),
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Groups waiting clients in arrival order and starts a room for each group.
+fn matchmake(
+    queue: Receiver<Client>,
+    garbage: HoleStrategy,
+    players: usize,
+    results: Arc<Mutex<Results>>,
+) {
+    let mut rooms = 0;
+    loop {
+        let clients: Vec<Client> = queue.iter().take(players).collect();
+        if clients.len() < players {
+            return;
+        }
+        rooms += 1;
+        let room = rooms;
+        let results = Arc::clone(&results);
+        thread::spawn(move || {
+            let Some(result) = Room::new(room, clients, garbage).and_then(Room::run) else {
+                println!("Room {} was abandoned before it started", room);
+                return;
+            };
+            let mut results = results.lock().unwrap_or_else(|err| err.into_inner());
+            println!(
+                "Room {}: {} won after {} frames ({:?})",
+                room, result.players[result.winner], result.frames, result.outcome
+            );
+            results.record(result);
+            for (name, record) in &results.records {
+                println!("  {:<24} {:>3} W {:>3} L", name, record.wins, record.losses);
+            }
+        });
+    }
+}
+
+/// A match being played between clients.
+struct Room {
+    id: u64,
+    streams: Vec<TcpStream>,
+    names: Vec<String>,
+    messages: Receiver<(usize, Result<Message, String>)>,
+    game: Match,
+    inputs: Vec<VecDeque<Input>>,
+    received_frames: Vec<u64>,
+    simulated_frames: u64,
+    // Garbage reported by each client and garbage the simulation saw them
+    // send, as (frame, lines), not matched against each other yet
+    reported: Vec<VecDeque<(u64, u32)>>,
+    simulated: Vec<VecDeque<(u64, u32)>>,
+    /// Why each player forfeited, if they did.
+    forfeits: Vec<Option<String>>,
+}
+
+impl Room {
+    /// Sets up a room and tells every client the rules of the match.
+    ///
+    /// # Returns
+    ///
+    /// The room, or `None` if a client could not be reached.
+    fn new(id: u64, clients: Vec<Client>, garbage: HoleStrategy) -> Option<Self> {
+        let seed = fastrand::u64(..);
+        let players = clients.len();
+        let mode = if players > 2 {
+            Mode::Royale
+        } else {
+            Mode::Versus
+        };
+        let (sender, messages) = mpsc::channel();
+        let mut streams = Vec::new();
+        let mut names = Vec::new();
+        for (player, client) in clients.into_iter().enumerate() {
+            let mut stream = client.stream;
+            Message::Setup {
+                player: player as u8,
+                players: players as u8,
+                seed,
+                mode,
+                garbage,
+            }
+            .write_to(&mut stream)
+            .ok()?;
+            stream.set_nodelay(true).ok()?;
+            let mut reader = BufReader::new(stream.try_clone().ok()?);
+            let sender = sender.clone();
+            thread::spawn(move || {
+                loop {
+                    let message = Message::read_from(&mut reader);
+                    let failed = message.is_err();
+                    if sender.send((player, message)).is_err() || failed {
+                        break;
+                    }
+                }
+            });
+            streams.push(stream);
+            names.push(client.name);
+        }
+        Some(Self {
+            id,
+            streams,
+            names,
+            messages,
+            game: Match::new(ruleset(mode, garbage), seed, players),
+            inputs: vec![VecDeque::new(); players],
+            received_frames: vec![0; players],
+            simulated_frames: 0,
+            reported: vec![VecDeque::new(); players],
+            simulated: vec![VecDeque::new(); players],
+            forfeits: vec![None; players],
+        })
+    }
+
+    /// Plays the match to the end.
+    ///
+    /// # Returns
+    ///
+    /// The result, or `None` if a client left before the first frame.
+    fn run(mut self) -> Option<MatchResult> {
+        let winner = loop {
+            let Ok((player, message)) = self.messages.recv() else {
+                return None;
+            };
+            if self.forfeits[player].is_some() {
+                continue;
+            }
+            match message {
+                Err(_) if self.simulated_frames == 0 => return None,
+                Err(err) => self.forfeit(player, err),
+                Ok(Message::Input {
+                    player: sender,
+                    frame,
+                    input,
+                }) if sender as usize == player && frame == self.received_frames[player] => {
+                    self.received_frames[player] += 1;
+                    self.inputs[player].push_back(input);
+                    self.relay(player, frame, input);
+                }
+                Ok(Message::GarbageSent {
+                    player: sender,
+                    frame,
+                    lines,
+                }) if sender as usize == player => {
+                    self.reported[player].push_back((frame, lines));
+                }
+                // Clients announce the winner they saw, but the server's own
+                // simulation decides
+                Ok(Message::GameOver { .. }) => {}
+                Ok(other) => self.forfeit(player, format!("unexpected message {:?}", other)),
+            }
+            if let Some(cheater) = self.mismatched_garbage() {
+                let reason = "garbage reports disagree with the server".to_string();
+                self.forfeit(cheater, reason);
+            }
+            if let Some(winner) = self.simulate() {
+                break winner;
+            }
+        };
+        for player in 0..self.streams.len() {
+            self.send(
+                player,
+                Message::GameOver {
+                    winner: winner as u8,
+                },
+            );
+        }
+        let outcome = self
+            .game
+            .runner_up()
+            .and_then(|player| self.forfeits[player].clone())
+            .map_or(Outcome::TopOut, Outcome::Forfeit);
+        Some(MatchResult {
+            room: self.id,
+            players: self.names.clone(),
+            winner,
+            frames: self.simulated_frames,
+            outcome,
+        })
+    }
+
+    fn send(&mut self, player: usize, message: Message) {
+        // A client that cannot be reached any more is noticed by its reader
+        let _ = message.write_to(&mut self.streams[player]);
+    }
+
+    /// Sends a player's input to every other player.
+    fn relay(&mut self, player: usize, frame: u64, input: Input) {
+        for other in 0..self.streams.len() {
+            if other != player {
+                self.send(
+                    other,
+                    Message::Input {
+                        player: player as u8,
+                        frame,
+                        input,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Stops listening to a player, whose remaining frames are played with
+    /// [`FORFEIT_INPUTS`] from now on.
+    fn forfeit(&mut self, player: usize, reason: String) {
+        println!(
+            "Room {}: {} forfeits ({})",
+            self.id, self.names[player], reason
+        );
+        let _ = self.streams[player].shutdown(Shutdown::Both);
+        self.forfeits[player] = Some(reason);
+    }
+
+    /// Simulates every frame for which all inputs are known, relaying the
+    /// garbage each player sends to everyone else.
+    ///
+    /// # Returns
+    ///
+    /// The winner, once the match is over.
+    fn simulate(&mut self) -> Option<usize> {
+        loop {
+            if self.game.winner().is_some() {
+                return self.game.winner();
+            }
+            let players = self.inputs.len();
+            for player in 0..players {
+                if self.forfeits[player].is_some() && self.inputs[player].is_empty() {
+                    let frame = self.received_frames[player];
+                    let input = FORFEIT_INPUTS[frame as usize % FORFEIT_INPUTS.len()];
+                    self.received_frames[player] += 1;
+                    self.inputs[player].push_back(input);
+                    self.relay(player, frame, input);
+                }
+            }
+            if self.inputs.iter().any(|inputs| inputs.is_empty()) {
+                return None;
+            }
+            let inputs: Vec<Input> = self
+                .inputs
+                .iter_mut()
+                .map(|inputs| inputs.pop_front().unwrap_or_default())
+                .collect();
+            let sent = self.game.sent().to_vec();
+            self.game.update(&inputs);
+            let frame = self.simulated_frames;
+            self.simulated_frames += 1;
+            for (player, sent) in sent.into_iter().enumerate() {
+                let lines = self.game.sent()[player] - sent;
+                if lines == 0 {
+                    continue;
+                }
+                self.simulated[player].push_back((frame, lines));
+                let message = Message::GarbageSent {
+                    player: player as u8,
+                    frame,
+                    lines,
+                };
+                for other in (0..players).filter(|&other| other != player) {
+                    self.send(other, message);
+                }
+            }
+        }
+    }
+
+    /// Returns a player whose garbage reports disagree with the simulation.
+    fn mismatched_garbage(&mut self) -> Option<usize> {
+        (0..self.reported.len()).find(|&player| {
+            let (reported, simulated) = (&mut self.reported[player], &mut self.simulated[player]);
+            while let (Some(report), Some(expected)) = (reported.front(), simulated.front()) {
+                if report != expected {
+                    return true;
+                }
+                reported.pop_front();
+                simulated.pop_front();
+            }
+            false
+        })
+    }
+}
+
+impl Drop for Room {
+    fn drop(&mut self) {
+        // Unblocks the reader threads
+        for stream in &self.streams {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+}
@@ -0,0 +1,288 @@
+//! Settings module: the screen where players change their [`Config`]
+//! without editing the file.
+//!
+//! Every row is either a key binding, rebound by selecting it and pressing
+//! the new key, or an option changed with Left and Right. Changes apply at
+//! once and are written back to the config file when the screen closes.
+
+use macroquad::prelude::*;
+
+use crate::config::{Bindings, Config};
+use crate::controls::{ACTIONS, key_name};
+use crate::renderer::Theme;
+use crate::ruleset::FRAME_RATE;
+
+/// The players whose keys can be rebound, with the name shown for them.
+const PLAYERS: [&str; 3] = ["Solo", "Versus P1", "Versus P2"];
+
+/// The longest delay the DAS slider reaches, in frames.
+const MAX_DAS: u32 = 30;
+
+/// The longest delay the ARR slider reaches, in frames.
+const MAX_ARR: u32 = 10;
+
+/// The fastest soft drop factor the SDF slider reaches.
+const MAX_SDF: u32 = 40;
+
+/// The most previews that can be shown.
+const MAX_PREVIEW: usize = 5;
+
+/// One line of the settings screen.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Row {
+    /// Whose keys the action rows show.
+    Player,
+    /// The keys of one of [`ACTIONS`].
+    Action(usize),
+    Das,
+    Arr,
+    Sdf,
+    Preview,
+    Ghost,
+    Grid,
+    Theme,
+    Volume,
+}
+
+const ROWS: [Row; 17] = [
+    Row::Player,
+    Row::Action(0),
+    Row::Action(1),
+    Row::Action(2),
+    Row::Action(3),
+    Row::Action(4),
+    Row::Action(5),
+    Row::Action(6),
+    Row::Action(7),
+    Row::Das,
+    Row::Arr,
+    Row::Sdf,
+    Row::Preview,
+    Row::Ghost,
+    Row::Grid,
+    Row::Theme,
+    Row::Volume,
+];
+
+/// What happened to the settings on a frame.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// Nothing changed.
+    None,
+    /// The config changed and should be applied.
+    Changed,
+    /// The player left the screen, and the config should be saved.
+    Closed,
+}
+
+/// The state of the settings screen.
+#[derive(Debug, Clone, Default)]
+pub struct Settings {
+    selected: usize,
+    /// The index of the player in [`PLAYERS`] whose keys are shown.
+    player: usize,
+    /// Whether the next key pressed is bound to the selected action.
+    capturing: bool,
+}
+
+impl Settings {
+    /// Opens the screen on its first row.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handles the keys pressed this frame.
+    ///
+    /// Up and Down pick a row, Left and Right change an option, Enter
+    /// listens for a key to bind to an action and Backspace unbinds all of
+    /// its keys. A key bound to an action is taken off the player's other
+    /// actions. Escape cancels listening, or closes the screen.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The config being edited
+    pub fn update(&mut self, config: &mut Config) -> Outcome {
+        let row = ROWS[self.selected];
+        if self.capturing {
+            let Some(key) = get_last_key_pressed() else {
+                return Outcome::None;
+            };
+            self.capturing = false;
+            let (Row::Action(action), false) = (row, key == KeyCode::Escape) else {
+                return Outcome::None;
+            };
+            let bindings = self.bindings(config);
+            let name = key_name(key);
+            for (input, _) in ACTIONS {
+                bindings.keys_mut(input).retain(|bound| *bound != name);
+            }
+            bindings.keys_mut(ACTIONS[action].0).push(name);
+            return Outcome::Changed;
+        }
+
+        if is_key_pressed(KeyCode::Escape) {
+            return Outcome::Closed;
+        }
+        if is_key_pressed(KeyCode::Up) {
+            self.selected = (self.selected + ROWS.len() - 1) % ROWS.len();
+        }
+        if is_key_pressed(KeyCode::Down) {
+            self.selected = (self.selected + 1) % ROWS.len();
+        }
+        if let Row::Action(action) = row {
+            if is_key_pressed(KeyCode::Enter) {
+                self.capturing = true;
+            }
+            if is_key_pressed(KeyCode::Backspace) {
+                self.bindings(config).keys_mut(ACTIONS[action].0).clear();
+                return Outcome::Changed;
+            }
+            return Outcome::None;
+        }
+
+        let step: i32 = match (
+            is_key_pressed(KeyCode::Left),
+            is_key_pressed(KeyCode::Right),
+        ) {
+            (true, false) => -1,
+            (false, true) => 1,
+            _ => return Outcome::None,
+        };
+        let handling = &mut config.handling;
+        let visuals = &mut config.visuals;
+        match row {
+            Row::Player => {
+                self.player = cycle(self.player, PLAYERS.len(), step);
+                return Outcome::None;
+            }
+            Row::Das => handling.das = nudge(handling.das, step, 0, MAX_DAS),
+            Row::Arr => handling.arr = nudge(handling.arr, step, 0, MAX_ARR),
+            Row::Sdf => handling.sdf = nudge(handling.sdf, step, 1, MAX_SDF),
+            Row::Preview => {
+                visuals.preview =
+                    nudge(visuals.preview as u32, step, 0, MAX_PREVIEW as u32) as usize
+            }
+            Row::Ghost => visuals.ghost = !visuals.ghost,
+            Row::Grid => visuals.grid = !visuals.grid,
+            Row::Theme => {
+                let index = Theme::ALL.iter().position(|&theme| theme == visuals.theme);
+                let next = cycle(index.unwrap_or(0), Theme::ALL.len(), step);
+                visuals.theme = Theme::ALL[next];
+            }
+            Row::Volume => {
+                let tenths = (config.volume * 10.0).round() as u32;
+                config.volume = nudge(tenths, step, 0, 10) as f32 / 10.0;
+            }
+            Row::Action(_) => {}
+        }
+        Outcome::Changed
+    }
+
+    /// Draws the screen.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The config being edited
+    pub fn draw(&self, config: &Config) {
+        clear_background(BLACK);
+        draw_text("SETTINGS", 150.0, 60.0, 50.0, WHITE);
+        let bindings = match self.player {
+            0 => &config.keys,
+            1 => &config.versus_left,
+            _ => &config.versus_right,
+        };
+        let handling = &config.handling;
+        let visuals = &config.visuals;
+        for (i, &row) in ROWS.iter().enumerate() {
+            let y = 110.0 + i as f32 * 36.0;
+            let color = if i == self.selected { YELLOW } else { WHITE };
+            let (label, value) = match row {
+                Row::Player => (
+                    "Keys of".to_string(),
+                    format!("< {} >", PLAYERS[self.player]),
+                ),
+                Row::Action(action) => {
+                    let (input, name) = ACTIONS[action];
+                    let keys = bindings.keys(input);
+                    let value = if self.capturing && i == self.selected {
+                        "Press a key...".to_string()
+                    } else if keys.is_empty() {
+                        "-".to_string()
+                    } else {
+                        keys.join(", ")
+                    };
+                    (format!("  {}", name.replace('_', " ")), value)
+                }
+                Row::Das => {
+                    draw_slider(handling.das, 0, MAX_DAS, y, color);
+                    ("DAS".to_string(), frames(handling.das))
+                }
+                Row::Arr => {
+                    draw_slider(handling.arr, 0, MAX_ARR, y, color);
+                    ("ARR".to_string(), frames(handling.arr))
+                }
+                Row::Sdf => {
+                    draw_slider(handling.sdf, 1, MAX_SDF, y, color);
+                    ("SDF".to_string(), format!("{}x", handling.sdf))
+                }
+                Row::Preview => {
+                    let preview = visuals.preview as u32;
+                    draw_slider(preview, 0, MAX_PREVIEW as u32, y, color);
+                    ("Previews".to_string(), preview.to_string())
+                }
+                Row::Ghost => ("Ghost piece".to_string(), on_off(visuals.ghost)),
+                Row::Grid => ("Grid".to_string(), on_off(visuals.grid)),
+                Row::Theme => ("Theme".to_string(), format!("< {} >", visuals.theme.name())),
+                Row::Volume => {
+                    let tenths = (config.volume * 10.0).round() as u32;
+                    draw_slider(tenths, 0, 10, y, color);
+                    ("Volume".to_string(), format!("{}%", tenths * 10))
+                }
+            };
+            draw_text(&label, 40.0, y, 25.0, color);
+            draw_text(&value, 200.0, y, 25.0, color);
+        }
+        let hint = if self.capturing {
+            "Press the key to bind   Escape: cancel"
+        } else if matches!(ROWS[self.selected], Row::Action(_)) {
+            "Enter: add key   Backspace: clear   Escape: save and back"
+        } else {
+            "Left/Right: change   Escape: save and back"
+        };
+        draw_text(hint, 40.0, 760.0, 20.0, GRAY);
+    }
+
+    fn bindings<'a>(&self, config: &'a mut Config) -> &'a mut Bindings {
+        match self.player {
+            0 => &mut config.keys,
+            1 => &mut config.versus_left,
+            _ => &mut config.versus_right,
+        }
+    }
+}
+
+/// Draws a slider to the right of a row's value.
+fn draw_slider(value: u32, min: u32, max: u32, y: f32, color: Color) {
+    let (x, width) = (380.0, 180.0);
+    let filled = (value.clamp(min, max) - min) as f32 / (max - min) as f32 * width;
+    draw_rectangle_lines(x, y - 14.0, width, 14.0, 1.0, GRAY);
+    draw_rectangle(x, y - 14.0, filled, 14.0, color);
+}
+
+/// Returns a delay in frames and milliseconds, e.g. `"10 f (167 ms)"`.
+fn frames(frames: u32) -> String {
+    let millis = (f64::from(frames) * 1000.0 / FRAME_RATE).round();
+    format!("{} f ({} ms)", frames, millis)
+}
+
+fn on_off(on: bool) -> String {
+    if on { "On" } else { "Off" }.to_string()
+}
+
+fn cycle(index: usize, len: usize, step: i32) -> usize {
+    (index as i32 + step).rem_euclid(len as i32) as usize
+}
+
+fn nudge(value: u32, step: i32, min: u32, max: u32) -> u32 {
+    value.saturating_add_signed(step).clamp(min, max)
+}
@@ -0,0 +1,595 @@
+//! Spectate module streaming running games to read-only viewers.
+//!
+//! A [`Broadcaster`] is fed the games on screen every frame and streams
+//! them to every attached [`Spectator`], after a delay so that viewers
+//! cannot relay what they see back to the players in time to matter. A
+//! spectator first receives a snapshot of every board, then only the
+//! [`Event`]s describing what changed since. A co-op game is streamed as
+//! one wide board with both players' falling pieces.
+//!
+//! Every event is a tag byte followed by its fields in big-endian order:
+//!
+//! | Tag | Event   | Fields                                                  |
+//! |-----|---------|---------------------------------------------------------|
+//! | 0   | `Reset` | boards `u8`, then per board width `u8`                  |
+//! | 1   | `Rows`  | board `u8`, count `u8`, then per row y `u8`, cells `u32`|
+//! | 2   | `Piece` | board `u8`, count `u8`, then per piece shape, rotation  |
+//! |     |         | system, rotation `u8`, x `i8`, y `i8`, scale `u8`       |
+//! | 3   | `Stats` | board `u8`, score, lines, level `u32`, over `u8`        |
+//! | 4   | `Queue` | board `u8`, hold `u8` (`255` for none), count `u8`,     |
+//! |     |         | next shapes `u8`                                        |
+
+use std::collections::VecDeque;
+use std::io::{BufReader, Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::board::{BOARD_HEIGHT, BOARD_WIDTH, Board, MAX_BOARD_WIDTH};
+use crate::coop::Coop;
+use crate::game::{Game, GameState, match_shape};
+use crate::protocol::read_array;
+use crate::tetromino::{RotationSystem, Shape, Tetromino};
+
+/// How long spectators are kept behind the players by default.
+pub const DEFAULT_DELAY: Duration = Duration::from_secs(2);
+
+/// The marker for an empty hold slot.
+const NO_HOLD: u8 = 255;
+
+/// A falling piece of a board, as seen by spectators.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PieceView {
+    pub shape: Shape,
+    pub rotation_system: RotationSystem,
+    pub rotation: u8,
+    pub pos: (i32, i32),
+    pub scale: i32,
+}
+
+impl PieceView {
+    /// Captures what spectators see of a falling piece.
+    pub fn of(piece: &Tetromino) -> Self {
+        Self {
+            shape: piece.shape,
+            rotation_system: piece.rotation_system,
+            rotation: piece.rotation,
+            pos: piece.pos,
+            scale: piece.scale,
+        }
+    }
+}
+
+/// Everything spectators see of one board.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoardView {
+    /// The number of columns of the board.
+    pub width: usize,
+    /// The locked cells, columns past the width always empty.
+    pub grid: [[bool; MAX_BOARD_WIDTH]; BOARD_HEIGHT],
+    /// The falling pieces, one per player on the board.
+    pub pieces: Vec<PieceView>,
+    pub score: u32,
+    pub lines: u32,
+    pub level: u32,
+    pub over: bool,
+    pub hold: Option<Shape>,
+    pub next: Vec<Shape>,
+}
+
+impl Default for BoardView {
+    fn default() -> Self {
+        Self::empty(BOARD_WIDTH)
+    }
+}
+
+impl BoardView {
+    /// Returns an empty view of a board `width` columns wide, clamped like
+    /// [`Board::with_width`].
+    pub fn empty(width: usize) -> Self {
+        Self {
+            width: width.clamp(BOARD_WIDTH, MAX_BOARD_WIDTH),
+            grid: [[false; MAX_BOARD_WIDTH]; BOARD_HEIGHT],
+            pieces: Vec::new(),
+            score: 0,
+            lines: 0,
+            level: 0,
+            over: false,
+            hold: None,
+            next: Vec::new(),
+        }
+    }
+
+    /// Captures what spectators see of a game.
+    pub fn of(game: &Game) -> Self {
+        Self {
+            width: game.board.width(),
+            grid: game.board.grid(),
+            pieces: game.current_piece.iter().map(PieceView::of).collect(),
+            score: game.score,
+            lines: game.lines,
+            level: game.level,
+            over: !matches!(game.state, GameState::Playing),
+            hold: game.hold,
+            next: game
+                .next
+                .iter()
+                .take(game.ruleset.preview)
+                .copied()
+                .collect(),
+        }
+    }
+
+    /// Captures what spectators see of a co-op game: the shared board with
+    /// both players' falling pieces, and the shared score. Neither player's
+    /// hold piece nor upcoming pieces are shown.
+    pub fn of_coop(coop: &Coop) -> Self {
+        Self {
+            width: coop.board.width(),
+            grid: coop.board.grid(),
+            pieces: coop
+                .players
+                .iter()
+                .filter_map(|game| game.current_piece.as_ref())
+                .map(PieceView::of)
+                .collect(),
+            score: coop.players[0].score,
+            lines: coop.players[0].lines,
+            level: coop.players[0].level,
+            over: coop.is_over(),
+            hold: None,
+            next: Vec::new(),
+        }
+    }
+
+    /// Returns the locked cells as a board.
+    pub fn board(&self) -> Board {
+        let mut board = Board::with_width(self.width);
+        for (y, cells) in self.grid.iter().enumerate() {
+            board.set_row(y, bits(cells));
+        }
+        board
+    }
+
+    /// Returns the falling pieces.
+    pub fn tetrominoes(&self) -> Vec<Tetromino> {
+        self.pieces
+            .iter()
+            .map(|view| {
+                let mut piece =
+                    Tetromino::with_rotation_system(view.pos, view.shape, view.rotation_system);
+                piece.rotation = view.rotation;
+                piece.scale = view.scale;
+                piece
+            })
+            .collect()
+    }
+
+    /// Returns the events that turn `old` into this view.
+    ///
+    /// # Arguments
+    ///
+    /// * `board` - The index of the board in the broadcast
+    /// * `old` - The view spectators currently have
+    fn events(&self, board: u8, old: &BoardView) -> Vec<Event> {
+        let mut events = Vec::new();
+        let rows: Vec<_> = (0..BOARD_HEIGHT)
+            .filter(|&y| self.grid[y] != old.grid[y])
+            .map(|y| (y as u8, self.grid[y]))
+            .collect();
+        if !rows.is_empty() {
+            events.push(Event::Rows { board, rows });
+        }
+        if self.pieces != old.pieces {
+            events.push(Event::Piece {
+                board,
+                pieces: self.pieces.clone(),
+            });
+        }
+        let stats = (self.score, self.lines, self.level, self.over);
+        if stats != (old.score, old.lines, old.level, old.over) {
+            events.push(Event::Stats {
+                board,
+                score: self.score,
+                lines: self.lines,
+                level: self.level,
+                over: self.over,
+            });
+        }
+        if (self.hold, &self.next) != (old.hold, &old.next) {
+            events.push(Event::Queue {
+                board,
+                hold: self.hold,
+                next: self.next.clone(),
+            });
+        }
+        events
+    }
+
+    fn apply(&mut self, event: Event) {
+        match event {
+            Event::Reset { .. } => {}
+            Event::Rows { rows, .. } => {
+                for (y, row) in rows {
+                    if let Some(cells) = self.grid.get_mut(y as usize) {
+                        *cells = row;
+                    }
+                }
+            }
+            Event::Piece { pieces, .. } => self.pieces = pieces,
+            Event::Stats {
+                score,
+                lines,
+                level,
+                over,
+                ..
+            } => {
+                self.score = score;
+                self.lines = lines;
+                self.level = level;
+                self.over = over;
+            }
+            Event::Queue { hold, next, .. } => {
+                self.hold = hold;
+                self.next = next;
+            }
+        }
+    }
+}
+
+/// Returns the cells of a row as a bitmask, bit `x` set when column `x` is
+/// occupied.
+fn bits(cells: &[bool; MAX_BOARD_WIDTH]) -> u32 {
+    (0..MAX_BOARD_WIDTH)
+        .filter(|&x| cells[x])
+        .fold(0, |bits, x| bits | 1 << x)
+}
+
+/// Returns the widths of boards, as sent in a [`Event::Reset`].
+fn widths(views: &[BoardView]) -> Vec<u8> {
+    views.iter().map(|view| view.width as u8).collect()
+}
+
+/// A change streamed to spectators.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// The boards changed, given by their widths; every board starts empty.
+    Reset { widths: Vec<u8> },
+    /// Rows of a board changed, as `(y, cells)`.
+    Rows {
+        board: u8,
+        rows: Vec<(u8, [bool; MAX_BOARD_WIDTH])>,
+    },
+    /// The falling pieces of a board changed.
+    Piece { board: u8, pieces: Vec<PieceView> },
+    /// The score or state of a board changed.
+    Stats {
+        board: u8,
+        score: u32,
+        lines: u32,
+        level: u32,
+        over: bool,
+    },
+    /// The hold piece or upcoming pieces of a board changed.
+    Queue {
+        board: u8,
+        hold: Option<Shape>,
+        next: Vec<Shape>,
+    },
+}
+
+impl Event {
+    /// Returns the board an event applies to, `None` for a reset.
+    fn board(&self) -> Option<usize> {
+        match self {
+            Event::Reset { .. } => None,
+            Event::Rows { board, .. }
+            | Event::Piece { board, .. }
+            | Event::Stats { board, .. }
+            | Event::Queue { board, .. } => Some(*board as usize),
+        }
+    }
+
+    /// Appends the encoded event to a buffer.
+    fn encode(&self, bytes: &mut Vec<u8>) {
+        match self {
+            Event::Reset { widths } => {
+                bytes.extend([0, widths.len() as u8]);
+                bytes.extend(widths);
+            }
+            Event::Rows { board, rows } => {
+                bytes.extend([1, *board, rows.len() as u8]);
+                for (y, cells) in rows {
+                    bytes.push(*y);
+                    bytes.extend(bits(cells).to_be_bytes());
+                }
+            }
+            Event::Piece { board, pieces } => {
+                bytes.extend([2, *board, pieces.len() as u8]);
+                for piece in pieces {
+                    bytes.extend([
+                        piece.shape as u8,
+                        piece.rotation_system as u8,
+                        piece.rotation,
+                        piece.pos.0 as i8 as u8,
+                        piece.pos.1 as i8 as u8,
+                        piece.scale as u8,
+                    ]);
+                }
+            }
+            Event::Stats {
+                board,
+                score,
+                lines,
+                level,
+                over,
+            } => {
+                bytes.extend([3, *board]);
+                bytes.extend(score.to_be_bytes());
+                bytes.extend(lines.to_be_bytes());
+                bytes.extend(level.to_be_bytes());
+                bytes.push(u8::from(*over));
+            }
+            Event::Queue { board, hold, next } => {
+                let hold = hold.map_or(NO_HOLD, |shape| shape as u8);
+                bytes.extend([4, *board, hold, next.len() as u8]);
+                bytes.extend(next.iter().map(|&shape| shape as u8));
+            }
+        }
+    }
+
+    /// Reads the next event from a stream, blocking until it has arrived.
+    fn read_from(reader: &mut impl Read) -> Result<Self, String> {
+        let [tag] = read_array(reader)?;
+        let read_u32 = |reader: &mut _| read_array(reader).map(u32::from_be_bytes);
+        match tag {
+            0 => {
+                let [boards] = read_array(reader)?;
+                let widths = (0..boards)
+                    .map(|_| read_array(reader).map(|[width]| width))
+                    .collect::<Result<_, String>>()?;
+                Ok(Event::Reset { widths })
+            }
+            1 => {
+                let [board, count] = read_array(reader)?;
+                let rows = (0..count)
+                    .map(|_| {
+                        let [y] = read_array(reader)?;
+                        let bits = read_u32(reader)?;
+                        Ok((y, std::array::from_fn(|x| bits & 1 << x != 0)))
+                    })
+                    .collect::<Result<_, String>>()?;
+                Ok(Event::Rows { board, rows })
+            }
+            2 => {
+                let [board, count] = read_array(reader)?;
+                let mut pieces = Vec::new();
+                for _ in 0..count {
+                    let [shape, system, rotation, x, y, scale] = read_array(reader)?;
+                    let rotation_system = match system {
+                        0 => RotationSystem::Classic,
+                        1 => RotationSystem::Ars,
+                        2 => RotationSystem::Srs,
+                        _ => return Err(format!("Unknown rotation system {}", system)),
+                    };
+                    pieces.push(PieceView {
+                        shape: match_shape(shape),
+                        rotation_system,
+                        rotation,
+                        pos: (x as i8 as i32, y as i8 as i32),
+                        scale: scale as i32,
+                    });
+                }
+                Ok(Event::Piece { board, pieces })
+            }
+            3 => {
+                let [board] = read_array(reader)?;
+                let score = read_u32(reader)?;
+                let lines = read_u32(reader)?;
+                let level = read_u32(reader)?;
+                let [over] = read_array(reader)?;
+                Ok(Event::Stats {
+                    board,
+                    score,
+                    lines,
+                    level,
+                    over: over != 0,
+                })
+            }
+            4 => {
+                let [board, hold, count] = read_array(reader)?;
+                let mut next = Vec::new();
+                for _ in 0..count {
+                    let [shape] = read_array(reader)?;
+                    next.push(match_shape(shape));
+                }
+                let hold = (hold != NO_HOLD).then(|| match_shape(hold));
+                Ok(Event::Queue { board, hold, next })
+            }
+            _ => Err(format!("Unknown event tag {}", tag)),
+        }
+    }
+}
+
+/// Streams the games on screen to attached spectators.
+pub struct Broadcaster {
+    delay: Duration,
+    /// The views published but not shown to spectators yet, oldest first.
+    pending: VecDeque<(Instant, Vec<BoardView>)>,
+    /// What spectators currently see.
+    shown: Vec<BoardView>,
+    joining: Receiver<TcpStream>,
+    spectators: Vec<Sender<Vec<u8>>>,
+}
+
+impl Broadcaster {
+    /// Starts accepting spectators.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The address to listen on, e.g. `0.0.0.0:7879`
+    /// * `delay` - How long spectators are kept behind the players
+    ///
+    /// # Returns
+    ///
+    /// The broadcaster, or a description of what went wrong.
+    pub fn start(addr: &str, delay: Duration) -> Result<Self, String> {
+        let listener = TcpListener::bind(addr).map_err(|err| err.to_string())?;
+        let (sender, joining) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if sender.send(stream).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Self {
+            delay,
+            pending: VecDeque::new(),
+            shown: Vec::new(),
+            joining,
+            spectators: Vec::new(),
+        })
+    }
+
+    /// Publishes the boards currently on screen.
+    ///
+    /// Spectators see them once the delay has passed.
+    ///
+    /// # Arguments
+    ///
+    /// * `views` - The boards on screen, see [`BoardView::of`] and
+    ///   [`BoardView::of_coop`], empty when none is being played
+    pub fn publish(&mut self, views: Vec<BoardView>) {
+        let now = Instant::now();
+        self.pending.push_back((now, views));
+
+        let mut bytes = Vec::new();
+        while let Some((published, _)) = self.pending.front()
+            && now.duration_since(*published) >= self.delay
+        {
+            let Some((_, views)) = self.pending.pop_front() else {
+                break;
+            };
+            if widths(&views) != widths(&self.shown) {
+                self.shown = views
+                    .iter()
+                    .map(|view| BoardView::empty(view.width))
+                    .collect();
+                Event::Reset {
+                    widths: widths(&views),
+                }
+                .encode(&mut bytes);
+            }
+            for (board, (view, shown)) in views.iter().zip(&self.shown).enumerate() {
+                for event in view.events(board as u8, shown) {
+                    event.encode(&mut bytes);
+                }
+            }
+            self.shown = views;
+        }
+        if !bytes.is_empty() {
+            self.spectators
+                .retain(|spectator| spectator.send(bytes.clone()).is_ok());
+        }
+
+        while let Ok(stream) = self.joining.try_recv() {
+            self.attach(stream);
+        }
+    }
+
+    /// Starts streaming to a new spectator, beginning with a snapshot of
+    /// what the others currently see.
+    fn attach(&mut self, mut stream: TcpStream) {
+        let _ = stream.set_nodelay(true);
+        let mut snapshot = Vec::new();
+        Event::Reset {
+            widths: widths(&self.shown),
+        }
+        .encode(&mut snapshot);
+        for (board, view) in self.shown.iter().enumerate() {
+            for event in view.events(board as u8, &BoardView::empty(view.width)) {
+                event.encode(&mut snapshot);
+            }
+        }
+        // Every spectator gets its own writer so a slow one never blocks the game
+        let (sender, bytes) = mpsc::channel::<Vec<u8>>();
+        let _ = sender.send(snapshot);
+        thread::spawn(move || {
+            for chunk in bytes {
+                if stream.write_all(&chunk).is_err() {
+                    break;
+                }
+            }
+        });
+        self.spectators.push(sender);
+    }
+}
+
+/// A read-only view of a broadcast.
+pub struct Spectator {
+    /// The boards of the broadcast, as currently shown.
+    pub views: Vec<BoardView>,
+    /// Set once the connection fails.
+    pub error: Option<String>,
+    stream: TcpStream,
+    events: Receiver<Result<Event, String>>,
+}
+
+impl Spectator {
+    /// Attaches to a broadcast.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The address of the broadcaster, e.g. `127.0.0.1:7879`
+    pub fn connect(addr: &str) -> Result<Self, String> {
+        let stream = TcpStream::connect(addr).map_err(|err| err.to_string())?;
+        let mut reader = BufReader::new(stream.try_clone().map_err(|err| err.to_string())?);
+        let (sender, events) = mpsc::channel();
+        thread::spawn(move || {
+            loop {
+                let event = Event::read_from(&mut reader);
+                let failed = event.is_err();
+                if sender.send(event).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        Ok(Self {
+            views: Vec::new(),
+            error: None,
+            stream,
+            events,
+        })
+    }
+
+    /// Applies every event received since the last update.
+    pub fn update(&mut self) {
+        loop {
+            match self.events.try_recv() {
+                Ok(Ok(Event::Reset { widths })) => {
+                    self.views = widths
+                        .into_iter()
+                        .map(|width| BoardView::empty(usize::from(width)))
+                        .collect();
+                }
+                Ok(Ok(event)) => {
+                    if let Some(view) = event.board().and_then(|board| self.views.get_mut(board)) {
+                        view.apply(event);
+                    }
+                }
+                Ok(Err(err)) => self.error = Some(err),
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => return,
+            }
+        }
+    }
+}
+
+impl Drop for Spectator {
+    fn drop(&mut self) {
+        // Unblocks the reader thread
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
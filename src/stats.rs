@@ -0,0 +1,197 @@
+//! Stats module tracking how a game is played.
+//!
+//! Every [`Game`](crate::game::Game) keeps [`Stats`] of its pieces, key
+//! presses, attack and clears, from which the rates players compare each
+//! other by are worked out: pieces per second, keys per piece and attack
+//! per minute.
+
+use crate::game::{LineClear, TSpin};
+use crate::input::Input;
+use crate::ruleset::FRAME_RATE;
+use crate::versus::attack;
+
+/// The kinds of placement counted by [`Stats`]: line clears by size, and
+/// T-spins with or without lines.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ClearType {
+    Single,
+    Double,
+    Triple,
+    Tetris,
+    MiniTSpin,
+    MiniTSpinSingle,
+    MiniTSpinDouble,
+    TSpin,
+    TSpinSingle,
+    TSpinDouble,
+    TSpinTriple,
+}
+
+impl ClearType {
+    /// Every clear type, in the order they are listed.
+    pub const ALL: [ClearType; 11] = [
+        ClearType::Single,
+        ClearType::Double,
+        ClearType::Triple,
+        ClearType::Tetris,
+        ClearType::MiniTSpin,
+        ClearType::MiniTSpinSingle,
+        ClearType::MiniTSpinDouble,
+        ClearType::TSpin,
+        ClearType::TSpinSingle,
+        ClearType::TSpinDouble,
+        ClearType::TSpinTriple,
+    ];
+
+    /// Returns the clear type of a placement.
+    ///
+    /// # Arguments
+    ///
+    /// * `lines` - The lines the placement cleared
+    /// * `tspin` - The T-spin the placement counted as
+    ///
+    /// # Returns
+    ///
+    /// The clear type, or `None` for a placement clearing nothing without
+    /// a T-spin.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_tetris::game::TSpin;
+    /// # use rust_tetris::stats::ClearType;
+    /// assert_eq!(ClearType::of(2, TSpin::Full), Some(ClearType::TSpinDouble));
+    /// assert_eq!(ClearType::of(0, TSpin::None), None);
+    /// ```
+    pub fn of(lines: u32, tspin: TSpin) -> Option<Self> {
+        let kind = match (tspin, lines) {
+            (TSpin::None, 0) => return None,
+            (TSpin::None, 1) => ClearType::Single,
+            (TSpin::None, 2) => ClearType::Double,
+            (TSpin::None, 3) => ClearType::Triple,
+            (TSpin::None, _) => ClearType::Tetris,
+            (TSpin::Mini, 0) => ClearType::MiniTSpin,
+            (TSpin::Mini, 1) => ClearType::MiniTSpinSingle,
+            (TSpin::Mini, _) => ClearType::MiniTSpinDouble,
+            (TSpin::Full, 0) => ClearType::TSpin,
+            (TSpin::Full, 1) => ClearType::TSpinSingle,
+            (TSpin::Full, 2) => ClearType::TSpinDouble,
+            (TSpin::Full, _) => ClearType::TSpinTriple,
+        };
+        Some(kind)
+    }
+
+    /// The display name of the clear type.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClearType::Single => "Single",
+            ClearType::Double => "Double",
+            ClearType::Triple => "Triple",
+            ClearType::Tetris => "Tetris",
+            ClearType::MiniTSpin => "Mini T-Spin",
+            ClearType::MiniTSpinSingle => "Mini TSS",
+            ClearType::MiniTSpinDouble => "Mini TSD",
+            ClearType::TSpin => "T-Spin",
+            ClearType::TSpinSingle => "TSS",
+            ClearType::TSpinDouble => "TSD",
+            ClearType::TSpinTriple => "TST",
+        }
+    }
+}
+
+/// Running totals of a game.
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    /// Pieces locked.
+    pub pieces: u32,
+    /// Buttons pressed, each press counted once however long it is held.
+    pub keys: u32,
+    /// Garbage lines the clears made would send, see [`attack`].
+    pub attack: u32,
+    /// Placements of every clear type, indexed like [`ClearType::ALL`].
+    pub clears: [u32; ClearType::ALL.len()],
+    pub perfect_clears: u32,
+    /// The most clears in a row, less one.
+    pub max_combo: u32,
+    /// The back-to-back clears in the current chain of difficult clears.
+    pub back_to_back: u32,
+    /// The longest chain of back-to-back clears.
+    pub max_back_to_back: u32,
+}
+
+impl Stats {
+    /// Counts the buttons pressed on a frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `pressed` - The buttons pressed since the previous frame
+    pub fn record_keys(&mut self, pressed: Input) {
+        self.keys += (pressed.0 & !Input::TARGET.0).count_ones();
+    }
+
+    /// Counts a piece locking.
+    ///
+    /// # Arguments
+    ///
+    /// * `tspin` - The T-spin the piece counted as
+    /// * `clear` - The lines it cleared, `None` if it cleared nothing
+    pub fn record_lock(&mut self, tspin: TSpin, clear: Option<&LineClear>) {
+        self.pieces += 1;
+        let lines = clear.map_or(0, |clear| clear.lines);
+        if let Some(kind) = ClearType::of(lines, tspin) {
+            self.clears[kind as usize] += 1;
+        }
+        let Some(clear) = clear else {
+            return;
+        };
+        self.attack += attack(clear);
+        self.max_combo = self.max_combo.max(clear.streak);
+        if clear.back_to_back {
+            self.back_to_back += 1;
+            self.max_back_to_back = self.max_back_to_back.max(self.back_to_back);
+        } else if !clear.is_difficult() {
+            self.back_to_back = 0;
+        }
+        if clear.perfect_clear {
+            self.perfect_clears += 1;
+        }
+    }
+
+    /// Returns how many placements of a clear type were made.
+    pub fn count(&self, kind: ClearType) -> u32 {
+        self.clears[kind as usize]
+    }
+
+    /// Returns the pieces locked per second.
+    ///
+    /// # Arguments
+    ///
+    /// * `frames` - The frames played
+    pub fn pps(&self, frames: u64) -> f64 {
+        per_second(self.pieces, frames)
+    }
+
+    /// Returns the keys pressed per piece locked.
+    pub fn kpp(&self) -> f64 {
+        if self.pieces == 0 {
+            return 0.0;
+        }
+        f64::from(self.keys) / f64::from(self.pieces)
+    }
+
+    /// Returns the attack sent per minute.
+    ///
+    /// # Arguments
+    ///
+    /// * `frames` - The frames played
+    pub fn apm(&self, frames: u64) -> f64 {
+        per_second(self.attack, frames) * 60.0
+    }
+}
+
+fn per_second(count: u32, frames: u64) -> f64 {
+    if frames == 0 {
+        return 0.0;
+    }
+    f64::from(count) * FRAME_RATE / frames as f64
+}
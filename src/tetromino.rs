@@ -1,4 +1,4 @@
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Shape {
     I,
     O,
@@ -9,12 +9,33 @@ pub enum Shape {
     L,
 }
 
+/// How pieces rotate, which decides both the shape of every rotation state
+/// and where a blocked rotation is retried.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum RotationSystem {
+    /// The original rotation states. Blocked rotations fail.
+    Classic,
+    /// The original rotation states. Blocked rotations are retried one
+    /// column to the right, then one column to the left. The I piece never
+    /// kicks.
+    Ars,
+    /// Super Rotation System: true rotation about the piece centre and the
+    /// guideline wall kick tables.
+    Srs,
+}
+
+#[derive(Debug, Clone)]
 pub struct Tetromino {
     pub pos: (i32, i32),
     pub shape: Shape,
     pub rotation: u8,
+    pub rotation_system: RotationSystem,
+    /// The size of every mino in board cells: 1 normally, 2 in Big mode.
+    /// Position and movement are in units of minos.
+    pub scale: i32,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Direction {
     Left,
     Down,
@@ -28,6 +49,19 @@ impl Tetromino {
             pos: initial_cords,
             shape: piece_shape,
             rotation: 0,
+            rotation_system: RotationSystem::Classic,
+            scale: 1,
+        }
+    }
+
+    pub fn with_rotation_system(
+        initial_cords: (i32, i32),
+        piece_shape: Shape,
+        rotation_system: RotationSystem,
+    ) -> Self {
+        Self {
+            rotation_system,
+            ..Self::new(initial_cords, piece_shape)
         }
     }
 
@@ -57,7 +91,118 @@ impl Tetromino {
         self.rotation = (self.rotation + 3) % 4;
     }
     pub fn get_cords(&self) -> [(i32, i32); 4] {
-        get_shape(&self.shape, self.rotation).map(|(x, y)| (x + self.pos.0, y + self.pos.1))
+        let cells = match self.rotation_system {
+            RotationSystem::Classic | RotationSystem::Ars => get_shape(&self.shape, self.rotation),
+            RotationSystem::Srs => get_srs_shape(&self.shape, self.rotation),
+        };
+        cells.map(|(x, y)| (x + self.pos.0, y + self.pos.1))
+    }
+
+    /// Returns the board cells covered by the piece: every mino from
+    /// [`get_cords`](Tetromino::get_cords) expanded to a `scale` x `scale` block.
+    pub fn cells(&self) -> impl Iterator<Item = (i32, i32)> + use<> {
+        let scale = self.scale;
+        self.get_cords().into_iter().flat_map(move |(x, y)| {
+            (0..scale * scale).map(move |i| (x * scale + i % scale, y * scale + i / scale))
+        })
+    }
+
+    /// Returns the board cells covered by the piece as row bitmasks.
+    ///
+    /// # Returns
+    ///
+    /// The leftmost column and top row covered, and a mask for every row
+    /// from the top one, with bit `i` set when column `left + i` is covered.
+    /// Masks past the bottom row of the piece are `0`.
+    pub fn row_masks(&self) -> (i32, i32, [u32; 8]) {
+        let cords = self.get_cords();
+        let left = cords.iter().map(|&(x, _)| x).min().unwrap_or(0);
+        let top = cords.iter().map(|&(_, y)| y).min().unwrap_or(0);
+        let scale = self.scale;
+        let block = (1 << scale) - 1;
+        let mut masks = [0; 8];
+        for (x, y) in cords {
+            let row = ((y - top) * scale) as usize;
+            for mask in &mut masks[row..row + scale as usize] {
+                *mask |= block << ((x - left) * scale);
+            }
+        }
+        (left * scale, top * scale, masks)
+    }
+
+    /// Returns the offsets to try, in order, when rotating from `from` to the
+    /// piece's current rotation. The first offset is always `(0, 0)`.
+    pub fn kicks(&self, from: u8) -> &'static [(i32, i32)] {
+        match self.rotation_system {
+            RotationSystem::Classic => &[(0, 0)],
+            RotationSystem::Ars => match self.shape {
+                Shape::I => &[(0, 0)],
+                _ => &[(0, 0), (1, 0), (-1, 0)],
+            },
+            RotationSystem::Srs => get_srs_kicks(&self.shape, from, self.rotation),
+        }
+    }
+}
+
+/// SRS rotation states, in a 3x3 box (4x4 for I) with y pointing down.
+pub fn get_srs_shape(piece_shape: &Shape, rot: u8) -> [(i32, i32); 4] {
+    match (piece_shape, rot) {
+        (Shape::I, 0) => [(0, 1), (1, 1), (2, 1), (3, 1)],
+        (Shape::I, 1) => [(2, 0), (2, 1), (2, 2), (2, 3)],
+        (Shape::I, 2) => [(0, 2), (1, 2), (2, 2), (3, 2)],
+        (Shape::I, 3) => [(1, 0), (1, 1), (1, 2), (1, 3)],
+
+        (Shape::O, 0..=3) => [(0, 0), (1, 0), (0, 1), (1, 1)],
+
+        (Shape::T, 0) => [(1, 0), (0, 1), (1, 1), (2, 1)],
+        (Shape::T, 1) => [(1, 0), (1, 1), (2, 1), (1, 2)],
+        (Shape::T, 2) => [(0, 1), (1, 1), (2, 1), (1, 2)],
+        (Shape::T, 3) => [(1, 0), (0, 1), (1, 1), (1, 2)],
+
+        (Shape::S, 0) => [(1, 0), (2, 0), (0, 1), (1, 1)],
+        (Shape::S, 1) => [(1, 0), (1, 1), (2, 1), (2, 2)],
+        (Shape::S, 2) => [(1, 1), (2, 1), (0, 2), (1, 2)],
+        (Shape::S, 3) => [(0, 0), (0, 1), (1, 1), (1, 2)],
+
+        (Shape::Z, 0) => [(0, 0), (1, 0), (1, 1), (2, 1)],
+        (Shape::Z, 1) => [(2, 0), (1, 1), (2, 1), (1, 2)],
+        (Shape::Z, 2) => [(0, 1), (1, 1), (1, 2), (2, 2)],
+        (Shape::Z, 3) => [(1, 0), (0, 1), (1, 1), (0, 2)],
+
+        (Shape::J, 0) => [(0, 0), (0, 1), (1, 1), (2, 1)],
+        (Shape::J, 1) => [(1, 0), (2, 0), (1, 1), (1, 2)],
+        (Shape::J, 2) => [(0, 1), (1, 1), (2, 1), (2, 2)],
+        (Shape::J, 3) => [(1, 0), (1, 1), (0, 2), (1, 2)],
+
+        (Shape::L, 0) => [(2, 0), (0, 1), (1, 1), (2, 1)],
+        (Shape::L, 1) => [(1, 0), (1, 1), (1, 2), (2, 2)],
+        (Shape::L, 2) => [(0, 1), (1, 1), (2, 1), (0, 2)],
+        (Shape::L, 3) => [(0, 0), (1, 0), (1, 1), (1, 2)],
+
+        (_, _) => panic!("Invalid rotation state"),
+    }
+}
+
+/// SRS wall kick offsets for a rotation from `from` to `to`, with y pointing down.
+pub fn get_srs_kicks(piece_shape: &Shape, from: u8, to: u8) -> &'static [(i32, i32)] {
+    match (piece_shape, from, to) {
+        (Shape::O, _, _) => &[(0, 0)],
+
+        (Shape::I, 0, 1) => &[(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)],
+        (Shape::I, 1, 0) => &[(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)],
+        (Shape::I, 1, 2) => &[(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],
+        (Shape::I, 2, 1) => &[(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)],
+        (Shape::I, 2, 3) => &[(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)],
+        (Shape::I, 3, 2) => &[(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)],
+        (Shape::I, 3, 0) => &[(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)],
+        (Shape::I, 0, 3) => &[(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],
+        (Shape::I, _, _) => &[(0, 0)],
+
+        (_, 0, 1) | (_, 2, 1) => &[(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+        (_, 1, 0) | (_, 1, 2) => &[(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+        (_, 2, 3) | (_, 0, 3) => &[(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+        (_, 3, 2) | (_, 3, 0) => &[(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+        (_, _, _) => &[(0, 0)],
     }
 }
 
@@ -0,0 +1,546 @@
+//! Tune module evolving the weights of the bot.
+//!
+//! Every candidate set of [`Weights`] is scored by letting a [`Bot`] play a
+//! batch of headless seeded games with it, while garbage keeps arriving at a
+//! steady rate like it would from an opponent. A candidate's [`Fitness`]
+//! rewards both the lines it clears before topping out and the attack it
+//! makes per piece. The games of a generation are spread over threads, and
+//! every candidate of a generation plays the same seeds so they are compared
+//! fairly.
+//!
+//! Candidates are evolved either by a genetic algorithm or by CMA-ES, and the
+//! best weights found so far are written to a file after every generation in
+//! the format read by [`Weights::parse`].
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+use crate::bot::{Bot, Weights};
+use crate::game::{Game, GameState};
+use crate::garbage::{GarbageHoles, HoleStrategy};
+use crate::ruleset::{FRAME_RATE, Ruleset};
+use crate::versus::attack;
+
+/// How many weights are tuned.
+const DIMENSIONS: usize = Weights::NAMES.len();
+
+/// The most lines a piece can clear on average: its four cells over the ten
+/// of a row.
+const LINES_PER_PIECE: f64 = 0.4;
+
+/// Where the holes of the garbage pushed during tuning games go.
+const PRESSURE_GARBAGE: HoleStrategy = HoleStrategy::Messy(30);
+
+/// How many of the best candidates the genetic algorithm keeps unchanged.
+const ELITES: usize = 2;
+
+/// How many candidates compete for each parent in the genetic algorithm.
+const TOURNAMENT: usize = 3;
+
+/// The chance of each weight of a child being mutated.
+const MUTATION_RATE: f64 = 0.3;
+
+/// The step size CMA-ES starts with.
+const INITIAL_SIGMA: f64 = 0.5;
+
+/// How candidate weights are evolved.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Tournament selection, uniform crossover and gaussian mutation.
+    Genetic,
+    /// The covariance matrix adaptation evolution strategy.
+    CmaEs,
+}
+
+impl Algorithm {
+    /// Parses an algorithm from its command line name, `ga` or `cmaes`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rust_tetris::tune::Algorithm;
+    /// assert_eq!(Algorithm::parse("cmaes"), Ok(Algorithm::CmaEs));
+    /// assert!(Algorithm::parse("annealing").is_err());
+    /// ```
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text {
+            "ga" => Ok(Algorithm::Genetic),
+            "cmaes" => Ok(Algorithm::CmaEs),
+            _ => Err(format!("Unknown algorithm '{}'", text)),
+        }
+    }
+}
+
+/// The options a tuning run uses.
+pub struct TuneConfig {
+    pub algorithm: Algorithm,
+    /// How many generations to evolve.
+    pub generations: u32,
+    /// How many candidates are scored every generation.
+    pub population: usize,
+    /// How many games every candidate plays per generation.
+    pub games: usize,
+    /// How many pieces a game lasts at most.
+    pub pieces: u32,
+    /// How many garbage lines arrive per piece placed.
+    pub pressure: f64,
+    /// How many preview pieces the bot searches, see [`Bot::lookahead`].
+    pub lookahead: usize,
+    /// How many games are played at once.
+    pub threads: usize,
+    /// The seed of the games and of the evolution.
+    pub seed: u64,
+    /// The file the best weights are written to.
+    pub output: PathBuf,
+}
+
+/// How well a candidate played, averaged over its games.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct Fitness {
+    /// Lines cleared before topping out or running out of pieces.
+    pub lines: f64,
+    /// Pieces placed.
+    pub pieces: f64,
+    /// Garbage lines sent per piece placed, see [`attack`].
+    pub attack_per_piece: f64,
+}
+
+impl Fitness {
+    /// Returns the single number candidates are ranked by: the share of
+    /// the most lines the game allowed that were cleared, plus the attack
+    /// per piece.
+    ///
+    /// # Arguments
+    ///
+    /// * `pieces` - The most pieces a game lasts
+    pub fn score(&self, pieces: u32) -> f64 {
+        self.lines / (pieces as f64 * LINES_PER_PIECE) + self.attack_per_piece
+    }
+}
+
+/// Plays one headless game with a set of weights.
+///
+/// # Arguments
+///
+/// * `weights` - The weights the bot plays with
+/// * `config` - The options of the tuning run
+/// * `seed` - The seed of the pieces and garbage
+///
+/// # Returns
+///
+/// The lines cleared, pieces placed and garbage lines sent.
+pub fn play(weights: Weights, config: &TuneConfig, seed: u64) -> (u32, u32, u32) {
+    let mut game = Game::new(Ruleset::versus(), seed);
+    let mut bot = Bot::new(weights, f64::INFINITY);
+    bot.lookahead = config.lookahead;
+    let mut holes = GarbageHoles::new(PRESSURE_GARBAGE, seed);
+    let mut owed = 0.0;
+    let mut sent = 0;
+    // A bot that stops placing pieces must not hang the run
+    let max_frames = config.pieces as u64 * FRAME_RATE as u64;
+
+    while matches!(game.state, GameState::Playing)
+        && game.pieces < config.pieces
+        && game.frame < max_frames
+    {
+        let pieces = game.pieces;
+        let input = bot.update(&game);
+        game.update(input);
+        if game.pieces == pieces {
+            continue;
+        }
+        if let Some(clear) = game.last_clear {
+            let lines = attack(&clear);
+            sent += lines;
+            game.cancel_garbage(lines);
+        }
+        owed += config.pressure;
+        if owed >= 1.0 {
+            let width = game.board.width();
+            game.incoming.extend(holes.attack(owed as u32, width));
+            owed = owed.fract();
+        }
+    }
+    (game.lines, game.pieces, sent)
+}
+
+/// Scores every candidate of a generation, playing their games on
+/// [`TuneConfig::threads`] threads.
+///
+/// # Arguments
+///
+/// * `candidates` - The weights to score
+/// * `config` - The options of the tuning run
+/// * `generation` - The generation, which decides the seeds played
+pub fn evaluate(candidates: &[Weights], config: &TuneConfig, generation: u32) -> Vec<Fitness> {
+    let jobs = candidates.len() * config.games;
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(vec![(0, 0, 0); jobs]);
+    thread::scope(|scope| {
+        for _ in 0..config.threads.max(1) {
+            scope.spawn(|| {
+                loop {
+                    let job = next.fetch_add(1, Ordering::Relaxed);
+                    if job >= jobs {
+                        break;
+                    }
+                    let (candidate, game) = (job / config.games, job % config.games);
+                    let seed = config
+                        .seed
+                        .wrapping_add(generation as u64 * config.games as u64 + game as u64);
+                    let result = play(candidates[candidate], config, seed);
+                    results.lock().unwrap()[job] = result;
+                }
+            });
+        }
+    });
+
+    let results = results.into_inner().unwrap();
+    results
+        .chunks(config.games.max(1))
+        .map(|games| {
+            let count = games.len() as f64;
+            let lines: u32 = games.iter().map(|game| game.0).sum();
+            let pieces: u32 = games.iter().map(|game| game.1).sum();
+            let sent: u32 = games.iter().map(|game| game.2).sum();
+            Fitness {
+                lines: lines as f64 / count,
+                pieces: pieces as f64 / count,
+                attack_per_piece: sent as f64 / pieces.max(1) as f64,
+            }
+        })
+        .collect()
+}
+
+/// Runs a tuning run to the end, reporting every generation on stdout.
+///
+/// # Arguments
+///
+/// * `config` - The options to run with
+///
+/// # Returns
+///
+/// A description of the error that stopped the run.
+pub fn run(config: TuneConfig) -> Result<(), String> {
+    if config.population < 2 || config.games == 0 {
+        return Err("The population needs two candidates and at least one game".to_string());
+    }
+    let mut rng = fastrand::Rng::with_seed(config.seed);
+    let mut cma = CmaEs::new(Weights::default().to_array(), config.population);
+    let mut population = initial_population(&mut rng, config.population);
+    let mut best: Option<(f64, Weights)> = None;
+
+    for generation in 0..config.generations {
+        if config.algorithm == Algorithm::CmaEs {
+            population = cma.sample(&mut rng);
+        }
+        let candidates: Vec<Weights> = population
+            .iter()
+            .copied()
+            .map(Weights::from_array)
+            .collect();
+        let fitness = evaluate(&candidates, &config, generation);
+        let scores: Vec<f64> = fitness.iter().map(|fit| fit.score(config.pieces)).collect();
+        let mut ranked: Vec<usize> = (0..population.len()).collect();
+        ranked.sort_by(|&a, &b| scores[b].total_cmp(&scores[a]));
+
+        let top = ranked[0];
+        let mean = scores.iter().sum::<f64>() / scores.len() as f64;
+        println!(
+            "Generation {}: best {:.3} (lines {:.1}, pieces {:.1}, attack/piece {:.3}), mean {:.3}",
+            generation + 1,
+            scores[top],
+            fitness[top].lines,
+            fitness[top].pieces,
+            fitness[top].attack_per_piece,
+            mean
+        );
+        if best.is_none_or(|(score, _)| scores[top] > score) {
+            best = Some((scores[top], candidates[top]));
+            let text = format!(
+                "# Generation {}, fitness {:.3}: lines {:.1}, attack/piece {:.3}\n{}",
+                generation + 1,
+                scores[top],
+                fitness[top].lines,
+                fitness[top].attack_per_piece,
+                candidates[top].to_text()
+            );
+            fs::write(&config.output, text)
+                .map_err(|err| format!("Could not write {}: {}", config.output.display(), err))?;
+        }
+
+        let ranked: Vec<[f64; DIMENSIONS]> = ranked.iter().map(|&i| population[i]).collect();
+        population = match config.algorithm {
+            Algorithm::Genetic => breed(&mut rng, &ranked),
+            Algorithm::CmaEs => {
+                cma.update(&ranked);
+                ranked
+            }
+        };
+    }
+    if let Some((score, _)) = best {
+        println!(
+            "Best fitness {:.3}, weights written to {}",
+            score,
+            config.output.display()
+        );
+    }
+    Ok(())
+}
+
+/// Returns the default weights followed by random variations of them.
+fn initial_population(rng: &mut fastrand::Rng, size: usize) -> Vec<[f64; DIMENSIONS]> {
+    let default = Weights::default().to_array();
+    let mut population = vec![default];
+    while population.len() < size {
+        population.push(default.map(|weight| mutate(rng, weight)));
+    }
+    population
+}
+
+/// Returns the next generation of the genetic algorithm.
+///
+/// # Arguments
+///
+/// * `ranked` - The current generation, best first
+fn breed(rng: &mut fastrand::Rng, ranked: &[[f64; DIMENSIONS]]) -> Vec<[f64; DIMENSIONS]> {
+    let mut next: Vec<[f64; DIMENSIONS]> = ranked.iter().take(ELITES).copied().collect();
+    while next.len() < ranked.len() {
+        // The lowest index of a few random picks is the fittest of them
+        let mut parent = || {
+            ranked[(0..TOURNAMENT)
+                .map(|_| rng.usize(..ranked.len()))
+                .min()
+                .unwrap()]
+        };
+        let (first, second) = (parent(), parent());
+        let mut child = [0.0; DIMENSIONS];
+        for i in 0..DIMENSIONS {
+            child[i] = if rng.bool() { first[i] } else { second[i] };
+            if rng.f64() < MUTATION_RATE {
+                child[i] = mutate(rng, child[i]);
+            }
+        }
+        next.push(child);
+    }
+    next
+}
+
+/// Returns a weight moved by a random step proportional to its size.
+fn mutate(rng: &mut fastrand::Rng, weight: f64) -> f64 {
+    weight + gaussian(rng) * (0.2 * weight.abs() + 0.05)
+}
+
+/// Returns a sample of the standard normal distribution.
+fn gaussian(rng: &mut fastrand::Rng) -> f64 {
+    // Box-Muller transform, keeping the logarithm away from zero
+    let radius = (-2.0 * (1.0 - rng.f64()).ln()).sqrt();
+    radius * (std::f64::consts::TAU * rng.f64()).cos()
+}
+
+type Vector = [f64; DIMENSIONS];
+type Matrix = [[f64; DIMENSIONS]; DIMENSIONS];
+
+/// The state of a CMA-ES search, following Hansen's "The CMA Evolution
+/// Strategy: A Tutorial".
+struct CmaEs {
+    mean: Vector,
+    sigma: f64,
+    covariance: Matrix,
+    /// The eigenvectors of the covariance, as columns.
+    basis: Matrix,
+    /// The square roots of the eigenvalues of the covariance.
+    scales: Vector,
+    path_c: Vector,
+    path_sigma: Vector,
+    lambda: usize,
+    /// The recombination weights of the best half of a generation.
+    recombination: Vec<f64>,
+    mueff: f64,
+    cc: f64,
+    cs: f64,
+    c1: f64,
+    cmu: f64,
+    damps: f64,
+    chi_n: f64,
+    generation: u32,
+}
+
+impl CmaEs {
+    fn new(mean: Vector, lambda: usize) -> Self {
+        let n = DIMENSIONS as f64;
+        let mu = lambda / 2;
+        let raw: Vec<f64> = (0..mu)
+            .map(|i| (mu as f64 + 0.5).ln() - ((i + 1) as f64).ln())
+            .collect();
+        let total: f64 = raw.iter().sum();
+        let recombination: Vec<f64> = raw.iter().map(|weight| weight / total).collect();
+        let mueff = 1.0
+            / recombination
+                .iter()
+                .map(|weight| weight * weight)
+                .sum::<f64>();
+        let cs = (mueff + 2.0) / (n + mueff + 5.0);
+        let c1 = 2.0 / ((n + 1.3).powi(2) + mueff);
+        let mut identity = [[0.0; DIMENSIONS]; DIMENSIONS];
+        for (i, row) in identity.iter_mut().enumerate() {
+            row[i] = 1.0;
+        }
+        Self {
+            mean,
+            sigma: INITIAL_SIGMA,
+            covariance: identity,
+            basis: identity,
+            scales: [1.0; DIMENSIONS],
+            path_c: [0.0; DIMENSIONS],
+            path_sigma: [0.0; DIMENSIONS],
+            lambda,
+            recombination,
+            mueff,
+            cc: (4.0 + mueff / n) / (n + 4.0 + 2.0 * mueff / n),
+            cs,
+            c1,
+            cmu: (1.0 - c1).min(2.0 * (mueff - 2.0 + 1.0 / mueff) / ((n + 2.0).powi(2) + mueff)),
+            damps: 1.0 + 2.0 * (((mueff - 1.0) / (n + 1.0)).sqrt() - 1.0).max(0.0) + cs,
+            chi_n: n.sqrt() * (1.0 - 1.0 / (4.0 * n) + 1.0 / (21.0 * n * n)),
+            generation: 0,
+        }
+    }
+
+    /// Draws a generation of candidates around the mean.
+    fn sample(&self, rng: &mut fastrand::Rng) -> Vec<Vector> {
+        (0..self.lambda)
+            .map(|_| {
+                let z: Vector = std::array::from_fn(|_| gaussian(rng));
+                let y = self.transform(|i| self.scales[i] * z[i]);
+                std::array::from_fn(|i| self.mean[i] + self.sigma * y[i])
+            })
+            .collect()
+    }
+
+    /// Returns the basis times a vector given by its components.
+    fn transform(&self, component: impl Fn(usize) -> f64) -> Vector {
+        let components: Vector = std::array::from_fn(component);
+        std::array::from_fn(|i| {
+            (0..DIMENSIONS)
+                .map(|j| self.basis[i][j] * components[j])
+                .sum()
+        })
+    }
+
+    /// Moves the search towards the best candidates of a generation.
+    ///
+    /// # Arguments
+    ///
+    /// * `ranked` - The candidates drawn by [`CmaEs::sample`], best first
+    fn update(&mut self, ranked: &[Vector]) {
+        let n = DIMENSIONS as f64;
+        self.generation += 1;
+        let old = self.mean;
+        self.mean = std::array::from_fn(|i| {
+            self.recombination
+                .iter()
+                .zip(ranked)
+                .map(|(weight, x)| weight * x[i])
+                .sum()
+        });
+        let step: Vector = std::array::from_fn(|i| (self.mean[i] - old[i]) / self.sigma);
+
+        // The step with the covariance undone: B D^-1 B^T step
+        let rotated: Vector =
+            std::array::from_fn(|j| (0..DIMENSIONS).map(|i| self.basis[i][j] * step[i]).sum());
+        let whitened = self.transform(|j| rotated[j] / self.scales[j]);
+        let cs_factor = (self.cs * (2.0 - self.cs) * self.mueff).sqrt();
+        for (path, white) in self.path_sigma.iter_mut().zip(whitened) {
+            *path = (1.0 - self.cs) * *path + cs_factor * white;
+        }
+        let norm = self.path_sigma.iter().map(|x| x * x).sum::<f64>().sqrt();
+        let decay = 1.0 - (1.0 - self.cs).powi(2 * self.generation as i32);
+        let hsig = norm / decay.sqrt() / self.chi_n < 1.4 + 2.0 / (n + 1.0);
+        let cc_factor = (self.cc * (2.0 - self.cc) * self.mueff).sqrt();
+        for (path, step) in self.path_c.iter_mut().zip(step) {
+            *path = (1.0 - self.cc) * *path + if hsig { cc_factor * step } else { 0.0 };
+        }
+
+        let steps: Vec<Vector> = ranked
+            .iter()
+            .take(self.recombination.len())
+            .map(|x| std::array::from_fn(|i| (x[i] - old[i]) / self.sigma))
+            .collect();
+        let correction = if hsig {
+            0.0
+        } else {
+            self.c1 * self.cc * (2.0 - self.cc)
+        };
+        for i in 0..DIMENSIONS {
+            for j in 0..DIMENSIONS {
+                let rank_mu: f64 = self
+                    .recombination
+                    .iter()
+                    .zip(&steps)
+                    .map(|(weight, y)| weight * y[i] * y[j])
+                    .sum();
+                self.covariance[i][j] = (1.0 - self.c1 - self.cmu + correction)
+                    * self.covariance[i][j]
+                    + self.c1 * self.path_c[i] * self.path_c[j]
+                    + self.cmu * rank_mu;
+            }
+        }
+        self.sigma *= ((self.cs / self.damps) * (norm / self.chi_n - 1.0)).exp();
+
+        let (values, vectors) = eigen(self.covariance);
+        self.basis = vectors;
+        self.scales = values.map(|value| value.max(1e-20).sqrt());
+    }
+}
+
+/// Returns the eigenvalues and eigenvectors, as columns, of a symmetric
+/// matrix, using the cyclic Jacobi method.
+fn eigen(mut matrix: Matrix) -> (Vector, Matrix) {
+    let mut vectors = [[0.0; DIMENSIONS]; DIMENSIONS];
+    for (i, row) in vectors.iter_mut().enumerate() {
+        row[i] = 1.0;
+    }
+    for _ in 0..100 {
+        let off: f64 = (0..DIMENSIONS)
+            .flat_map(|i| {
+                (0..DIMENSIONS)
+                    .filter(move |&j| j != i)
+                    .map(move |j| (i, j))
+            })
+            .map(|(i, j)| matrix[i][j] * matrix[i][j])
+            .sum();
+        if off < 1e-22 {
+            break;
+        }
+        for p in 0..DIMENSIONS {
+            for q in p + 1..DIMENSIONS {
+                if matrix[p][q].abs() < 1e-300 {
+                    continue;
+                }
+                let theta = (matrix[q][q] - matrix[p][p]) / (2.0 * matrix[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+                let c = 1.0 / (t * t + 1.0).sqrt();
+                let s = t * c;
+                // Rotate columns p and q, then rows p and q
+                for row in matrix.iter_mut() {
+                    let (kp, kq) = (row[p], row[q]);
+                    row[p] = c * kp - s * kq;
+                    row[q] = s * kp + c * kq;
+                }
+                let (row_p, row_q) = (matrix[p], matrix[q]);
+                matrix[p] = std::array::from_fn(|k| c * row_p[k] - s * row_q[k]);
+                matrix[q] = std::array::from_fn(|k| s * row_p[k] + c * row_q[k]);
+                for row in vectors.iter_mut() {
+                    let (kp, kq) = (row[p], row[q]);
+                    row[p] = c * kp - s * kq;
+                    row[q] = s * kp + c * kq;
+                }
+            }
+        }
+    }
+    (std::array::from_fn(|i| matrix[i][i]), vectors)
+}
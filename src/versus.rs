@@ -0,0 +1,142 @@
+//! Versus module pitting two games against each other.
+//!
+//! Line clears are turned into attacks with [`attack`]. An attack first
+//! cancels garbage waiting in the attacker's own queue, and whatever is left
+//! is queued for the opponent, who receives it the next time they lock a
+//! piece without clearing a line. The last player standing wins.
+//!
+//! Both games are driven by [`Versus::update`] with one [`Input`] per player,
+//! so a match only depends on its seed and the inputs it was given.
+
+use crate::game::{Game, GameState, LineClear, TSpin};
+use crate::garbage::GarbageHoles;
+use crate::input::Input;
+use crate::ruleset::Ruleset;
+
+/// Extra lines sent for consecutive clears, indexed by the clear streak.
+const COMBO_TABLE: [u32; 12] = [0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+
+/// Lines sent for clearing the whole board, on top of the clear itself.
+const PERFECT_CLEAR_BONUS: u32 = 10;
+
+/// Returns how many garbage lines a line clear sends.
+///
+/// # Arguments
+///
+/// * `clear` - The line clear made by the attacker
+///
+/// # Examples
+///
+/// ```
+/// # use rust_tetris::game::{LineClear, TSpin};
+/// # use rust_tetris::versus::attack;
+/// // A back-to-back tetris
+/// let clear = LineClear {
+///     lines: 4,
+///     level: 0,
+///     combo: 1,
+///     soft_drop_frames: 0,
+///     perfect_clear: false,
+///     tspin: TSpin::None,
+///     streak: 0,
+///     back_to_back: true,
+/// };
+/// assert_eq!(attack(&clear), 5);
+/// ```
+pub fn attack(clear: &LineClear) -> u32 {
+    let base = match (clear.tspin, clear.lines) {
+        (TSpin::Full, lines) => 2 * lines,
+        (TSpin::Mini, lines) => lines - 1,
+        (TSpin::None, 4) => 4,
+        (TSpin::None, lines) => lines - 1,
+    };
+    let back_to_back = u32::from(clear.back_to_back);
+    let combo = COMBO_TABLE[(clear.streak as usize).min(COMBO_TABLE.len() - 1)];
+    let perfect_clear = if clear.perfect_clear {
+        PERFECT_CLEAR_BONUS
+    } else {
+        0
+    };
+    base + back_to_back + combo + perfect_clear
+}
+
+/// A local match between two players.
+pub struct Versus {
+    pub players: [Game; 2],
+    /// The index of the winning player once the match is over.
+    pub winner: Option<usize>,
+    /// The total lines each player has sent.
+    pub sent: [u32; 2],
+    holes: GarbageHoles,
+}
+
+impl Versus {
+    /// Creates a new match.
+    ///
+    /// Both players are dealt the same pieces.
+    ///
+    /// # Arguments
+    ///
+    /// * `ruleset` - The rules both players play by
+    /// * `seed` - The seed for the pieces and the garbage holes
+    pub fn new(ruleset: Ruleset, seed: u64) -> Self {
+        Self {
+            players: [Game::new(ruleset, seed), Game::new(ruleset, seed)],
+            winner: None,
+            sent: [0; 2],
+            holes: GarbageHoles::new(ruleset.garbage, seed),
+        }
+    }
+
+    /// Returns whether the match has ended.
+    pub fn is_over(&self) -> bool {
+        self.players
+            .iter()
+            .any(|game| !matches!(game.state, GameState::Playing))
+    }
+
+    /// Advances both games by one frame and exchanges garbage.
+    ///
+    /// # Arguments
+    ///
+    /// * `inputs` - The buttons held by each player during this frame
+    pub fn update(&mut self, inputs: [Input; 2]) {
+        if self.is_over() {
+            return;
+        }
+        for (player, input) in inputs.into_iter().enumerate() {
+            let pieces = self.players[player].pieces;
+            self.players[player].update(input);
+            if self.players[player].pieces == pieces {
+                continue;
+            }
+            if let Some(clear) = self.players[player].last_clear {
+                let lines = self.players[player].cancel_garbage(attack(&clear));
+                self.send(1 - player, lines);
+            }
+        }
+
+        let alive = self
+            .players
+            .each_ref()
+            .map(|game| matches!(game.state, GameState::Playing));
+        self.winner = match alive {
+            [true, false] => Some(0),
+            [false, true] => Some(1),
+            // Both topped out on the same frame: the player who sent more wins
+            [false, false] => Some(usize::from(self.sent[1] > self.sent[0])),
+            _ => None,
+        };
+    }
+
+    fn send(&mut self, target: usize, lines: u32) {
+        if lines == 0 {
+            return;
+        }
+        self.sent[1 - target] += lines;
+        let width = self.players[target].board.width();
+        self.players[target]
+            .incoming
+            .extend(self.holes.attack(lines, width));
+    }
+}
@@ -0,0 +1,91 @@
+//! Plays a networked versus match between two sessions on localhost.
+
+use std::collections::HashMap;
+use std::sync::atomic::AtomicBool;
+use std::thread;
+use std::time::Duration;
+
+use rust_tetris::board::BOARD_HEIGHT;
+use rust_tetris::garbage::HoleStrategy;
+use rust_tetris::input::Input;
+use rust_tetris::net::NetSession;
+
+const ADDR: &str = "127.0.0.1:47878";
+
+/// How many frames each side plays.
+const FRAMES: u64 = 400;
+
+/// The buttons a player holds on a frame: some movement, rotations and a
+/// hard drop every half second, so that garbage is sent both ways.
+fn input(player: u64, frame: u64) -> Input {
+    match (frame + player * 7) % 30 {
+        0 => Input::HARD_DROP,
+        3..=5 if player == 0 => Input::LEFT,
+        3..=5 => Input::RIGHT,
+        10 => Input::ROTATE_CW,
+        20 => Input::ROTATE_CCW,
+        _ => Input::NONE,
+    }
+}
+
+/// The boards of both players at the session's current frame.
+fn boards(session: &NetSession) -> (u64, Vec<Vec<u32>>) {
+    let players = session.game.players();
+    let rows = players
+        .iter()
+        .map(|game| (0..BOARD_HEIGHT).map(|y| game.board.row(y)).collect())
+        .collect();
+    (players[0].frame, rows)
+}
+
+#[test]
+fn sessions_stay_in_sync() {
+    let host =
+        thread::spawn(|| NetSession::host(ADDR, HoleStrategy::Clean, &AtomicBool::new(false)));
+    let never = AtomicBool::new(false);
+    let mut client = (0..100)
+        .find_map(|_| {
+            thread::sleep(Duration::from_millis(20));
+            NetSession::join(ADDR, &never).ok()
+        })
+        .expect("could not join the host");
+    let mut host = host.join().unwrap().expect("could not host");
+    assert_eq!((host.player, client.player), (0, 1));
+
+    let mut seen = [HashMap::new(), HashMap::new()];
+    for frame in 0..FRAMES {
+        for (session, seen) in [&mut host, &mut client].into_iter().zip(&mut seen) {
+            session.update(input(session.player as u64, frame));
+            let (at, rows) = boards(session);
+            seen.insert(at, rows);
+        }
+        thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(host.error, None);
+    assert_eq!(client.error, None);
+
+    let mut compared = 0;
+    for (frame, rows) in &seen[0] {
+        if let Some(other) = seen[1].get(frame) {
+            assert_eq!(rows, other, "boards differ on frame {}", frame);
+            compared += 1;
+        }
+    }
+    assert!(
+        compared > FRAMES as usize / 2,
+        "only {} frames compared",
+        compared
+    );
+    let (frame, rows) = boards(&host);
+    assert!(frame > FRAMES / 2);
+    assert!(rows.iter().all(|board| board.iter().any(|&row| row != 0)));
+}
+
+#[test]
+fn cancelled_host_frees_its_address() {
+    let addr = "127.0.0.1:47879";
+    let cancel = AtomicBool::new(true);
+    let hosted = NetSession::host(addr, HoleStrategy::Clean, &cancel);
+    assert_eq!(hosted.err(), Some("Cancelled".to_string()));
+    assert!(std::net::TcpListener::bind(addr).is_ok());
+}
@@ -0,0 +1,57 @@
+//! Streams local games to a spectator on localhost.
+
+use std::thread;
+use std::time::Duration;
+
+use rust_tetris::coop::Coop;
+use rust_tetris::game::Game;
+use rust_tetris::input::Input;
+use rust_tetris::ruleset::Ruleset;
+use rust_tetris::spectate::{BoardView, Broadcaster, Spectator};
+
+/// Publishes views until the spectator sees them, or gives up.
+fn watch(broadcaster: &mut Broadcaster, spectator: &mut Spectator, views: &[BoardView]) {
+    for _ in 0..200 {
+        broadcaster.publish(views.to_vec());
+        thread::sleep(Duration::from_millis(5));
+        spectator.update();
+        if spectator.views == views {
+            return;
+        }
+    }
+    assert_eq!(spectator.views, views);
+}
+
+#[test]
+fn spectators_see_solo_and_coop_boards() {
+    let addr = "127.0.0.1:47880";
+    let mut broadcaster = Broadcaster::start(addr, Duration::ZERO).unwrap();
+    let mut spectator = Spectator::connect(addr).unwrap();
+
+    let mut game = Game::new(Ruleset::versus(), 5);
+    for _ in 0..3 {
+        game.update(Input::HARD_DROP);
+        game.update(Input::NONE);
+    }
+    watch(&mut broadcaster, &mut spectator, &[BoardView::of(&game)]);
+
+    let mut coop = Coop::new(Ruleset::coop(), 5, 20);
+    for frame in 0..120 {
+        let input = if frame % 20 == 0 {
+            Input::HARD_DROP
+        } else {
+            Input::NONE
+        };
+        coop.update([input, input]);
+    }
+    let view = BoardView::of_coop(&coop);
+    assert_eq!(view.width, 20);
+    assert_eq!(view.pieces.len(), 2);
+    assert!(
+        view.grid
+            .iter()
+            .any(|row| row[10..].iter().any(|&cell| cell))
+    );
+    watch(&mut broadcaster, &mut spectator, &[view]);
+    assert_eq!(spectator.error, None);
+}